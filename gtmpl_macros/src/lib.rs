@@ -0,0 +1,157 @@
+//! Templates for [gtmpl](https://docs.rs/crate/gtmpl) which are validated at compile time.
+//!
+//! `template!` parses a template while the crate is compiled. Syntax errors and calls of
+//! unknown functions fail the build with the line and column of the error instead of
+//! surfacing when the template is first used.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::Context;
+//! use gtmpl_macros::template;
+//!
+//! let tmpl = template!("Hello {{ . | printf \"%q\" }}!");
+//! let output = tmpl.render(&Context::from("World").unwrap());
+//! assert_eq!(&output.unwrap(), "Hello \"World\"!");
+//! ```
+//!
+//! A broken template does not compile:
+//! ```compile_fail
+//! let tmpl = gtmpl_macros::template!("{{ if . }}");
+//! ```
+//!
+//! Neither does a missing template file:
+//! ```compile_fail
+//! let tmpl = gtmpl_macros::template!(file = "templates/typo.tmpl");
+//! ```
+extern crate proc_macro;
+
+use std::path::Path;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Error, Ident, LitStr, Path as FnPath, Token};
+
+struct FuncArg {
+    name: Ident,
+    func: FnPath,
+}
+
+impl Parse for FuncArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let func = input.parse()?;
+        Ok(FuncArg { name, func })
+    }
+}
+
+enum Source {
+    Text(LitStr),
+    File(LitStr),
+}
+
+impl Parse for Source {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            return Ok(Source::Text(input.parse()?));
+        }
+        let key: Ident = input.parse()?;
+        if key != "file" {
+            return Err(Error::new(
+                key.span(),
+                "expected a template literal or `file = \"...\"`",
+            ));
+        }
+        input.parse::<Token![=]>()?;
+        Ok(Source::File(input.parse()?))
+    }
+}
+
+struct TemplateArgs {
+    source: Source,
+    funcs: Vec<FuncArg>,
+}
+
+impl Parse for TemplateArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = input.parse()?;
+        let mut funcs = vec![];
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            funcs.extend(Punctuated::<FuncArg, Token![,]>::parse_terminated(input)?);
+        }
+        Ok(TemplateArgs { source, funcs })
+    }
+}
+
+/// Expands to a `&'static gtmpl::Template` parsed on first use.
+///
+/// The argument is either the template text itself or `file = "path"` naming a template
+/// file relative to the directory of the crate's `Cargo.toml`. Custom functions are passed
+/// as `name = path` and may be used by the template:
+///
+/// ```rust
+/// use gtmpl::{Context, Value};
+/// use gtmpl_macros::template;
+///
+/// fn twice(args: &[Value]) -> Result<Value, String> {
+///     Ok(Value::from(format!("{0}{0}", args[0])))
+/// }
+///
+/// let tmpl = template!("{{ twice . }}", twice = twice);
+/// assert_eq!(&tmpl.render(&Context::from("ab").unwrap()).unwrap(), "abab");
+/// ```
+#[proc_macro]
+pub fn template(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as TemplateArgs);
+    match expand(&args) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(args: &TemplateArgs) -> syn::Result<proc_macro2::TokenStream> {
+    let (lit, text, source) = match args.source {
+        Source::Text(ref lit) => (lit, lit.value(), quote!(#lit)),
+        Source::File(ref lit) => {
+            let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+            let path = Path::new(&dir).join(lit.value());
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                Error::new(
+                    lit.span(),
+                    format!("unable to read template file {}: {}", lit.value(), e),
+                )
+            })?;
+            let path = path.to_string_lossy().into_owned();
+            // `include_str!` makes cargo rebuild the crate when the file changes.
+            (lit, text, quote!(include_str!(#path)))
+        }
+    };
+
+    let names: Vec<String> = args.funcs.iter().map(|f| f.name.to_string()).collect();
+    let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+    if let Err((line, col, msg)) = gtmpl::__validate(&text, &name_refs) {
+        return Err(Error::new(
+            lit.span(),
+            format!("template error at {}:{}: {}", line, col, msg),
+        ));
+    }
+
+    let funcs = args.funcs.iter().zip(&names).map(|(f, name)| {
+        let func = &f.func;
+        quote!(template.add_func(#name, #func);)
+    });
+    Ok(quote!({
+        static TEMPLATE: ::std::sync::LazyLock<::gtmpl::Template> =
+            ::std::sync::LazyLock::new(|| {
+                let mut template = ::gtmpl::Template::default();
+                #(#funcs)*
+                template
+                    .parse(#source)
+                    .expect("template validated at compile time");
+                template
+            });
+        &*TEMPLATE
+    }))
+}
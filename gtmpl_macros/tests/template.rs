@@ -0,0 +1,55 @@
+use gtmpl::{Context, Value};
+use gtmpl_macros::template;
+
+fn shout(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::from(format!("{}!", args[0])))
+}
+
+#[test]
+fn test_literal() {
+    let tmpl = template!("{{ if eq . 1 }}one{{ else }}other{{ end }}");
+    assert_eq!(tmpl.render(&Context::from(1).unwrap()), Ok("one".into()));
+    assert_eq!(tmpl.render(&Context::from(2).unwrap()), Ok("other".into()));
+    let tmpl = template!("Version 1.2 is out.");
+    assert_eq!(
+        tmpl.render(&Context::empty()),
+        Ok("Version 1.2 is out.".into())
+    );
+    assert_eq!(template!("N/A").render(&Context::empty()), Ok("N/A".into()));
+    assert_eq!(
+        template!("v1.2").render(&Context::empty()),
+        Ok("v1.2".into())
+    );
+    assert_eq!(
+        template!("Done.").render(&Context::empty()),
+        Ok("Done.".into())
+    );
+    assert_eq!(
+        template!("Cargo.toml").render(&Context::empty()),
+        Ok("Cargo.toml".into())
+    );
+}
+
+#[test]
+fn test_file() {
+    let tmpl = template!(file = "tests/templates/list.tmpl");
+    let out = tmpl.render(&Context::from(vec!["a", "b"]).unwrap());
+    assert_eq!(out, Ok("- a\n- b\n".into()));
+}
+
+#[test]
+fn test_funcs() {
+    let tmpl = template!("{{ shout . | len }}", shout = shout);
+    assert_eq!(tmpl.render(&Context::from("ab").unwrap()), Ok("3".into()));
+    let tmpl = template!(file = "tests/templates/list.tmpl", shout = shout);
+    let out = tmpl.render(&Context::from(vec!["a"]).unwrap());
+    assert_eq!(out, Ok("- a\n".into()));
+}
+
+#[test]
+fn test_static() {
+    fn get() -> &'static gtmpl::Template {
+        template!("{{ . }}")
+    }
+    assert!(std::ptr::eq(get(), get()));
+}
@@ -0,0 +1,346 @@
+//! Static analysis of the data a template uses.
+//!
+//! [`fields`](fn.fields.html) walks the parsed trees and reports every field chain like
+//! `.Values.image.tag` with its position. Dot is followed through `with` and `range`, `$`
+//! and variables refer to the values they were declared with and templates called by
+//! `{{ template }}` or `include` are analyzed with the data passed to them. Fields of
+//! values which are not known statically, e.g. results of functions, are reported as
+//! unresolved.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::analyze::fields;
+//! use gtmpl::Template;
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.parse(r#"{{ range .items }}{{ .name }}{{ end }}{{ with $.meta }}{{ .id }}{{ end }}"#)
+//!     .unwrap();
+//! let paths: Vec<String> = fields(&tmpl).iter().map(|f| f.to_string()).collect();
+//! assert_eq!(paths, vec![".items", ".items[].name", ".meta", ".meta.id"]);
+//! ```
+use std::fmt;
+
+use crate::node::*;
+use crate::parse::Tree;
+use crate::template::Template;
+
+/// A field chain used by a template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldRef {
+    /// Field names from the root of the data. `[]` stands for the elements of a value which
+    /// is ranged over. Unresolved paths start at an unknown value.
+    pub path: Vec<String>,
+    /// Name of the template containing the field chain.
+    pub template: String,
+    /// Byte offset of the field chain in the text the template was parsed from.
+    pub pos: Pos,
+    /// Whether the path starts at the root of the data.
+    pub resolved: bool,
+}
+
+impl fmt::Display for FieldRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.resolved {
+            write!(f, "?")?;
+        } else if self.path.is_empty() {
+            write!(f, ".")?;
+        }
+        for field in &self.path {
+            if field == "[]" {
+                write!(f, "[]")?;
+            } else {
+                write!(f, ".{}", field)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the field chains used by the main template of `template`, see
+/// [`analyze`](index.html).
+pub fn fields(template: &Template) -> Vec<FieldRef> {
+    fields_of(template, &template.name)
+}
+
+/// Returns the field chains used by the template `name`, see [`analyze`](index.html).
+pub fn fields_of(template: &Template, name: &str) -> Vec<FieldRef> {
+    let mut analyzer = Analyzer {
+        template,
+        refs: vec![],
+        vars: vec![],
+        calls: vec![],
+    };
+    analyzer.call(name, Some(vec![]));
+    analyzer.refs
+}
+
+// Path of a value from the root of the data, `None` if it is not known.
+type Path = Option<Vec<String>>;
+
+struct Analyzer<'a> {
+    template: &'a Template,
+    refs: Vec<FieldRef>,
+    vars: Vec<(String, Path)>,
+    // Templates being analyzed, the innermost last.
+    calls: Vec<String>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn call(&mut self, name: &str, dot: Path) {
+        // Recursive templates are analyzed once.
+        if self.calls.iter().any(|c| c == name) {
+            return;
+        }
+        let loaded;
+        let tree: &Tree = match self.template.tree_set.get(name) {
+            Some(tree) => tree,
+            None => match self.template.load_template(name) {
+                Ok(Some(tree)) => {
+                    loaded = tree;
+                    &loaded
+                }
+                _ => return,
+            },
+        };
+        let vars = std::mem::replace(&mut self.vars, vec![("$".to_owned(), dot.clone())]);
+        self.calls.push(name.to_owned());
+        if let Some(ref root) = tree.root {
+            self.walk(root, &dot);
+        }
+        self.calls.pop();
+        self.vars = vars;
+    }
+
+    fn record(&mut self, base: &Path, fields: &[String], pos: Pos) {
+        let mut path = base.clone().unwrap_or_default();
+        path.extend(fields.iter().cloned());
+        let field = FieldRef {
+            path,
+            template: self.calls.last().cloned().unwrap_or_default(),
+            pos,
+            resolved: base.is_some(),
+        };
+        if !self.refs.contains(&field) {
+            self.refs.push(field);
+        }
+    }
+
+    fn declare(&mut self, decl: &[VariableNode], value: &Path) {
+        for var in decl {
+            self.vars.push((var.ident[0].clone(), value.clone()));
+        }
+    }
+
+    fn walk(&mut self, node: &Nodes, dot: &Path) {
+        match *node {
+            Nodes::List(ref list) => self.walk_list(list, dot),
+            Nodes::Action(ref action) => {
+                let value = self.pipe(&action.pipe, dot);
+                self.declare(&action.pipe.decl, &value);
+            }
+            Nodes::If(ref branch) => {
+                let mark = self.vars.len();
+                let value = self.pipe(&branch.pipe, dot);
+                self.declare(&branch.pipe.decl, &value);
+                self.walk_list(&branch.list, dot);
+                self.vars.truncate(mark);
+                self.walk_else(branch, dot);
+            }
+            Nodes::With(ref branch) => {
+                let mark = self.vars.len();
+                let value = self.pipe(&branch.pipe, dot);
+                self.declare(&branch.pipe.decl, &value);
+                self.walk_list(&branch.list, &value);
+                self.vars.truncate(mark);
+                self.walk_else(branch, dot);
+            }
+            Nodes::Range(ref branch) => {
+                let mark = self.vars.len();
+                let elem = self.pipe(&branch.pipe, dot).map(|mut p| {
+                    p.push("[]".to_owned());
+                    p
+                });
+                match branch.pipe.decl.len() {
+                    0 => {}
+                    1 => self.declare(&branch.pipe.decl, &elem),
+                    _ => {
+                        self.declare(&branch.pipe.decl[..1], &None);
+                        self.declare(&branch.pipe.decl[1..], &elem);
+                    }
+                }
+                self.walk_list(&branch.list, &elem);
+                self.vars.truncate(mark);
+                self.walk_else(branch, dot);
+            }
+            Nodes::Template(ref template) => {
+                let arg = match template.pipe {
+                    Some(ref pipe) => self.pipe(pipe, dot),
+                    None => None,
+                };
+                match template.name {
+                    PipeOrString::String(ref name) => self.call(name, arg),
+                    PipeOrString::Pipe(ref pipe) => {
+                        self.pipe(pipe, dot);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn walk_list(&mut self, list: &ListNode, dot: &Path) {
+        for n in &list.nodes {
+            self.walk(n, dot);
+        }
+    }
+
+    fn walk_else(&mut self, branch: &BranchNode, dot: &Path) {
+        if let Some(ref else_list) = branch.else_list {
+            self.walk_list(else_list, dot);
+        }
+    }
+
+    // Records the fields of a pipeline and returns the path of its value.
+    fn pipe(&mut self, pipe: &PipeNode, dot: &Path) -> Path {
+        let mut value = None;
+        for cmd in &pipe.cmds {
+            value = self.command(cmd, dot);
+        }
+        // Later commands of a pipeline are always function calls.
+        if pipe.cmds.len() == 1 {
+            value
+        } else {
+            None
+        }
+    }
+
+    fn command(&mut self, cmd: &CommandNode, dot: &Path) -> Path {
+        let mut values = vec![];
+        for arg in &cmd.args {
+            values.push(self.value(arg, dot));
+        }
+        match (cmd.args.first(), cmd.args.get(1)) {
+            (Some(Nodes::Identifier(ref f)), Some(Nodes::String(ref name)))
+                if f.ident == "include" =>
+            {
+                let name = name.value.to_string();
+                let arg = values.get(2).cloned().unwrap_or(None);
+                self.call(&name, arg);
+            }
+            _ => {}
+        }
+        if values.len() == 1 {
+            values.pop().unwrap_or(None)
+        } else {
+            None
+        }
+    }
+
+    fn value(&mut self, node: &Nodes, dot: &Path) -> Path {
+        match *node {
+            Nodes::Dot(_) => dot.clone(),
+            Nodes::Field(ref field) => {
+                self.record(dot, &field.ident, field.pos());
+                dot.clone().map(|mut p| {
+                    p.extend(field.ident.iter().cloned());
+                    p
+                })
+            }
+            Nodes::Variable(ref var) => {
+                let base = self
+                    .vars
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| *name == var.ident[0])
+                    .and_then(|(_, path)| path.clone());
+                if var.ident.len() > 1 {
+                    self.record(&base, &var.ident[1..], var.pos());
+                }
+                base.map(|mut p| {
+                    p.extend(var.ident[1..].iter().cloned());
+                    p
+                })
+            }
+            Nodes::Chain(ref chain) => {
+                let base = self.value(&chain.node, dot);
+                self.record(&base, &chain.field, chain.pos());
+                base.map(|mut p| {
+                    p.extend(chain.field.iter().cloned());
+                    p
+                })
+            }
+            Nodes::Pipe(ref pipe) => self.pipe(pipe, dot),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn paths(text: &str) -> Vec<String> {
+        let mut t = Template::default();
+        t.parse(text).unwrap();
+        fields(&t).iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(paths("{{ .a.b }} {{ .c | len }}"), vec![".a.b", ".c"]);
+        assert_eq!(
+            paths("{{ with .a }}{{ .b }}{{ $.c }}{{ else }}{{ .d }}{{ end }}"),
+            vec![".a", ".a.b", ".c", ".d"]
+        );
+        assert_eq!(
+            paths("{{ range $i, $e := .list }}{{ $e.x }}{{ .y }}{{ end }}"),
+            vec![".list", ".list[].x", ".list[].y"]
+        );
+        assert_eq!(
+            paths("{{ $v := .a }}{{ if true }}{{ $w := $v.b }}{{ end }}{{ $v.c }}"),
+            vec![".a", ".a.b", ".a.c"]
+        );
+        assert_eq!(
+            paths("{{ (.a).b }} {{ (index .list 0).c }} {{ with len .d }}{{ .e }}{{ end }}"),
+            vec![".a", ".a.b", ".list", "?.c", ".d", "?.e"]
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        let mut t = Template::default();
+        t.parse(
+            r#"{{ define "item" }}{{ .name }}{{ template "item" .child }}{{ end -}}
+{{ template "item" .root }}{{ template "item" }}{{ template "missing" .x }}"#,
+        )
+        .unwrap();
+        let refs = fields(&t);
+        let paths: Vec<String> = refs.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                ".root",
+                ".root.name",
+                ".root.child",
+                "?.name",
+                "?.child",
+                ".x"
+            ]
+        );
+        assert_eq!(refs[1].template, "item");
+        assert_eq!(refs[1].pos, 22);
+        assert_eq!(refs[0].template, "");
+        assert!(fields_of(&t, "item").iter().all(|f| f.resolved));
+    }
+
+    #[cfg(feature = "include")]
+    #[test]
+    fn test_include() {
+        let mut t = Template::default();
+        t.add_include_funcs();
+        t.parse(r#"{{ define "x" }}{{ .a }}{{ end }}{{ include "x" .b | len }}"#)
+            .unwrap();
+        let paths: Vec<String> = fields(&t).iter().map(|f| f.to_string()).collect();
+        assert_eq!(paths, vec![".b", ".b.a"]);
+    }
+}
@@ -0,0 +1,204 @@
+//! Rendering all templates below a directory into a mirrored output directory.
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+use glob::Pattern;
+use gtmpl::{Context, Template};
+use serde_json::Value;
+
+/// Name of the template rendering output paths.
+const OUTPUT_MAP: &str = "gtmpl:output-map";
+
+// Joins the components of a relative path with `/`.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Returns whether `rel` is excluded. Patterns containing `/` are matched against the path
+// relative to the input directory, all others against the file name.
+fn excluded(rel: &str, exclude: &[Pattern]) -> bool {
+    let name = rel.rsplit('/').next().unwrap_or(rel);
+    exclude.iter().any(|p| {
+        if p.as_str().contains('/') {
+            p.matches(rel)
+        } else {
+            p.matches(name)
+        }
+    })
+}
+
+fn collect(
+    root: &Path,
+    dir: &Path,
+    exclude: &[Pattern],
+    files: &mut Vec<String>,
+) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("unable to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("unable to read {}: {}", dir.display(), e))?;
+        let path = entry.path();
+        let rel = slash_path(path.strip_prefix(root).unwrap_or(&path));
+        if excluded(&rel, exclude) {
+            continue;
+        }
+        // Symbolic links to directories are not followed to avoid cycles.
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            collect(root, &path, exclude, files)?;
+        } else if path.is_file() {
+            files.push(rel);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files below `root` which are not excluded as `/` separated paths relative to
+/// `root` in lexical order. Excluded directories are skipped completely.
+pub fn input_files(root: &Path, exclude: &[Pattern]) -> Result<Vec<String>, String> {
+    let mut files = vec![];
+    collect(root, root, exclude, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+// Writes `contents` to a temporary file next to `path` which is only accessible by the
+// owner, applies `permissions` and renames it to `path`. Like this the content is never
+// readable with wider permissions and read-only outputs of earlier runs are replaced.
+fn write_file(path: &Path, contents: &str, permissions: Permissions) -> Result<(), String> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.gtmpl-{}", file_name, process::id()));
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .and_then(|_| fs::set_permissions(&tmp, permissions))
+        .and_then(|_| fs::rename(&tmp, path));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("unable to write {}: {}", path.display(), e));
+    }
+    Ok(())
+}
+
+/// Renders every file below `input_dir` to the same relative path below `output_dir`,
+/// copying the permissions of the input file. All files are parsed into `template` first,
+/// so templates defined in one file can be used by all others.
+///
+/// The `output_map` template renders the output path instead, with `.in` set to the
+/// relative input path and `.ctx` to the data. Nothing is written if an output path is
+/// invalid or used for more than one file.
+pub fn render_dir(
+    template: &mut Template,
+    input_dir: &Path,
+    output_dir: &Path,
+    exclude: &[Pattern],
+    output_map: Option<&str>,
+    data: &Value,
+) -> Result<(), String> {
+    let files = input_files(input_dir, exclude)?;
+    for name in &files {
+        let path = input_dir.join(name);
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        template
+            .add_template(name.as_str(), text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    if let Some(output_map) = output_map {
+        template.add_template(OUTPUT_MAP, output_map)?;
+    }
+    let mut targets: Vec<PathBuf> = vec![];
+    let mut sources: HashMap<PathBuf, &str> = HashMap::new();
+    for name in &files {
+        let out_name = if output_map.is_some() {
+            let map_data: Context = serde_json::json!({ "in": name, "ctx": data }).into();
+            template
+                .render_template(OUTPUT_MAP, &map_data)
+                .map_err(|e| format!("{}: output map: {}", name, e))?
+                .trim()
+                .to_owned()
+        } else {
+            name.clone()
+        };
+        let out_rel = Path::new(&out_name);
+        if out_name.is_empty()
+            || !out_rel
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(format!(
+                "{}: output path {:?} is not a relative path below the output directory",
+                name, out_name
+            ));
+        }
+        let out_path = output_dir.join(out_rel);
+        if let Some(other) = sources.insert(out_path.clone(), name) {
+            return Err(format!(
+                "{}: output path {:?} is also used for {}",
+                name, out_name, other
+            ));
+        }
+        targets.push(out_path);
+    }
+    let context: Context = data.clone().into();
+    for (name, out_path) in files.iter().zip(&targets) {
+        let output = template
+            .render_template(name, &context)
+            .map_err(|e| format!("{}: {}", name, e))?;
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("unable to create {}: {}", parent.display(), e))?;
+        }
+        let in_path = input_dir.join(name);
+        let permissions = fs::metadata(&in_path)
+            .map_err(|e| format!("unable to read {}: {}", in_path.display(), e))?
+            .permissions();
+        write_file(out_path, &output, permissions)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    #[test]
+    fn test_input_files() {
+        let dir = std::env::temp_dir().join(format!("gtmpl-cli-batch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::create_dir_all(dir.join("skip")).unwrap();
+        for file in &[
+            "x.tmpl",
+            "a/y.tmpl",
+            "a/b/z.tmpl",
+            "a/b/z.bak",
+            "skip/w.tmpl",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let exclude = vec![
+            Pattern::new("*.bak").unwrap(),
+            Pattern::new("skip").unwrap(),
+            Pattern::new("a/y.*").unwrap(),
+        ];
+        assert_eq!(
+            input_files(&dir, &exclude).unwrap(),
+            vec!["a/b/z.tmpl", "x.tmpl"]
+        );
+        assert_eq!(input_files(&dir, &[]).unwrap().len(), 5);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
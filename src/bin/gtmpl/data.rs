@@ -0,0 +1,163 @@
+//! Building the data passed to the template.
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+/// Parses a data file, the format is chosen by the extension: `json`, `yaml`, `yml` or
+/// `toml`.
+pub fn load_file(path: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "json" => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+        "toml" => toml::from_str(&text).map_err(|e| e.to_string()),
+        _ => Err(String::from(
+            "unknown data format, expected .json, .yaml, .yml or .toml",
+        )),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Merges `from` into `into`. Maps are merged recursively, all other values replace the
+/// existing ones.
+pub fn merge(into: &mut Value, from: Value) {
+    match (into, from) {
+        (Value::Object(into), Value::Object(from)) => {
+            for (key, value) in from {
+                match into.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        into.insert(key, value);
+                    }
+                }
+            }
+        }
+        (into, from) => *into = from,
+    }
+}
+
+// Converts a `--set` value like Helm does: `true`, `false`, `null` and integers without
+// leading zeros are typed, everything else is a string, e.g. the version `1.10`.
+fn typed_value(raw: &str) -> Value {
+    let digits = raw.strip_prefix('-').unwrap_or(raw);
+    let integer = !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    match raw {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "null" => Value::Null,
+        _ if integer => raw
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(raw.to_owned())),
+        _ => Value::String(raw.to_owned()),
+    }
+}
+
+/// Applies an assignment like `image.tag=1.2`. `true`, `false`, `null` and integers are typed
+/// values, everything else is taken as string.
+pub fn set(data: &mut Value, assignment: &str) -> Result<(), String> {
+    let (path, raw) = match assignment.find('=') {
+        Some(i) => (&assignment[..i], &assignment[i + 1..]),
+        None => return Err(format!("expected key.path=value, got {}", assignment)),
+    };
+    if path.is_empty() || path.split('.').any(|k| k.is_empty()) {
+        return Err(format!("invalid key path {:?}", path));
+    }
+    let value = typed_value(raw);
+    let mut current = data;
+    for key in path.split('.') {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = match current {
+            Value::Object(map) => map.entry(key.to_owned()).or_insert(Value::Null),
+            _ => unreachable!(),
+        };
+    }
+    *current = value;
+    Ok(())
+}
+
+/// Returns the environment variables as map.
+pub fn env() -> Value {
+    Value::Object(
+        std::env::vars()
+            .map(|(key, value)| (key, Value::String(value)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut data = serde_json::json!({"a": {"b": 1, "c": 2}, "d": [1]});
+        merge(&mut data, serde_json::json!({"a": {"c": 3}, "d": [2, 3]}));
+        assert_eq!(
+            data,
+            serde_json::json!({"a": {"b": 1, "c": 3}, "d": [2, 3]})
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let mut data = serde_json::json!({"a": 1});
+        set(&mut data, "a.b=true").unwrap();
+        set(&mut data, "c.d=1.10").unwrap();
+        set(&mut data, "c.e=a=b").unwrap();
+        set(&mut data, "f=[1, \"x\"]").unwrap();
+        assert_eq!(
+            data,
+            serde_json::json!({"a": {"b": true}, "c": {"d": "1.10", "e": "a=b"}, "f": "[1, \"x\"]"})
+        );
+        for (raw, value) in &[
+            ("42", serde_json::json!(42)),
+            ("-7", serde_json::json!(-7)),
+            ("0", serde_json::json!(0)),
+            ("null", serde_json::json!(null)),
+            ("007", serde_json::json!("007")),
+            ("1e3", serde_json::json!("1e3")),
+            (
+                "99999999999999999999",
+                serde_json::json!("99999999999999999999"),
+            ),
+            ("-", serde_json::json!("-")),
+            ("", serde_json::json!("")),
+        ] {
+            set(&mut data, &format!("v={}", raw)).unwrap();
+            assert_eq!(data["v"], *value, "{}", raw);
+        }
+        assert!(set(&mut data, "a").is_err());
+        assert!(set(&mut data, "a..b=1").is_err());
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = std::env::temp_dir().join(format!("gtmpl-cli-data-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.toml"), "[image]\ntag = \"1.2\"\n").unwrap();
+        fs::write(dir.join("a.yml"), "image:\n  name: app\n").unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        assert_eq!(
+            load_file(&dir.join("a.toml")).unwrap(),
+            serde_json::json!({"image": {"tag": "1.2"}})
+        );
+        assert_eq!(
+            load_file(&dir.join("a.yml")).unwrap(),
+            serde_json::json!({"image": {"name": "app"}})
+        );
+        assert!(load_file(&dir.join("a.txt")).is_err());
+        assert!(load_file(&dir.join("missing.json")).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
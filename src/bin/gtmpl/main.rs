@@ -0,0 +1,320 @@
+//! `gtmpl` renders golang templates from the command line.
+//!
+//! Data is read from JSON, YAML or TOML files and `--set` assignments, environment
+//! variables are available as `.Env`. See `gtmpl --help`.
+extern crate glob;
+extern crate gtmpl;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
+
+mod batch;
+mod data;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use glob::Pattern;
+use gtmpl::{MissingKey, Template};
+
+const USAGE: &str = "\
+Usage: gtmpl [OPTIONS] [TEMPLATE]
+       gtmpl [OPTIONS] --input-dir DIR --output-dir DIR
+
+Renders TEMPLATE, or the standard input if TEMPLATE is missing or -, to the standard
+output. With --input-dir every file below DIR is rendered to the same path below the
+output directory, keeping its mode. All files share one template set, so templates
+defined in one file can be used in all others. Environment variables are available
+as .Env.
+
+Options:
+  -d, --data FILE          read data from a .json, .yaml, .yml or .toml file, later
+                           files are merged into earlier ones
+  -s, --set KEY.PATH=VALUE set a value, true, false, null and integers are typed,
+                           everything else is a string
+  -t, --template-dir DIR   parse all files below DIR as templates named by their
+                           relative path, e.g. partials/header.tmpl, hidden files
+                           are skipped
+      --missingkey MODE    handling of missing map keys: default, zero or error
+      --left-delim DELIM   left action delimiter, defaults to {{
+      --right-delim DELIM  right action delimiter, defaults to }}
+  -o, --out FILE           write the output to FILE
+      --input-dir DIR      render all files below DIR
+      --output-dir DIR     write the files rendered from --input-dir below DIR
+      --exclude GLOB       skip files and directories of --input-dir matching GLOB,
+                           e.g. *.bak, GLOBs containing / match the relative path
+      --output-map TMPL    render the output path of every file with TMPL, .in is
+                           the input path and .ctx the data, e.g.
+                           '{{ .in | trimSuffix \".tmpl\" }}'
+  -h, --help               print this help
+  -V, --version            print the version";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    template: Option<PathBuf>,
+    data: Vec<PathBuf>,
+    set: Vec<String>,
+    template_dirs: Vec<PathBuf>,
+    missing_key: MissingKey,
+    left_delim: String,
+    right_delim: String,
+    out: Option<PathBuf>,
+    input_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    exclude: Vec<String>,
+    output_map: Option<String>,
+    help: bool,
+    version: bool,
+}
+
+fn parse_missing_key(mode: &str) -> Result<MissingKey, String> {
+    match mode {
+        "default" | "invalid" => Ok(MissingKey::Default),
+        "zero" => Ok(MissingKey::Zero),
+        "error" => Ok(MissingKey::Error),
+        _ => Err(format!(
+            "invalid --missingkey {}, expected default, zero or error",
+            mode
+        )),
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut opts = Options::default();
+    while let Some(arg) = args.next() {
+        if arg == "-" || !arg.starts_with('-') {
+            if opts.template.is_some() {
+                return Err(format!("unexpected argument {}", arg));
+            }
+            opts.template = Some(PathBuf::from(arg));
+            continue;
+        }
+        // Long options take their value after `=` or as next argument.
+        let (flag, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_owned())),
+            _ => (arg.as_str(), None),
+        };
+        match flag {
+            "-h" | "--help" => opts.help = true,
+            "-V" | "--version" => opts.version = true,
+            _ => {
+                let value = match inline.or_else(|| args.next()) {
+                    Some(value) => value,
+                    None => return Err(format!("missing value for {}", flag)),
+                };
+                match flag {
+                    "-d" | "--data" => opts.data.push(value.into()),
+                    "-s" | "--set" => opts.set.push(value),
+                    "-t" | "--template-dir" => opts.template_dirs.push(value.into()),
+                    "--missingkey" => opts.missing_key = parse_missing_key(&value)?,
+                    "--left-delim" => opts.left_delim = value,
+                    "--right-delim" => opts.right_delim = value,
+                    "-o" | "--out" => opts.out = Some(value.into()),
+                    "--input-dir" => opts.input_dir = Some(value.into()),
+                    "--output-dir" => opts.output_dir = Some(value.into()),
+                    "--exclude" => opts.exclude.push(value),
+                    "--output-map" => opts.output_map = Some(value),
+                    _ => return Err(format!("unknown option {}", flag)),
+                }
+            }
+        }
+    }
+    if opts.input_dir.is_some() {
+        if opts.output_dir.is_none() {
+            return Err(String::from("--input-dir requires --output-dir"));
+        }
+        if opts.template.is_some() || opts.out.is_some() {
+            return Err(String::from(
+                "--input-dir can't be combined with TEMPLATE or --out",
+            ));
+        }
+    } else if opts.output_dir.is_some() || !opts.exclude.is_empty() || opts.output_map.is_some() {
+        return Err(String::from(
+            "--output-dir, --exclude and --output-map require --input-dir",
+        ));
+    }
+    Ok(opts)
+}
+
+/// Returns a template with the given options and the functions of all enabled features
+/// except `host`, which would give templates access to the file system.
+fn new_template(name: &str, opts: &Options) -> Template {
+    let mut template = Template::with_name(name);
+    template.add_default_funcs();
+    template.add_dict_funcs();
+    template.add_encoding_funcs();
+    template.add_include_funcs();
+    template.add_json_funcs();
+    template.add_list_funcs();
+    template.add_math_funcs();
+    template.add_string_funcs();
+    template.add_yaml_funcs();
+    #[cfg(feature = "random")]
+    template.add_random_funcs();
+    #[cfg(feature = "regex")]
+    template.add_regex_funcs();
+    #[cfg(feature = "semver")]
+    template.add_semver_funcs();
+    #[cfg(feature = "time")]
+    template.add_time_funcs();
+    template.set_delims(opts.left_delim.as_str(), opts.right_delim.as_str());
+    template.set_missing_key(opts.missing_key);
+    template
+}
+
+/// Adds all files below `dir` to `template`, named by their relative path. Hidden files and
+/// directories like editor swap files are skipped.
+fn add_template_dir(template: &mut Template, dir: &Path) -> Result<(), String> {
+    let hidden = Pattern::new(".*").map_err(|e| e.to_string())?;
+    for name in batch::input_files(dir, &[hidden])? {
+        let path = dir.join(&name);
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        template
+            .add_template(name.as_str(), text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn load_data(opts: &Options) -> Result<serde_json::Value, String> {
+    let mut values = serde_json::json!({ "Env": data::env() });
+    for path in &opts.data {
+        data::merge(&mut values, data::load_file(path)?);
+    }
+    for assignment in &opts.set {
+        data::set(&mut values, assignment)?;
+    }
+    Ok(values)
+}
+
+fn run_batch(opts: &Options, input_dir: &Path, output_dir: &Path) -> Result<(), String> {
+    let exclude = opts
+        .exclude
+        .iter()
+        .map(|p| Pattern::new(p).map_err(|e| format!("invalid --exclude {}: {}", p, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut template = new_template("", opts);
+    for dir in &opts.template_dirs {
+        add_template_dir(&mut template, dir)?;
+    }
+    batch::render_dir(
+        &mut template,
+        input_dir,
+        output_dir,
+        &exclude,
+        opts.output_map.as_deref(),
+        &load_data(opts)?,
+    )
+}
+
+fn run(opts: &Options) -> Result<(), String> {
+    if let (Some(input_dir), Some(output_dir)) = (&opts.input_dir, &opts.output_dir) {
+        return run_batch(opts, input_dir, output_dir);
+    }
+    let (name, text) = match opts.template {
+        Some(ref path) if path.as_os_str() != "-" => {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+            (path.display().to_string(), text)
+        }
+        _ => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("unable to read stdin: {}", e))?;
+            (String::from("stdin"), text)
+        }
+    };
+    let mut template = new_template(&name, opts);
+    for dir in &opts.template_dirs {
+        add_template_dir(&mut template, dir)?;
+    }
+    template.parse(text)?;
+    let output = template.render(&load_data(opts)?.into())?;
+    match opts.out {
+        Some(ref path) => fs::write(path, output)
+            .map_err(|e| format!("unable to write {}: {}", path.display(), e)),
+        None => io::stdout()
+            .write_all(output.as_bytes())
+            .map_err(|e| format!("unable to write stdout: {}", e)),
+    }
+}
+
+fn main() {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("gtmpl: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if opts.help {
+        println!("{}", USAGE);
+    } else if opts.version {
+        println!("gtmpl {}", env!("CARGO_PKG_VERSION"));
+    } else if let Err(e) = run(&opts) {
+        eprintln!("gtmpl: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let opts = args(&[
+            "-d",
+            "a.json",
+            "--data=b.toml",
+            "--set",
+            "a.b=c=d",
+            "--missingkey=error",
+            "--left-delim",
+            "[[",
+            "-o",
+            "out.txt",
+            "main.tmpl",
+        ])
+        .unwrap();
+        assert_eq!(
+            opts,
+            Options {
+                template: Some("main.tmpl".into()),
+                data: vec!["a.json".into(), "b.toml".into()],
+                set: vec!["a.b=c=d".into()],
+                missing_key: MissingKey::Error,
+                left_delim: "[[".into(),
+                out: Some("out.txt".into()),
+                ..Options::default()
+            }
+        );
+        assert_eq!(args(&["-"]).unwrap().template, Some("-".into()));
+        assert!(args(&["-x", "1"]).is_err());
+        assert!(args(&["--data"]).is_err());
+        assert!(args(&["--missingkey", "strict"]).is_err());
+        assert!(args(&["a.tmpl", "b.tmpl"]).is_err());
+        let opts = args(&[
+            "--input-dir",
+            "in",
+            "--output-dir=out",
+            "--exclude",
+            "*.bak",
+        ])
+        .unwrap();
+        assert_eq!(opts.input_dir, Some("in".into()));
+        assert_eq!(opts.output_dir, Some("out".into()));
+        assert_eq!(opts.exclude, vec!["*.bak"]);
+        assert!(args(&["--input-dir", "in"]).is_err());
+        assert!(args(&["--input-dir", "in", "--output-dir", "out", "a.tmpl"]).is_err());
+        assert!(args(&["--output-map", "x"]).is_err());
+    }
+}
@@ -0,0 +1,590 @@
+//! Checking data against the fields a template uses before rendering it.
+//!
+//! [`Template::check`](../struct.Template.html#method.check) walks the template like an
+//! execution without producing output. Branches of `if` and `with` are followed as the data
+//! decides or both of them if the condition isn't known without calling a function. Only the
+//! side effect free builtins like `eq` or `len` are called. `range` checks every element.
+//!
+//! A missing value is not reported if it is used as condition of `if` or `with`, or as
+//! argument of `default`, `empty` or `coalesce`.
+//!
+//! Templates nested deeper than the maximum template depth or than the stack allows are
+//! reported like execution fails, and checking stops there.
+//!
+//! ## Example
+//! ```rust
+//! use std::collections::HashMap;
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.parse(r#"{{ .image.name }}:{{ .image.tag }}{{ if .debug }}{{ .level }}{{ end }}"#)
+//!     .unwrap();
+//! let mut image = HashMap::new();
+//! image.insert("name".to_owned(), "app".to_owned());
+//! let mut data = HashMap::new();
+//! data.insert("image".to_owned(), image);
+//! let errors = tmpl.check(&Context::from(data).unwrap()).unwrap_err();
+//! assert_eq!(errors.len(), 1);
+//! assert_eq!(errors[0].to_string(), ":27: .image.tag: missing value");
+//! ```
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::exec::{Context, STACK_EXHAUSTED};
+use crate::node::*;
+use crate::parse::Tree;
+use crate::print_verb::type_name;
+use crate::template::Template;
+use crate::utils::{is_true, stack_exhausted, stack_position};
+use gtmpl_value::Value;
+
+// Builtins without side effects, which are called to follow conditions.
+static PURE: &[&str] = &[
+    "eq", "ne", "lt", "le", "gt", "ge", "len", "and", "or", "not", "index", "print", "printf",
+    "println", "urlquery",
+];
+
+// Functions handling missing values.
+static OPTIONAL: &[&str] = &["default", "empty", "coalesce"];
+
+/// A value used by a template which is missing or has the wrong type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathError {
+    /// Path of the value like `.items[0].name`.
+    pub path: String,
+    /// Name of the template using the value.
+    pub template: String,
+    /// Byte offset of the use in the text the template was parsed from.
+    pub pos: Pos,
+    pub message: String,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "."
+        } else {
+            &self.path
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.template, self.pos, path, self.message
+        )
+    }
+}
+
+// A value with its path, `None` if the value is not known without executing functions.
+#[derive(Clone)]
+struct Val {
+    value: Option<Value>,
+    path: String,
+}
+
+impl Val {
+    fn known(value: Value, path: String) -> Val {
+        Val {
+            value: Some(value),
+            path,
+        }
+    }
+
+    fn unknown(path: String) -> Val {
+        Val { value: None, path }
+    }
+}
+
+struct Checker<'a> {
+    template: &'a Template,
+    vars: Vec<(String, Val)>,
+    calls: Vec<String>,
+    // Templates checked with an unknown dot, checking them again finds nothing new.
+    unknown_calls: HashSet<String>,
+    errors: Vec<PathError>,
+    // Set once the maximum template depth or the stack budget is exceeded.
+    aborted: bool,
+    stack_start: usize,
+}
+
+impl Template {
+    /// Checks that `data` has all values the template uses with the right types, see
+    /// [`check`](check/index.html). Returns all problems found.
+    pub fn check(&self, data: &Context) -> Result<(), Vec<PathError>> {
+        let mut checker = Checker {
+            template: self,
+            vars: vec![],
+            calls: vec![],
+            unknown_calls: HashSet::new(),
+            errors: vec![],
+            aborted: false,
+            stack_start: stack_position(),
+        };
+        checker.call(&self.name, Val::known(data.dot.clone(), String::new()), 0);
+        if checker.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(checker.errors)
+        }
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, pos: Pos, path: &str, message: String) {
+        let error = PathError {
+            path: path.to_owned(),
+            template: self.calls.last().cloned().unwrap_or_default(),
+            pos,
+            message,
+        };
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    fn call(&mut self, name: &str, dot: Val, pos: Pos) {
+        if self.aborted {
+            return;
+        }
+        let max_depth = self.template.max_depth;
+        if self.calls.len() >= max_depth {
+            let message = format!("exceeded maximum template depth ({})", max_depth);
+            self.report(pos, &dot.path, message);
+            self.aborted = true;
+            return;
+        }
+        // Fields of unknown values are never reported, so the results don't depend on dot.
+        if dot.value.is_none() && !self.unknown_calls.insert(name.to_owned()) {
+            return;
+        }
+        let loaded;
+        let tree: &Tree = match self.template.tree_set.get(name) {
+            Some(tree) => tree,
+            None => match self.template.load_template(name) {
+                Ok(Some(tree)) => {
+                    loaded = tree;
+                    &loaded
+                }
+                _ => return,
+            },
+        };
+        let vars = std::mem::replace(&mut self.vars, vec![("$".to_owned(), dot.clone())]);
+        self.calls.push(name.to_owned());
+        if let Some(ref root) = tree.root {
+            self.walk(root, &dot);
+        }
+        self.calls.pop();
+        self.vars = vars;
+    }
+
+    fn walk(&mut self, node: &Nodes, dot: &Val) {
+        if self.aborted {
+            return;
+        }
+        if stack_exhausted(self.stack_start) {
+            self.report(node.pos(), &dot.path, String::from(STACK_EXHAUSTED));
+            self.aborted = true;
+            return;
+        }
+        match *node {
+            Nodes::List(ref list) => self.walk_list(list, dot),
+            Nodes::Action(ref action) => {
+                let value = self.pipe(&action.pipe, dot, false);
+                self.declare(&action.pipe, &value);
+            }
+            Nodes::If(ref branch) | Nodes::With(ref branch) => {
+                let mark = self.vars.len();
+                let value = self.pipe(&branch.pipe, dot, true);
+                self.declare(&branch.pipe, &value);
+                let inner = if let Nodes::With(_) = *node {
+                    value.clone()
+                } else {
+                    dot.clone()
+                };
+                let truth = value.value.as_ref().map(is_true);
+                if truth != Some(false) {
+                    self.walk_list(&branch.list, &inner);
+                }
+                if truth != Some(true) {
+                    if let Some(ref else_list) = branch.else_list {
+                        self.walk_list(else_list, dot);
+                    }
+                }
+                self.vars.truncate(mark);
+            }
+            Nodes::Range(ref range) => self.walk_range(range, dot),
+            Nodes::Template(ref template) => {
+                let arg = match template.pipe {
+                    Some(ref pipe) => self.pipe(pipe, dot, false),
+                    None => Val::known(Value::NoValue, String::new()),
+                };
+                let name = match template.name {
+                    PipeOrString::String(ref name) => Some(name.clone()),
+                    PipeOrString::Pipe(ref pipe) => match self.pipe(pipe, dot, false).value {
+                        Some(Value::String(name)) => Some(name),
+                        _ => None,
+                    },
+                };
+                if let Some(name) = name {
+                    self.call(&name, arg, template.pos());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn walk_list(&mut self, list: &ListNode, dot: &Val) {
+        for n in &list.nodes {
+            self.walk(n, dot);
+        }
+    }
+
+    fn walk_range(&mut self, range: &RangeNode, dot: &Val) {
+        let mark = self.vars.len();
+        let value = self.pipe(&range.pipe, dot, false);
+        let items: Vec<(Value, Val)> = match value.value {
+            Some(Value::Array(ref a)) => a
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let path = format!("{}[{}]", value.path, i);
+                    (Value::from(i), Val::known(v.clone(), path))
+                })
+                .collect(),
+            Some(Value::Map(ref m)) | Some(Value::Object(ref m)) => {
+                let mut keys: Vec<&String> = m.keys().collect();
+                keys.sort();
+                keys.into_iter()
+                    .map(|k| {
+                        let path = format!("{}.{}", value.path, k);
+                        (Value::from(k.as_str()), Val::known(m[k].clone(), path))
+                    })
+                    .collect()
+            }
+            Some(ref v) => {
+                let message = format!("cannot range over {}", type_name(v));
+                self.report(range.pipe.pos(), &value.path, message);
+                vec![]
+            }
+            None => {
+                // Check the body once for values which don't depend on the element.
+                let elem = Val::unknown(format!("{}[]", value.path));
+                self.declare_range(range, Val::unknown(String::new()), elem.clone());
+                self.walk_list(&range.list, &elem);
+                self.vars.truncate(mark);
+                if let Some(ref else_list) = range.else_list {
+                    self.walk_list(else_list, dot);
+                }
+                return;
+            }
+        };
+        if items.is_empty() {
+            if let Some(ref else_list) = range.else_list {
+                self.walk_list(else_list, dot);
+            }
+        }
+        for (key, elem) in items {
+            self.declare_range(range, Val::known(key, String::new()), elem.clone());
+            self.walk_list(&range.list, &elem);
+            self.vars.truncate(mark);
+        }
+    }
+
+    fn declare_range(&mut self, range: &RangeNode, key: Val, elem: Val) {
+        let decl = &range.pipe.decl;
+        match decl.len() {
+            0 => {}
+            1 => self.vars.push((decl[0].ident[0].clone(), elem)),
+            _ => {
+                self.vars.push((decl[0].ident[0].clone(), key));
+                self.vars.push((decl[1].ident[0].clone(), elem));
+            }
+        }
+    }
+
+    // Evaluates a pipeline without declaring its variables. Missing values are not reported
+    // for the value of an optional pipeline.
+    fn pipe(&mut self, pipe: &PipeNode, dot: &Val, optional: bool) -> Val {
+        let mut value = None;
+        for (i, cmd) in pipe.cmds.iter().enumerate() {
+            let optional = match pipe.cmds.get(i + 1) {
+                Some(next) => is_optional(next),
+                None => optional,
+            };
+            value = Some(self.command(cmd, dot, value, optional));
+        }
+        value.unwrap_or_else(|| Val::unknown(String::new()))
+    }
+
+    fn declare(&mut self, pipe: &PipeNode, value: &Val) {
+        for var in &pipe.decl {
+            self.vars.push((var.ident[0].clone(), value.clone()));
+        }
+    }
+
+    fn command(&mut self, cmd: &CommandNode, dot: &Val, fin: Option<Val>, optional: bool) -> Val {
+        let func = match cmd.args.first() {
+            Some(Nodes::Identifier(ref f)) => f.ident.as_str(),
+            Some(arg) if cmd.args.len() == 1 && fin.is_none() => {
+                return self.value(arg, dot, optional)
+            }
+            _ => {
+                for arg in &cmd.args {
+                    self.value(arg, dot, false);
+                }
+                return Val::unknown(format!("({})", cmd));
+            }
+        };
+        let optional_args = OPTIONAL.contains(&func);
+        let mut args: Vec<Val> = cmd.args[1..]
+            .iter()
+            .map(|arg| self.value(arg, dot, optional_args))
+            .collect();
+        args.extend(fin);
+        let path = match args.split_first() {
+            Some((base, keys)) if func == "index" => {
+                keys.iter().fold(base.path.clone(), |p, k| match k.value {
+                    Some(Value::String(ref s)) => format!("{}.{}", p, s),
+                    Some(ref v) => format!("{}[{}]", p, v),
+                    None => format!("{}[]", p),
+                })
+            }
+            _ => format!("({})", func),
+        };
+        let result = Val::unknown(path);
+        if func == "include" {
+            if let (Some(Nodes::String(ref name)), Some(arg)) = (cmd.args.get(1), args.get(1)) {
+                self.call(&name.value.to_string(), arg.clone(), cmd.pos());
+            }
+            return result;
+        }
+        if !PURE.contains(&func) {
+            return result;
+        }
+        let values: Option<Vec<Value>> = args.iter().map(|a| a.value.clone()).collect();
+        match (self.template.funcs.get(func), values) {
+            (Some(f), Some(values)) => match f(&values) {
+                Ok(v) => Val::known(v, result.path),
+                Err(e) => {
+                    let path = args
+                        .iter()
+                        .map(|a| a.path.as_str())
+                        .find(|p| p.starts_with('.'))
+                        .unwrap_or(&result.path)
+                        .to_owned();
+                    self.report(cmd.pos(), &path, e);
+                    result
+                }
+            },
+            _ => result,
+        }
+    }
+
+    fn value(&mut self, node: &Nodes, dot: &Val, optional: bool) -> Val {
+        match *node {
+            Nodes::Dot(_) => dot.clone(),
+            Nodes::Field(ref field) => {
+                self.fields(dot.clone(), &field.ident, field.pos(), optional)
+            }
+            Nodes::Variable(ref var) => {
+                let base = self
+                    .vars
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| *name == var.ident[0])
+                    .map(|(_, val)| val.clone())
+                    .unwrap_or_else(|| Val::unknown(var.ident[0].clone()));
+                self.fields(base, &var.ident[1..], var.pos(), optional)
+            }
+            Nodes::Chain(ref chain) => {
+                let base = self.value(&chain.node, dot, false);
+                self.fields(base, &chain.field, chain.pos(), optional)
+            }
+            Nodes::Pipe(ref pipe) => self.pipe(pipe, dot, optional),
+            Nodes::Identifier(ref f) => Val::unknown(format!("({})", f)),
+            Nodes::Bool(ref n) => Val::known(n.value.clone(), String::new()),
+            Nodes::Number(ref n) => Val::known(n.value.clone(), String::new()),
+            Nodes::String(ref n) => Val::known(n.value.clone(), String::new()),
+            _ => Val::unknown(String::new()),
+        }
+    }
+
+    fn fields(&mut self, mut val: Val, fields: &[String], pos: Pos, optional: bool) -> Val {
+        for (i, name) in fields.iter().enumerate() {
+            let path = format!("{}.{}", val.path, name);
+            val = match val.value {
+                None => Val::unknown(path),
+                Some(Value::Object(ref o)) | Some(Value::Map(ref o)) => match o.get(name) {
+                    // Methods are not called.
+                    Some(Value::Function(_)) => Val::unknown(path),
+                    Some(v) => Val::known(v.clone(), path),
+                    None if optional && i + 1 == fields.len() => Val::known(Value::NoValue, path),
+                    None => {
+                        self.report(pos, &path, String::from("missing value"));
+                        Val::unknown(path)
+                    }
+                },
+                Some(ref v) => {
+                    let message = format!("cannot access field {} of {}", name, type_name(v));
+                    self.report(pos, &path, message);
+                    Val::unknown(path)
+                }
+            };
+        }
+        val
+    }
+}
+
+fn is_optional(cmd: &CommandNode) -> bool {
+    match cmd.args.first() {
+        Some(Nodes::Identifier(ref f)) => OPTIONAL.contains(&f.ident.as_str()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::exec::MAX_TEMPLATE_DEPTH;
+    use std::collections::HashMap;
+
+    fn data() -> Context {
+        let mut item = HashMap::new();
+        item.insert("name".to_owned(), Value::from("a"));
+        let mut data = HashMap::new();
+        data.insert(
+            "items".to_owned(),
+            Value::from(vec![
+                Value::from(item),
+                Value::from(HashMap::<String, Value>::new()),
+            ]),
+        );
+        data.insert("n".to_owned(), Value::from(2));
+        data.insert("s".to_owned(), Value::from("str"));
+        Context::from(data).unwrap()
+    }
+
+    fn errors(text: &str) -> Vec<String> {
+        let mut t = Template::default();
+        t.parse(text).unwrap();
+        match t.check(&data()) {
+            Ok(()) => vec![],
+            Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(
+            errors("{{ .n }}{{ range .items }}{{ if .name }}{{ .name }}{{ end }}{{ end }}")
+                .is_empty()
+        );
+        assert_eq!(
+            errors("{{ .a.b }}{{ .s.x }}{{ range $i, $e := .items }}{{ $e.name }}{{ end }}"),
+            vec![
+                ":5: .a: missing value",
+                ":15: .s.x: cannot access field x of string",
+                ":53: .items[1].name: missing value"
+            ]
+        );
+        assert_eq!(
+            errors("{{ range .n }}{{ end }}{{ if lt .s 1 }}{{ end }}"),
+            vec![
+                ":9: .n: cannot range over int",
+                ":29: .s: unable to compare str and 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        assert!(errors("{{ if .missing }}{{ .x }}{{ end }}").is_empty());
+        assert!(errors("{{ if eq .n 3 }}{{ .x }}{{ else }}{{ .n }}{{ end }}").is_empty());
+        assert!(errors("{{ with .missing }}{{ .x }}{{ else }}{{ .n }}{{ end }}").is_empty());
+        assert_eq!(
+            errors("{{ if .n }}{{ .x }}{{ else }}{{ .y }}{{ end }}"),
+            vec![":14: .x: missing value"]
+        );
+        assert_eq!(
+            errors("{{ with index .items 0 }}{{ .name.x }}{{ end }}"),
+            vec![":33: .items[0].name.x: cannot access field x of string"]
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        assert_eq!(
+            errors(
+                r#"{{ define "t" }}{{ .name }}{{ end }}{{ range .items }}{{ template "t" . }}{{ end }}"#
+            ),
+            vec!["t:19: .items[1].name: missing value"]
+        );
+        assert_eq!(
+            errors(r#"{{ define "t" }}{{ template "t" .x }}{{ end }}{{ template "t" . }}"#),
+            vec!["t:32: .x: missing value"]
+        );
+        let mut t = Template::default();
+        t.set_max_depth(100);
+        t.parse(concat!(
+            r#"{{ define "t" }}{{ template "t" . }}{{ template "t" . }}{{ end }}"#,
+            r#"{{ template "t" . }}"#
+        ))
+        .unwrap();
+        let errors: Vec<String> = t
+            .check(&data())
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec!["t:28: .: exceeded maximum template depth (100)"]
+        );
+    }
+
+    #[test]
+    fn test_recursion_stack() {
+        let errors = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(|| {
+                let check = |text: String| {
+                    let mut t = Template::default();
+                    t.parse(text).unwrap();
+                    let errors = t.check(&Context::from(1).unwrap()).unwrap_err();
+                    errors.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+                };
+                vec![
+                    check(String::from(concat!(
+                        r#"{{ define "loop" }}{{ if . }}{{ template "loop" . }}{{ end }}{{ end }}"#,
+                        r#"{{ template "loop" . }}"#
+                    ))),
+                    check(format!(
+                        r#"{{{{ define "loop" }}}}{}{{{{ template "loop" . }}}}{}{{{{ end }}}}{{{{ template "loop" . }}}}"#,
+                        "{{ with . }}".repeat(100),
+                        "{{ end }}".repeat(100)
+                    )),
+                ]
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            errors[0],
+            vec![format!(
+                "loop:41: .: exceeded maximum template depth ({})",
+                MAX_TEMPLATE_DEPTH
+            )]
+        );
+        assert_eq!(errors[1].len(), 1);
+        assert!(errors[1][0].ends_with(STACK_EXHAUSTED), "{:?}", errors[1]);
+    }
+
+    #[cfg(feature = "defaults")]
+    #[test]
+    fn test_optional_funcs() {
+        let mut t = Template::default();
+        t.add_default_funcs();
+        t.parse(r#"{{ range .items }}{{ .name | default "x" }}{{ default 1 .m }}{{ end }}"#)
+            .unwrap();
+        assert_eq!(t.check(&data()), Ok(()));
+    }
+}
@@ -1,9 +1,11 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::io::Write;
 
 use crate::node::*;
+use crate::parse::Tree;
 use crate::template::Template;
-use crate::utils::is_true;
+use crate::utils::{is_true, stack_exhausted, stack_position};
 
 use gtmpl_value::{Func, Value};
 
@@ -21,16 +23,82 @@ where
     node: Option<&'a Nodes>,
     vars: VecDeque<VecDeque<Variable>>,
     depth: usize,
+    // Stack position at the start of the execution.
+    stack_start: usize,
+    local_trees: Option<&'a HashMap<String, Tree>>,
+}
+
+// Default maximum nesting depth of templates, guarding against infinite recursion. A level
+// takes up to about 5.5 KiB of stack in debug builds, so this stays within `STACK_BUDGET`.
+pub(crate) const MAX_TEMPLATE_DEPTH: usize = 150;
+
+// Error of executions exceeding the `STACK_BUDGET`, e.g. with deeply nested bodies.
+pub(crate) const STACK_EXHAUSTED: &str = "exceeded the stack available for template execution";
+
+/// How missing keys of maps are handled, see
+/// [`Template::set_missing_key`](struct.Template.html#method.set_missing_key).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MissingKey {
+    /// The value is missing and printed as `<no value>`.
+    #[default]
+    Default,
+    /// The value is `nil`.
+    Zero,
+    /// Execution stops with an error.
+    Error,
+}
+
+/// Error of [`Template::try_render`](struct.Template.html#method.try_render).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenderError {
+    /// The template called `fail` with this message.
+    Fail(String),
+    /// Rendering failed for any other reason.
+    Other(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenderError::Fail(ref message) | RenderError::Other(ref message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl From<RenderError> for String {
+    fn from(err: RenderError) -> String {
+        err.to_string()
+    }
 }
 
 /// A Context for the template. Passed to the template exectution.
 pub struct Context {
-    dot: Value,
+    pub(crate) dot: Value,
+    #[cfg(feature = "random")]
+    pub(crate) seed: Option<u64>,
 }
 
 impl Context {
     pub fn empty() -> Context {
-        Context { dot: Value::Nil }
+        Context::from_value(Value::Nil)
+    }
+
+    pub(crate) fn from_value(dot: Value) -> Context {
+        Context {
+            dot,
+            #[cfg(feature = "random")]
+            seed: None,
+        }
+    }
+
+    /// Seeds the random functions for executions with this context, overriding the seed of
+    /// the template. See [`Template::set_seed`](struct.Template.html#method.set_seed).
+    #[cfg(feature = "random")]
+    pub fn with_seed(mut self, seed: u64) -> Context {
+        self.seed = Some(seed);
+        self
     }
 
     pub fn from<T>(value: T) -> Result<Context, String>
@@ -38,12 +106,22 @@ impl Context {
         T: Into<Value>,
     {
         let serialized: Value = value.into();
-        Ok(Context { dot: serialized })
+        Ok(Context::from_value(serialized))
     }
 }
 
 impl<'b> Template {
     pub fn execute<T: Write>(&self, writer: &'b mut T, data: &Context) -> Result<(), String> {
+        self.execute_template(writer, &self.name, data)
+    }
+
+    /// Executes the template `name` of this template's set, like golang's `ExecuteTemplate`.
+    pub fn execute_template<T: Write>(
+        &self,
+        writer: &'b mut T,
+        name: &str,
+        data: &Context,
+    ) -> Result<(), String> {
         let mut vars: VecDeque<VecDeque<Variable>> = VecDeque::new();
         let mut dot = VecDeque::new();
         dot.push_back(Variable {
@@ -52,19 +130,23 @@ impl<'b> Template {
         });
         vars.push_back(dot);
 
+        let _state = crate::funcs::enter(&self.state.for_execution(data));
+
         let mut state = State {
             template: self,
             writer,
             node: None,
             vars,
             depth: 0,
+            stack_start: stack_position(),
+            local_trees: None,
         };
 
         let root = self
             .tree_set
-            .get(&self.name)
+            .get(name)
             .and_then(|tree| tree.root.as_ref())
-            .ok_or_else(|| format!("{} is an incomplete or empty template", self.name))?;
+            .ok_or_else(|| format!("{} is an incomplete or empty template", name))?;
         state.walk(data, root)?;
 
         Ok(())
@@ -75,6 +157,40 @@ impl<'b> Template {
         self.execute(&mut w, data)?;
         String::from_utf8(w).map_err(|e| format!("unable to contert output into utf8: {}", e))
     }
+
+    /// Like [`render`](#method.render), but tells failures of the `fail` function apart from
+    /// other errors, see [`defaults`](funcs/defaults/index.html).
+    pub fn try_render(&self, data: &Context) -> Result<String, RenderError> {
+        #[cfg(feature = "defaults")]
+        crate::funcs::defaults::take_failure();
+        self.render(data).map_err(|err| {
+            #[cfg(feature = "defaults")]
+            {
+                if crate::funcs::defaults::take_failure().as_ref() == Some(&err) {
+                    return RenderError::Fail(err);
+                }
+            }
+            RenderError::Other(err)
+        })
+    }
+
+    /// Renders the template `name` of this template's set.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::{Context, Template};
+    ///
+    /// let mut tmpl = Template::default();
+    /// tmpl.add_template("greeting", "Hello {{ . }}!").unwrap();
+    /// let output = tmpl.render_template("greeting", &Context::from("World").unwrap());
+    /// assert_eq!(&output.unwrap(), "Hello World!");
+    /// ```
+    pub fn render_template(&self, name: &str, data: &Context) -> Result<String, String> {
+        let mut w: Vec<u8> = vec![];
+        self.execute_template(&mut w, name, data)?;
+        String::from_utf8(w).map_err(|e| format!("unable to contert output into utf8: {}", e))
+    }
 }
 
 impl<'a, 'b, T: Write> State<'a, 'b, T> {
@@ -101,6 +217,30 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
         Err(format!("variable {} not found", key))
     }
 
+    fn lookup_template(&self, name: &str) -> Option<&'a Nodes> {
+        self.local_trees
+            .and_then(|trees| trees.get(name))
+            .or_else(|| self.template.tree_set.get(name))
+            .and_then(|tree| tree.root.as_ref())
+    }
+
+    fn check_depth(&self) -> Result<(), String> {
+        if self.depth >= self.template.max_depth {
+            return Err(format!(
+                "exceeded maximum template depth ({})",
+                self.template.max_depth
+            ));
+        }
+        self.check_stack()
+    }
+
+    fn check_stack(&self) -> Result<(), String> {
+        if stack_exhausted(self.stack_start) {
+            return Err(String::from(STACK_EXHAUSTED));
+        }
+        Ok(())
+    }
+
     fn walk_list(&mut self, ctx: &Context, node: &'a ListNode) -> Result<(), String> {
         for n in &node.nodes {
             self.walk(ctx, n)?;
@@ -111,6 +251,7 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
     // Top level walk function. Steps through the major parts for the template strcuture and
     // writes to the output.
     fn walk(&mut self, ctx: &Context, node: &'a Nodes) -> Result<(), String> {
+        self.check_stack()?;
         self.node = Some(node);
         match *node {
             Nodes::Action(ref n) => {
@@ -140,35 +281,44 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
                 }
             }
         };
-        let tree = self.template.tree_set.get(&name);
-        if let Some(tree) = tree {
-            if let Some(ref root) = tree.root {
-                let mut vars = VecDeque::new();
-                let mut dot = VecDeque::new();
-                let value = if let Some(ref pipe) = template.pipe {
-                    self.eval_pipeline(ctx, pipe)?
-                } else {
-                    Value::NoValue
-                };
-                dot.push_back(Variable {
-                    name: "$".to_owned(),
-                    value: value.clone(),
-                });
-                vars.push_back(dot);
-                let mut new_state = State {
-                    template: self.template,
-                    writer: self.writer,
-                    node: None,
-                    vars,
-                    depth: self.depth + 1,
-                };
-                return new_state.walk(&Context::from(value)?, root);
+        let loaded;
+        let root = match self.lookup_template(&name) {
+            Some(root) => root,
+            None => {
+                loaded = self.template.load_template(&name)?;
+                match loaded.as_ref().and_then(|tree| tree.root.as_ref()) {
+                    Some(root) => root,
+                    None => return Err(String::from("work in progress")),
+                }
             }
-        }
-        Err(String::from("work in progress"))
+        };
+        self.check_depth()?;
+        let mut vars = VecDeque::new();
+        let mut dot = VecDeque::new();
+        let value = if let Some(ref pipe) = template.pipe {
+            self.eval_pipeline(ctx, pipe)?
+        } else {
+            Value::NoValue
+        };
+        dot.push_back(Variable {
+            name: "$".to_owned(),
+            value: value.clone(),
+        });
+        vars.push_back(dot);
+        let mut new_state = State {
+            template: self.template,
+            writer: self.writer,
+            node: None,
+            vars,
+            depth: self.depth + 1,
+            stack_start: self.stack_start,
+            local_trees: self.local_trees,
+        };
+        new_state.walk(&Context::from(value)?, root)
     }
 
     fn eval_pipeline(&mut self, ctx: &Context, pipe: &PipeNode) -> Result<Value, String> {
+        self.check_stack()?;
         let mut val: Option<Value> = None;
         for cmd in &pipe.cmds {
             val = Some(self.eval_command(ctx, cmd, &val)?);
@@ -231,9 +381,95 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
             .funcs
             .get(name.as_str())
             .ok_or_else(|| format!("{} is not a defined function", name))?;
+        #[cfg(feature = "include")]
+        {
+            if self.template.state.include {
+                match name.as_str() {
+                    "include" => return self.eval_include(ctx, args, fin),
+                    "tpl" => return self.eval_tpl(ctx, args, fin),
+                    _ => {}
+                }
+            }
+        }
         self.eval_call(ctx, *function, args, fin)
     }
 
+    #[cfg(feature = "include")]
+    fn eval_include(
+        &mut self,
+        ctx: &Context,
+        args: &[Nodes],
+        fin: &Option<Value>,
+    ) -> Result<Value, String> {
+        let arg_vals = self.eval_args(ctx, args, fin)?;
+        crate::funcs::check_args("include", &arg_vals, 2)?;
+        let name = crate::utils::strval(&arg_vals[0]);
+        if let Some(root) = self.lookup_template(&name) {
+            return self.render_nested(root, &arg_vals[1], self.local_trees);
+        }
+        let loaded = self.template.load_template(&name)?;
+        let root = loaded
+            .as_ref()
+            .and_then(|tree| tree.root.as_ref())
+            .ok_or_else(|| format!("include: template {} is not defined", name))?;
+        self.render_nested(root, &arg_vals[1], self.local_trees)
+    }
+
+    #[cfg(feature = "include")]
+    fn eval_tpl(
+        &mut self,
+        ctx: &Context,
+        args: &[Nodes],
+        fin: &Option<Value>,
+    ) -> Result<Value, String> {
+        let arg_vals = self.eval_args(ctx, args, fin)?;
+        crate::funcs::check_args("tpl", &arg_vals, 2)?;
+        let trees = self
+            .template
+            .parse_trees(String::from("tpl"), crate::utils::strval(&arg_vals[0]))?;
+        match trees.get("tpl").and_then(|tree| tree.root.as_ref()) {
+            Some(root) => self.render_nested(root, &arg_vals[1], Some(&trees)),
+            None => Ok(Value::from("")),
+        }
+    }
+
+    // Renders another template with `dot` into a string.
+    #[cfg(feature = "include")]
+    fn render_nested<'c>(
+        &self,
+        root: &'c Nodes,
+        dot: &Value,
+        local_trees: Option<&'c HashMap<String, Tree>>,
+    ) -> Result<Value, String>
+    where
+        'a: 'c,
+    {
+        self.check_depth()?;
+        let mut vars = VecDeque::new();
+        let mut vars_dot = VecDeque::new();
+        vars_dot.push_back(Variable {
+            name: "$".to_owned(),
+            value: dot.clone(),
+        });
+        vars.push_back(vars_dot);
+        let mut buf: Vec<u8> = vec![];
+        {
+            let mut state = State {
+                template: self.template,
+                writer: &mut buf,
+                node: None,
+                vars,
+                depth: self.depth + 1,
+                stack_start: self.stack_start,
+                local_trees,
+            };
+            state.walk(&Context::from_value(dot.clone()), root)?;
+        }
+        String::from_utf8(buf)
+            .map(Value::from)
+            .map_err(|e| format!("unable to contert output into utf8: {}", e))
+    }
+
     fn eval_call(
         &mut self,
         ctx: &Context,
@@ -241,6 +477,16 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
         args: &[Nodes],
         fin: &Option<Value>,
     ) -> Result<Value, String> {
+        let arg_vals = self.eval_args(ctx, args, fin)?;
+        function(&arg_vals)
+    }
+
+    fn eval_args(
+        &mut self,
+        ctx: &Context,
+        args: &[Nodes],
+        fin: &Option<Value>,
+    ) -> Result<Vec<Value>, String> {
         let mut arg_vals = vec![];
         if !args.is_empty() {
             for arg in &args[1..] {
@@ -251,8 +497,7 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
         if let Some(ref f) = *fin {
             arg_vals.push(f.clone());
         }
-
-        function(&arg_vals)
+        Ok(arg_vals)
     }
 
     fn eval_chain_node(
@@ -337,7 +582,14 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
                 .get(field_name)
                 .cloned()
                 .ok_or_else(|| format!("no field {} for {}", field_name, receiver)),
-            Value::Map(ref o) => Ok(o.get(field_name).cloned().unwrap_or_else(|| Value::NoValue)),
+            Value::Map(ref o) => match (o.get(field_name), self.template.missing_key) {
+                (Some(v), _) => Ok(v.clone()),
+                (None, MissingKey::Default) => Ok(Value::NoValue),
+                (None, MissingKey::Zero) => Ok(Value::Nil),
+                (None, MissingKey::Error) => {
+                    Err(format!("map has no entry for key {}", field_name))
+                }
+            },
             _ => Err(String::from("only maps and objects have fields")),
         };
         if let Ok(Value::Function(ref f)) = ret {
@@ -372,7 +624,7 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
             match *node {
                 Nodes::If(ref n) => self.walk_list(ctx, &n.list)?,
                 Nodes::With(ref n) => {
-                    let ctx = Context { dot: val };
+                    let ctx = Context::from_value(val);
                     self.walk_list(&ctx, &n.list)?;
                 }
                 _ => {}
@@ -404,7 +656,7 @@ impl<'a, 'b, T: Write> State<'a, 'b, T> {
         }
         let vars = VecDeque::new();
         self.vars.push_back(vars);
-        let ctx = Context { dot: val };
+        let ctx = Context::from_value(val);
         self.walk_list(&ctx, &range.list)?;
         self.vars.pop_back();
         Ok(())
@@ -949,4 +1201,171 @@ mod tests_mocked {
         assert!(out.is_ok());
         assert_eq!(String::from_utf8(w).unwrap(), "bar");
     }
+
+    #[test]
+    fn test_missing_key() {
+        let data = Context::from(HashMap::<String, Value>::new()).unwrap();
+        let mut t = Template::default();
+        assert!(t.parse("{{ .a }}").is_ok());
+        assert_eq!(t.render(&data), Ok(String::from("<no value>")));
+        t.set_missing_key(MissingKey::Zero);
+        assert_eq!(t.render(&data), Ok(String::from("nil")));
+        t.set_missing_key(MissingKey::Error);
+        assert_eq!(
+            t.render(&data),
+            Err(String::from("map has no entry for key a"))
+        );
+    }
+
+    #[test]
+    fn test_recursion_depth() {
+        let mut t = Template::default();
+        t.set_max_depth(100);
+        assert!(t
+            .parse(r#"{{ define "loop" }}{{ template "loop" . }}{{ end }}{{ template "loop" }}"#)
+            .is_ok());
+        let out = t.render(&Context::empty());
+        assert_eq!(
+            out,
+            Err(String::from("exceeded maximum template depth (100)"))
+        );
+
+        let mut data = Value::from(0);
+        for _ in 0..140 {
+            let mut m = HashMap::new();
+            m.insert(String::from("n"), data);
+            data = Value::Object(m);
+        }
+        let mut t = Template::default();
+        assert!(t
+            .parse(concat!(
+                r#"{{ define "r" }}{{ with .n }}{{ template "r" . }}{{ else }}end{{ end }}{{ end }}"#,
+                r#"{{ template "r" . }}"#
+            ))
+            .is_ok());
+        assert_eq!(
+            t.render(&Context::from(data).unwrap()),
+            Ok(String::from("end"))
+        );
+    }
+
+    // Runs `f` on a thread with the default stack size of spawned threads.
+    fn on_small_stack<F: FnOnce() -> Vec<Result<String, String>> + Send + 'static>(
+        f: F,
+    ) -> Vec<Result<String, String>> {
+        std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_recursion_stack() {
+        let results = on_small_stack(|| {
+            let mut results = vec![];
+            let mut t = Template::default();
+            t.parse(
+                r#"{{ define "loop" }}{{ template "loop" . }}{{ end }}{{ template "loop" . }}"#,
+            )
+            .unwrap();
+            results.push(t.render(&Context::from(1).unwrap()));
+            let mut t = Template::default();
+            t.parse(format!(
+                r#"{{{{ define "loop" }}}}{}{{{{ template "loop" . }}}}{}{{{{ end }}}}{{{{ template "loop" . }}}}"#,
+                "{{ with . }}".repeat(100),
+                "{{ end }}".repeat(100)
+            ))
+            .unwrap();
+            results.push(t.render(&Context::from(1).unwrap()));
+            #[cfg(feature = "include")]
+            {
+                let mut t = Template::default();
+                t.add_include_funcs();
+                t.parse(
+                    r#"{{ define "loop" }}{{ include "loop" . }}{{ end }}{{ include "loop" . }}"#,
+                )
+                .unwrap();
+                results.push(t.render(&Context::from(1).unwrap()));
+                let mut t = Template::default();
+                t.add_include_funcs();
+                t.parse(r#"{{ tpl . . }}"#).unwrap();
+                results.push(t.render(&Context::from("{{ tpl . . }}").unwrap()));
+            }
+            results
+        });
+        let depth_exceeded = Err(format!(
+            "exceeded maximum template depth ({})",
+            MAX_TEMPLATE_DEPTH
+        ));
+        assert_eq!(results[0], depth_exceeded);
+        assert_eq!(results[1], Err(String::from(STACK_EXHAUSTED)));
+        for result in &results[2..] {
+            assert_eq!(result, &depth_exceeded);
+        }
+    }
+
+    #[cfg(feature = "include")]
+    #[test]
+    fn test_include() {
+        let mut t = Template::default();
+        t.add_include_funcs();
+        assert!(t
+            .parse(concat!(
+                r#"{{ define "item" }}<{{ . }}>{{ end }}"#,
+                r#"{{ include "item" . | printf "%q" }}{{ include "item" . | len }}"#
+            ))
+            .is_ok());
+        let out = t.render(&Context::from("a").unwrap());
+        assert_eq!(out, Ok(String::from(r#""<a>"3"#)));
+
+        let mut t = Template::default();
+        t.add_include_funcs();
+        assert!(t.parse(r#"{{ include "missing" . }}"#).is_ok());
+        assert!(t.render(&Context::empty()).is_err());
+
+        let mut t = Template::default();
+        t.add_include_funcs();
+        t.set_max_depth(100);
+        assert!(t
+            .parse(r#"{{ define "loop" }}{{ include "loop" . }}{{ end }}{{ include "loop" . }}"#)
+            .is_ok());
+        let out = t.render(&Context::empty());
+        assert_eq!(
+            out,
+            Err(String::from("exceeded maximum template depth (100)"))
+        );
+    }
+
+    #[cfg(feature = "include")]
+    #[test]
+    fn test_tpl() {
+        let mut t = Template::default();
+        t.add_include_funcs();
+        t.add_template("item", "<{{ . }}>").unwrap();
+        assert!(t
+            .parse(concat!(
+                r#"{{ tpl "{{ template \"item\" . }}{{ len . }}" . }}"#,
+                r#"{{ tpl "{{ define \"x\" }}x{{ end }}{{ include \"x\" . }}" . }}"#
+            ))
+            .is_ok());
+        let out = t.render(&Context::from("ab").unwrap());
+        assert_eq!(out, Ok(String::from("<ab>2x")));
+
+        let mut t = Template::default();
+        t.add_include_funcs();
+        assert!(t.parse(r#"{{ tpl "{{ unknownFunc }}" . }}"#).is_ok());
+        assert!(t.render(&Context::empty()).is_err());
+
+        let mut t = Template::default();
+        t.add_include_funcs();
+        t.set_max_depth(100);
+        assert!(t.parse(r#"{{ tpl . . }}"#).is_ok());
+        let out = t.render(&Context::from("{{ tpl . . }}").unwrap());
+        assert_eq!(
+            out,
+            Err(String::from("exceeded maximum template depth (100)"))
+        );
+    }
 }
@@ -0,0 +1,247 @@
+//! Parsing templates from files.
+extern crate glob;
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use self::glob::glob;
+use crate::template::Template;
+
+/// How templates parsed from files are named.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TemplateNaming {
+    /// The base name of the file like `base.tmpl`, golang's rule. Files with the same base
+    /// name replace each other.
+    #[default]
+    BaseName,
+    /// The path relative to the directory passed to `parse_dir` or to the first directory of
+    /// a `parse_glob` pattern containing wildcards, e.g. `partials/base.tmpl`. `parse_files`
+    /// uses the paths as given. Components are always separated by `/`.
+    RelativePath,
+}
+
+// Joins the normal components of a path with `/`.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(c) => Some(c.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Returns the leading directories of a glob pattern without wildcards.
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+fn collect_dir(dir: &Path, ext: &str, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("unable to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("unable to read {}: {}", dir.display(), e))?;
+        let path = entry.path();
+        // Symbolic links to directories are not followed to avoid cycles.
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if is_dir {
+            collect_dir(&path, ext, files)?;
+        } else if path.is_file() && path.extension() == Some(std::ffi::OsStr::new(ext)) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files matching a glob pattern in lexical order.
+pub(crate) fn glob_files(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let paths = glob(pattern).map_err(|e| format!("invalid pattern {}: {}", pattern, e))?;
+    let mut files = vec![];
+    for path in paths {
+        let path = path.map_err(|e| format!("{}", e))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the files below `root` with the given extension in lexical order.
+pub(crate) fn dir_files(root: &Path, extension: &str) -> Result<Vec<PathBuf>, String> {
+    let mut files = vec![];
+    collect_dir(root, extension.trim_start_matches('.'), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+impl Template {
+    /// Sets how [`parse_files`](#method.parse_files), [`parse_glob`](#method.parse_glob) and
+    /// [`parse_dir`](#method.parse_dir) name templates. Defaults to
+    /// [`TemplateNaming::BaseName`](enum.TemplateNaming.html).
+    pub fn set_naming(&mut self, naming: TemplateNaming) {
+        self.naming = naming;
+    }
+
+    fn parse_file(&mut self, path: &Path, base: &Path) -> Result<(), String> {
+        let name = match self.naming {
+            TemplateNaming::BaseName => path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            TemplateNaming::RelativePath => slash_path(path.strip_prefix(base).unwrap_or(path)),
+        };
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        self.add_template(name.as_str(), text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if self.name.is_empty() {
+            self.name = name;
+        }
+        Ok(())
+    }
+
+    /// Parses the given files as templates named like set with
+    /// [`set_naming`](#method.set_naming). A template without a name takes the name of the
+    /// first file.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::{Context, Template};
+    ///
+    /// let dir = std::env::temp_dir();
+    /// std::fs::write(dir.join("hello.tmpl"), r#"Hello {{ template "name.tmpl" . }}!"#).unwrap();
+    /// std::fs::write(dir.join("name.tmpl"), "{{ . }}").unwrap();
+    /// let mut tmpl = Template::default();
+    /// tmpl.parse_files(&[dir.join("hello.tmpl"), dir.join("name.tmpl")]).unwrap();
+    /// let output = tmpl.render(&Context::from("World").unwrap());
+    /// assert_eq!(&output.unwrap(), "Hello World!");
+    /// ```
+    pub fn parse_files<P: AsRef<Path>>(&mut self, paths: &[P]) -> Result<(), String> {
+        if paths.is_empty() {
+            return Err(String::from("no files named in call to parse_files"));
+        }
+        for path in paths {
+            self.parse_file(path.as_ref(), Path::new(""))?;
+        }
+        Ok(())
+    }
+
+    /// Parses the files matching a glob pattern like `templates/*.tmpl` in lexical order, see
+    /// [`parse_files`](#method.parse_files). Fails if no file matches.
+    pub fn parse_glob(&mut self, pattern: &str) -> Result<(), String> {
+        let files = glob_files(pattern)?;
+        if files.is_empty() {
+            return Err(format!("pattern matches no files: {}", pattern));
+        }
+        let base = glob_base(pattern);
+        for path in &files {
+            self.parse_file(path, &base)?;
+        }
+        Ok(())
+    }
+
+    /// Parses all files below `root` with the given extension like `tmpl`, see
+    /// [`parse_files`](#method.parse_files). Files are parsed in lexical order of their paths.
+    /// Fails if there is no such file.
+    pub fn parse_dir<P: AsRef<Path>>(&mut self, root: P, extension: &str) -> Result<(), String> {
+        let root = root.as_ref();
+        let files = dir_files(root, extension)?;
+        if files.is_empty() {
+            return Err(format!(
+                "no files with extension {} below {}",
+                extension.trim_start_matches('.'),
+                root.display()
+            ));
+        }
+        for path in &files {
+            self.parse_file(path, root)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::exec::Context;
+
+    fn setup(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gtmpl-files-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("partials")).unwrap();
+        fs::write(
+            dir.join("main.tmpl"),
+            r#"{{ template "head.tmpl" . }} {{ template "partials/body.tmpl" . }}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("head.tmpl"), "head {{ . }}").unwrap();
+        fs::write(dir.join("partials/body.tmpl"), "body {{ . }}").unwrap();
+        fs::write(dir.join("partials/broken.tpl"), "{{ if }}").unwrap();
+        fs::write(dir.join("notes.txt"), "{{").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_files() {
+        let dir = setup("parse-files");
+        let mut t = Template::default();
+        assert!(t
+            .parse_files(&[dir.join("head.tmpl"), dir.join("partials/body.tmpl")])
+            .is_ok());
+        assert_eq!(t.name, "head.tmpl");
+        assert!(t.tree_set.contains_key("body.tmpl"));
+        assert!(t.parse_files::<&str>(&[]).is_err());
+        let err = t
+            .parse_files(&[dir.join("partials/broken.tpl")])
+            .unwrap_err();
+        assert!(err.starts_with(&format!(
+            "{}: template: broken.tpl:",
+            dir.join("partials/broken.tpl").display()
+        )));
+        assert!(t.parse_files(&[dir.join("missing.tmpl")]).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_glob() {
+        let dir = setup("parse-glob");
+        let mut t = Template::with_name("main.tmpl");
+        t.set_naming(TemplateNaming::RelativePath);
+        let pattern = format!("{}/**/*.tmpl", dir.display());
+        assert!(t.parse_glob(&pattern).is_ok());
+        let out = t.render(&Context::from(1).unwrap());
+        assert_eq!(out, Ok(String::from("head 1 body 1")));
+        assert!(t.parse_glob(&format!("{}/*.none", dir.display())).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_dir() {
+        let dir = setup("parse-dir");
+        let mut t = Template::default();
+        assert!(t.parse_dir(&dir, ".tmpl").is_ok());
+        assert_eq!(t.name, "head.tmpl");
+        let mut keys: Vec<_> = t.tree_set.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["body.tmpl", "head.tmpl", "main.tmpl"]);
+
+        let mut t = Template::with_name("main.tmpl");
+        t.set_naming(TemplateNaming::RelativePath);
+        assert!(t.parse_dir(&dir, "tmpl").is_ok());
+        let out = t.render(&Context::from(2).unwrap());
+        assert_eq!(out, Ok(String::from("head 2 body 2")));
+        assert!(t.parse_dir(&dir, "tpl").unwrap_err().contains("broken.tpl"));
+        assert_eq!(
+            t.parse_dir(&dir, ".none"),
+            Err(format!(
+                "no files with extension none below {}",
+                dir.display()
+            ))
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+}
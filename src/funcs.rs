@@ -1,4 +1,5 @@
 //! Builtin functions.
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fmt::Write;
 
@@ -7,9 +8,39 @@ use gtmpl_value::{Func, Value};
 extern crate percent_encoding;
 use self::percent_encoding::{utf8_percent_encode, DEFAULT_ENCODE_SET};
 
+use crate::exec::Context;
 use crate::printf::sprintf;
 use crate::utils::is_true;
 
+#[cfg(feature = "defaults")]
+pub mod defaults;
+#[cfg(feature = "dicts")]
+pub mod dicts;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+#[cfg(feature = "host")]
+pub mod host;
+#[cfg(feature = "include")]
+pub mod include;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "lists")]
+pub mod lists;
+#[cfg(feature = "math")]
+pub mod math;
+#[cfg(feature = "random")]
+pub mod random;
+#[cfg(feature = "regex")]
+pub mod regex;
+#[cfg(feature = "semver")]
+pub mod semver;
+#[cfg(feature = "strings")]
+pub mod strings;
+#[cfg(feature = "time")]
+pub mod time;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
 pub static BUILTINS: &[(&str, Func)] = &[
     ("eq", eq as Func),
     ("ne", ne as Func),
@@ -123,6 +154,96 @@ macro_rules! gn {
     }
 }
 
+/// Returns an error unless `name` got exactly `n` arguments.
+#[cfg(any(
+    feature = "defaults",
+    feature = "dicts",
+    feature = "encoding",
+    feature = "host",
+    feature = "include",
+    feature = "json",
+    feature = "lists",
+    feature = "math",
+    feature = "random",
+    feature = "regex",
+    feature = "semver",
+    feature = "strings",
+    feature = "time",
+    feature = "yaml",
+))]
+pub(crate) fn check_args(name: &str, args: &[Value], n: usize) -> Result<(), String> {
+    if args.len() != n {
+        return Err(format!(
+            "{} requires {} argument{}",
+            name,
+            n,
+            if n == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(())
+}
+
+/// State of the optional function packs shared by all executions of a `Template`.
+#[derive(Clone, Default)]
+pub(crate) struct FuncState {
+    #[cfg(feature = "regex")]
+    pub regex_cache: regex::Cache,
+    #[cfg(feature = "time")]
+    pub clock: Option<time::Clock>,
+    #[cfg(feature = "host")]
+    pub host_root: Option<std::path::PathBuf>,
+    #[cfg(feature = "include")]
+    pub include: bool,
+    #[cfg(feature = "random")]
+    pub seed: Option<u64>,
+    #[cfg(feature = "random")]
+    pub rng: Option<random::Rng>,
+}
+
+impl FuncState {
+    /// Returns the state for a single execution of the template with `data`.
+    #[cfg_attr(not(feature = "random"), allow(unused_variables))]
+    pub(crate) fn for_execution(&self, data: &Context) -> FuncState {
+        #[cfg(feature = "random")]
+        return FuncState {
+            rng: Some(random::Rng::new(data.seed.or(self.seed))),
+            ..self.clone()
+        };
+        #[cfg(not(feature = "random"))]
+        self.clone()
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<Option<FuncState>> = const { RefCell::new(None) };
+}
+
+/// Makes `state` available to functions called on this thread until the guard is dropped.
+pub(crate) fn enter(state: &FuncState) -> StateGuard {
+    StateGuard(STATE.with(|s| s.replace(Some(state.clone()))))
+}
+
+pub(crate) struct StateGuard(Option<FuncState>);
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        let prev = self.0.take();
+        STATE.with(|s| *s.borrow_mut() = prev);
+    }
+}
+
+/// Calls `f` with the state of the template executing on this thread. Functions called
+/// directly, outside of an execution, get `None`.
+#[cfg(any(
+    feature = "host",
+    feature = "random",
+    feature = "regex",
+    feature = "time",
+))]
+pub(crate) fn with_state<R, F: FnOnce(Option<&FuncState>) -> R>(f: F) -> R {
+    STATE.with(|s| f(s.borrow().as_ref()))
+}
+
 ///	Returns the boolean OR of its arguments by returning the
 ///	first non-empty argument or the last argument, that is,
 ///	"or x y" behaves as "if x then x else y". All the
@@ -311,10 +432,11 @@ pub fn println(args: &[Value]) -> Result<Value, String> {
 }
 
 /// An implementation of golang's fmt.Sprintf
+///
+/// Like in golang mistakes are reported inline, e.g. `%!d(string=foo)` for a wrong verb or
+/// `%!d(MISSING)` for a missing argument.
 /// Limitations:
-/// - float:
-///   * `g`, `G`, and `b` are weired and not implement yet
-/// - pretty sure there are more
+/// - integral floats are stored as integers by `gtmpl_value` and print as such with `%v`
 ///
 /// # Example
 /// ```
@@ -357,7 +479,7 @@ pub fn index(args: &[Value]) -> Result<Value, String> {
     Ok(col.clone())
 }
 
-fn get_item<'a>(col: &'a Value, key: &Value) -> Result<&'a Value, String> {
+pub(crate) fn get_item<'a>(col: &'a Value, key: &Value) -> Result<&'a Value, String> {
     let ret = match (col, key) {
         (&Value::Array(ref a), &Value::Number(ref n)) => {
             if let Some(i) = n.as_u64() {
@@ -515,7 +637,7 @@ ge(a: ref Value, b: ref Value) -> Result<Value, String> {
     Ok(Value::from(ret))
 });
 
-fn cmp(left: &Value, right: &Value) -> Option<Ordering> {
+pub(crate) fn cmp(left: &Value, right: &Value) -> Option<Ordering> {
     match (left, right) {
         (&Value::Number(ref l), &Value::Number(ref r)) => {
             if let (Some(lf), Some(rf)) = (l.as_f64(), r.as_f64()) {
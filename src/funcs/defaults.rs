@@ -0,0 +1,177 @@
+//! Default value and flow control functions compatible with
+//! [Sprig](https://masterminds.github.io/sprig/defaults.html) and Helm.
+//!
+//! Emptiness follows golang's truthiness like `if` does: `false`, `0`, `nil`, the empty
+//! string and empty collections are empty.
+//!
+//! ## Example
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_default_funcs();
+//! tmpl.parse(r#"{{ .name | default "world" }} {{ ternary "yes" "no" (empty .) }}"#).unwrap();
+//! let ctx: HashMap<String, String> = HashMap::new();
+//! let output = tmpl.render(&Context::from(ctx).unwrap());
+//! assert_eq!(&output.unwrap(), "world yes");
+//! ```
+//!
+//! `fail` aborts rendering with a message for the user, which
+//! [`Template::try_render`](../../struct.Template.html#method.try_render) tells apart from
+//! other errors:
+//! ```rust
+//! use gtmpl::{Context, RenderError, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_default_funcs();
+//! tmpl.parse(r#"{{ if . }}{{ fail "no way" }}{{ else }}{{ .x }}{{ end }}"#).unwrap();
+//! let err = tmpl.try_render(&Context::from(true).unwrap()).unwrap_err();
+//! assert_eq!(err, RenderError::Fail(String::from("no way")));
+//! let err = tmpl.try_render(&Context::from(false).unwrap()).unwrap_err();
+//! assert!(matches!(err, RenderError::Other(_)));
+//! ```
+use std::cell::RefCell;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::utils::{is_true, strval};
+
+/// All default and flow control functions of this module.
+pub static DEFAULTS: &[(&str, Func)] = &[
+    ("default", default as Func),
+    ("empty", empty as Func),
+    ("coalesce", coalesce as Func),
+    ("ternary", ternary as Func),
+    ("required", required as Func),
+    ("fail", fail as Func),
+];
+
+thread_local! {
+    // Message of the last `fail` call on this thread.
+    static FAILURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Takes the message of the last `fail` call on this thread.
+pub(crate) fn take_failure() -> Option<String> {
+    FAILURE.with(|f| f.borrow_mut().take())
+}
+
+/// `default DEFAULT VALUE` returns `VALUE` unless it is empty or missing, `DEFAULT` otherwise.
+pub fn default(args: &[Value]) -> Result<Value, String> {
+    match args.len() {
+        1 => Ok(args[0].clone()),
+        2 if is_true(&args[1]) => Ok(args[1].clone()),
+        2 => Ok(args[0].clone()),
+        _ => Err(String::from("default requires 1 or 2 arguments")),
+    }
+}
+
+/// Returns true if its argument is empty.
+pub fn empty(args: &[Value]) -> Result<Value, String> {
+    check_args("empty", args, 1)?;
+    Ok(Value::from(!is_true(&args[0])))
+}
+
+/// Returns the first non empty argument or nil.
+pub fn coalesce(args: &[Value]) -> Result<Value, String> {
+    Ok(args
+        .iter()
+        .find(|v| is_true(v))
+        .cloned()
+        .unwrap_or(Value::Nil))
+}
+
+/// `ternary TRUE FALSE CONDITION` returns `TRUE` if the condition is not empty, `FALSE`
+/// otherwise.
+pub fn ternary(args: &[Value]) -> Result<Value, String> {
+    check_args("ternary", args, 3)?;
+    if is_true(&args[2]) {
+        Ok(args[0].clone())
+    } else {
+        Ok(args[1].clone())
+    }
+}
+
+/// `required MESSAGE VALUE` returns `VALUE` or fails with `MESSAGE` if it is nil, missing or
+/// the empty string. Like in Helm `0` and `false` are valid values.
+pub fn required(args: &[Value]) -> Result<Value, String> {
+    check_args("required", args, 2)?;
+    match args[1] {
+        Value::Nil | Value::NoValue => Err(strval(&args[0])),
+        Value::String(ref s) if s.is_empty() => Err(strval(&args[0])),
+        ref v => Ok(v.clone()),
+    }
+}
+
+/// `fail MESSAGE` aborts rendering with `MESSAGE` as error.
+/// [`Template::try_render`](../../struct.Template.html#method.try_render) reports it as
+/// `RenderError::Fail`.
+pub fn fail(args: &[Value]) -> Result<Value, String> {
+    check_args("fail", args, 1)?;
+    let message = strval(&args[0]);
+    FAILURE.with(|f| *f.borrow_mut() = Some(message.clone()));
+    Err(message)
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::RenderError;
+
+    #[test]
+    fn test_default() {
+        assert_eq!(default(&["d".into(), "v".into()]), Ok(Value::from("v")));
+        assert_eq!(default(&["d".into(), "".into()]), Ok(Value::from("d")));
+        assert_eq!(default(&["d".into(), 0.into()]), Ok(Value::from("d")));
+        assert_eq!(default(&["d".into(), Value::NoValue]), Ok(Value::from("d")));
+        assert_eq!(default(&["d".into()]), Ok(Value::from("d")));
+    }
+
+    #[test]
+    fn test_empty_coalesce() {
+        assert_eq!(empty(&[Value::Nil]), Ok(Value::from(true)));
+        assert_eq!(empty(&[vec![1].into()]), Ok(Value::from(false)));
+        assert_eq!(
+            coalesce(&[0.into(), "".into(), "a".into(), "b".into()]),
+            Ok(Value::from("a"))
+        );
+        assert_eq!(coalesce(&[0.into(), false.into()]), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn test_ternary() {
+        let args = ["a".into(), "b".into(), true.into()];
+        assert_eq!(ternary(&args), Ok(Value::from("a")));
+        let args = ["a".into(), "b".into(), 0.into()];
+        assert_eq!(ternary(&args), Ok(Value::from("b")));
+    }
+
+    #[test]
+    fn test_required_fail() {
+        assert_eq!(
+            required(&["need it".into(), Value::NoValue]),
+            Err(String::from("need it"))
+        );
+        assert!(required(&["need it".into(), "".into()]).is_err());
+        assert_eq!(required(&["need it".into(), 0.into()]), Ok(Value::from(0)));
+        take_failure();
+        assert_eq!(fail(&["boom".into()]), Err(String::from("boom")));
+        assert_eq!(take_failure(), Some(String::from("boom")));
+        assert_eq!(take_failure(), None);
+    }
+
+    #[test]
+    fn test_try_render() {
+        let mut t = crate::Template::default();
+        t.add_default_funcs();
+        t.parse(r#"{{ required "fail: x" . }}{{ fail . }}"#)
+            .unwrap();
+        let render = |data: &str| t.try_render(&crate::Context::from(data).unwrap());
+        assert_eq!(render(""), Err(RenderError::Other(String::from("fail: x"))));
+        assert_eq!(render("boom"), Err(RenderError::Fail(String::from("boom"))));
+        assert_eq!(render(""), Err(RenderError::Other(String::from("fail: x"))));
+    }
+}
@@ -0,0 +1,348 @@
+//! Dictionary functions modelled on
+//! [Sprig](https://masterminds.github.io/sprig/dicts.html).
+//!
+//! The functions operate on `Value::Map` and `Value::Object`. They never modify their
+//! arguments but return a new dictionary of the same kind instead.
+//!
+//! This makes `set` and `unset` incompatible with Sprig, where they change the
+//! dictionary in place. `{{ $_ := set $d "k" "v" }}` leaves `$d` as it was, the
+//! result has to be declared as `$d` again:
+//!
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_dict_funcs();
+//! tmpl.parse(concat!(
+//!     r#"{{ $d := dict "a" 1 }}{{ $_ := set $d "a" 2 }}{{ $d.a }} "#,
+//!     r#"{{ $d := set $d "a" 3 }}{{ $d.a }}"#,
+//! )).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "1 3");
+//! ```
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_dict_funcs();
+//! tmpl.add_template("greet", "{{ .greeting }} {{ .name }}").unwrap();
+//! tmpl.parse(r#"{{ template "greet" (dict "greeting" "Hello" "name" .) }}"#).unwrap();
+//! let output = tmpl.render(&Context::from("world").unwrap());
+//! assert_eq!(&output.unwrap(), "Hello world");
+//! ```
+use std::collections::HashMap;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::utils::strval;
+
+/// All dictionary functions of this module.
+pub static DICTS: &[(&str, Func)] = &[
+    ("dict", dict as Func),
+    ("get", get as Func),
+    ("set", set as Func),
+    ("unset", unset as Func),
+    ("hasKey", has_key as Func),
+    ("keys", keys as Func),
+    ("values", values as Func),
+    ("pick", pick as Func),
+    ("omit", omit as Func),
+    ("merge", merge as Func),
+    ("mergeOverwrite", merge_overwrite as Func),
+    ("deepCopy", deep_copy as Func),
+    ("dig", dig as Func),
+];
+
+fn dict_arg<'a>(name: &str, val: &'a Value) -> Result<&'a HashMap<String, Value>, String> {
+    match *val {
+        Value::Map(ref m) | Value::Object(ref m) => Ok(m),
+        _ => Err(format!("{} requires a dict, got {}", name, val)),
+    }
+}
+
+/// Wraps `m` in the same kind of dictionary as `like`.
+fn same_kind(like: &Value, m: HashMap<String, Value>) -> Value {
+    match *like {
+        Value::Object(_) => Value::Object(m),
+        _ => Value::Map(m),
+    }
+}
+
+fn sorted_keys(m: &HashMap<String, Value>) -> Vec<&String> {
+    let mut keys: Vec<&String> = m.keys().collect();
+    keys.sort();
+    keys
+}
+
+/// `dict KEY VALUE...` creates a dictionary from key value pairs. A missing last value is
+/// an empty string.
+pub fn dict(args: &[Value]) -> Result<Value, String> {
+    let m = args
+        .chunks(2)
+        .map(|kv| {
+            let v = kv.get(1).cloned().unwrap_or_else(|| Value::from(""));
+            (strval(&kv[0]), v)
+        })
+        .collect();
+    Ok(Value::Map(m))
+}
+
+/// `get DICT KEY` returns the value for the key or an empty string if it is missing.
+pub fn get(args: &[Value]) -> Result<Value, String> {
+    check_args("get", args, 2)?;
+    let m = dict_arg("get", &args[0])?;
+    Ok(m.get(&strval(&args[1]))
+        .cloned()
+        .unwrap_or_else(|| Value::from("")))
+}
+
+/// `set DICT KEY VALUE` returns a copy of the dictionary with the key set to the value.
+///
+/// Unlike Sprig the dictionary itself is not changed, use `{{ $d := set $d KEY VALUE }}`.
+pub fn set(args: &[Value]) -> Result<Value, String> {
+    check_args("set", args, 3)?;
+    let mut m = dict_arg("set", &args[0])?.clone();
+    m.insert(strval(&args[1]), args[2].clone());
+    Ok(same_kind(&args[0], m))
+}
+
+/// `unset DICT KEY` returns a copy of the dictionary without the key.
+///
+/// Unlike Sprig the dictionary itself is not changed, use `{{ $d := unset $d KEY }}`.
+pub fn unset(args: &[Value]) -> Result<Value, String> {
+    check_args("unset", args, 2)?;
+    let mut m = dict_arg("unset", &args[0])?.clone();
+    m.remove(&strval(&args[1]));
+    Ok(same_kind(&args[0], m))
+}
+
+/// `hasKey DICT KEY` tests whether the dictionary contains the key.
+pub fn has_key(args: &[Value]) -> Result<Value, String> {
+    check_args("hasKey", args, 2)?;
+    let m = dict_arg("hasKey", &args[0])?;
+    Ok(Value::from(m.contains_key(&strval(&args[1]))))
+}
+
+/// `keys DICT...` returns the sorted keys of all given dictionaries.
+pub fn keys(args: &[Value]) -> Result<Value, String> {
+    let mut keys = vec![];
+    for arg in args {
+        keys.extend(dict_arg("keys", arg)?.keys().cloned());
+    }
+    keys.sort();
+    Ok(Value::Array(keys.into_iter().map(Value::from).collect()))
+}
+
+/// Returns the values of a dictionary ordered by their keys.
+pub fn values(args: &[Value]) -> Result<Value, String> {
+    check_args("values", args, 1)?;
+    let m = dict_arg("values", &args[0])?;
+    Ok(Value::Array(
+        sorted_keys(m).into_iter().map(|k| m[k].clone()).collect(),
+    ))
+}
+
+/// `pick DICT KEY...` returns a dictionary with only the given keys.
+pub fn pick(args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(String::from("pick requires at least 1 argument"));
+    }
+    let m = dict_arg("pick", &args[0])?;
+    let picked = args[1..]
+        .iter()
+        .map(strval)
+        .filter_map(|k| m.get(&k).map(|v| (k, v.clone())))
+        .collect();
+    Ok(same_kind(&args[0], picked))
+}
+
+/// `omit DICT KEY...` returns a dictionary without the given keys.
+pub fn omit(args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(String::from("omit requires at least 1 argument"));
+    }
+    let mut m = dict_arg("omit", &args[0])?.clone();
+    for k in &args[1..] {
+        m.remove(&strval(k));
+    }
+    Ok(same_kind(&args[0], m))
+}
+
+/// `merge DEST SRC...` deeply merges the source dictionaries into the destination. Keys
+/// already present are not overwritten.
+pub fn merge(args: &[Value]) -> Result<Value, String> {
+    merge_all("merge", args, false)
+}
+
+/// `mergeOverwrite DEST SRC...` deeply merges the source dictionaries into the destination.
+/// Later sources take precedence.
+pub fn merge_overwrite(args: &[Value]) -> Result<Value, String> {
+    merge_all("mergeOverwrite", args, true)
+}
+
+fn merge_all(name: &str, args: &[Value], overwrite: bool) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(format!("{} requires at least 1 argument", name));
+    }
+    let mut dst = dict_arg(name, &args[0])?.clone();
+    for src in &args[1..] {
+        merge_into(&mut dst, dict_arg(name, src)?, overwrite);
+    }
+    Ok(same_kind(&args[0], dst))
+}
+
+fn merge_into(dst: &mut HashMap<String, Value>, src: &HashMap<String, Value>, overwrite: bool) {
+    for (k, v) in src {
+        match (dst.get_mut(k), v) {
+            (Some(&mut Value::Map(ref mut d)), &Value::Map(ref s))
+            | (Some(&mut Value::Map(ref mut d)), &Value::Object(ref s))
+            | (Some(&mut Value::Object(ref mut d)), &Value::Map(ref s))
+            | (Some(&mut Value::Object(ref mut d)), &Value::Object(ref s)) => {
+                merge_into(d, s, overwrite)
+            }
+            (Some(d), v) => {
+                if overwrite {
+                    *d = v.clone();
+                }
+            }
+            (None, v) => {
+                dst.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+/// Returns a deep copy of its argument.
+pub fn deep_copy(args: &[Value]) -> Result<Value, String> {
+    check_args("deepCopy", args, 1)?;
+    Ok(args[0].clone())
+}
+
+/// `dig KEY... DEFAULT DICT` walks nested dictionaries along the keys and returns the value
+/// found or `DEFAULT` if any key is missing.
+pub fn dig(args: &[Value]) -> Result<Value, String> {
+    if args.len() < 3 {
+        return Err(String::from("dig requires at least 3 arguments"));
+    }
+    let (keys, rest) = args.split_at(args.len() - 2);
+    let (default, mut cur) = (&rest[0], &rest[1]);
+    dict_arg("dig", cur)?;
+    for key in keys {
+        cur = match *cur {
+            Value::Map(ref m) | Value::Object(ref m) => match m.get(&strval(key)) {
+                Some(v) => v,
+                None => return Ok(default.clone()),
+            },
+            _ => return Ok(default.clone()),
+        };
+    }
+    Ok(cur.clone())
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn d(kv: &[(&str, Value)]) -> Value {
+        Value::Map(
+            kv.iter()
+                .map(|&(k, ref v)| (k.to_owned(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_dict() {
+        let m = dict(&["a".into(), 1.into(), 2.into()]).unwrap();
+        assert_eq!(m, d(&[("a", 1.into()), ("2", "".into())]));
+        assert_eq!(get(&[m.clone(), "a".into()]), Ok(Value::from(1)));
+        assert_eq!(get(&[m.clone(), "x".into()]), Ok(Value::from("")));
+        assert_eq!(has_key(&[m.clone(), "2".into()]), Ok(Value::from(true)));
+        assert_eq!(has_key(&[m, "x".into()]), Ok(Value::from(false)));
+        assert!(get(&[1.into(), "a".into()]).is_err());
+    }
+
+    #[test]
+    fn test_set_unset() {
+        let m = d(&[("a", 1.into())]);
+        assert_eq!(
+            set(&[m.clone(), "b".into(), 2.into()]),
+            Ok(d(&[("a", 1.into()), ("b", 2.into())]))
+        );
+        assert_eq!(unset(&[m, "a".into()]), Ok(d(&[])));
+        let o = Value::Object(HashMap::new());
+        assert_eq!(
+            set(&[o, "a".into(), 1.into()]),
+            Ok(Value::Object(
+                vec![("a".to_owned(), 1.into())].into_iter().collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_keys_values() {
+        let m = d(&[("b", 2.into()), ("c", 3.into()), ("a", 1.into())]);
+        assert_eq!(
+            keys(&[m.clone(), d(&[("d", 4.into())])]),
+            Ok(Value::from(vec!["a", "b", "c", "d"]))
+        );
+        assert_eq!(values(&[m]), Ok(Value::from(vec![1, 2, 3])));
+    }
+
+    #[test]
+    fn test_pick_omit() {
+        let m = d(&[("a", 1.into()), ("b", 2.into()), ("c", 3.into())]);
+        assert_eq!(
+            pick(&[m.clone(), "a".into(), "c".into(), "x".into()]),
+            Ok(d(&[("a", 1.into()), ("c", 3.into())]))
+        );
+        assert_eq!(
+            omit(&[m, "a".into(), "c".into()]),
+            Ok(d(&[("b", 2.into())]))
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let dst = d(&[("a", 1.into()), ("n", d(&[("x", 1.into())]))]);
+        let src = d(&[
+            ("a", 2.into()),
+            ("b", 2.into()),
+            ("n", d(&[("x", 2.into()), ("y", 2.into())])),
+        ]);
+        assert_eq!(
+            merge(&[dst.clone(), src.clone()]),
+            Ok(d(&[
+                ("a", 1.into()),
+                ("b", 2.into()),
+                ("n", d(&[("x", 1.into()), ("y", 2.into())])),
+            ]))
+        );
+        assert_eq!(
+            merge_overwrite(&[dst, src]),
+            Ok(d(&[
+                ("a", 2.into()),
+                ("b", 2.into()),
+                ("n", d(&[("x", 2.into()), ("y", 2.into())])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_dig() {
+        let m = d(&[("a", d(&[("b", "found".into())]))]);
+        assert_eq!(
+            dig(&["a".into(), "b".into(), "default".into(), m.clone()]),
+            Ok(Value::from("found"))
+        );
+        assert_eq!(
+            dig(&["a".into(), "x".into(), "default".into(), m.clone()]),
+            Ok(Value::from("default"))
+        );
+        let copy = m.clone();
+        assert_eq!(deep_copy(&[m]), Ok(copy));
+    }
+}
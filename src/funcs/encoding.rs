@@ -0,0 +1,161 @@
+//! Encoding and hashing functions compatible with
+//! [Sprig](https://masterminds.github.io/sprig/encoding.html).
+//!
+//! All functions operate on the string representation of their argument. Decoded bytes
+//! that are not valid UTF-8 are replaced with `U+FFFD`.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_encoding_funcs();
+//! tmpl.parse("{{ b64enc . }} {{ b64enc . | b64dec }}").unwrap();
+//! let output = tmpl.render(&Context::from("gtmpl").unwrap());
+//! assert_eq!(&output.unwrap(), "Z3RtcGw= gtmpl");
+//! ```
+extern crate adler32;
+extern crate data_encoding;
+extern crate sha1;
+extern crate sha2;
+
+use gtmpl_value::{Func, Value};
+
+use self::data_encoding::{Encoding, BASE32, BASE64, HEXLOWER, HEXLOWER_PERMISSIVE};
+use self::sha1::Sha1;
+use self::sha2::{Digest, Sha256, Sha512};
+use crate::funcs::check_args;
+use crate::utils::strval;
+
+/// All encoding and hashing functions of this module.
+pub static ENCODING: &[(&str, Func)] = &[
+    ("b64enc", b64enc as Func),
+    ("b64dec", b64dec as Func),
+    ("b32enc", b32enc as Func),
+    ("b32dec", b32dec as Func),
+    ("hex", hex as Func),
+    ("unhex", unhex as Func),
+    ("sha1sum", sha1sum as Func),
+    ("sha256sum", sha256sum as Func),
+    ("sha512sum", sha512sum as Func),
+    ("adler32sum", adler32sum as Func),
+];
+
+fn encode(name: &str, enc: &Encoding, args: &[Value]) -> Result<Value, String> {
+    check_args(name, args, 1)?;
+    Ok(Value::from(enc.encode(strval(&args[0]).as_bytes())))
+}
+
+fn decode(name: &str, enc: &Encoding, args: &[Value]) -> Result<Value, String> {
+    check_args(name, args, 1)?;
+    let bytes = enc
+        .decode(strval(&args[0]).as_bytes())
+        .map_err(|e| format!("{}: {}", name, e))?;
+    Ok(Value::from(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn digest<D: Digest>(name: &str, args: &[Value]) -> Result<Value, String> {
+    check_args(name, args, 1)?;
+    Ok(Value::from(
+        HEXLOWER.encode(&D::digest(strval(&args[0]).as_bytes())),
+    ))
+}
+
+/// Encodes its argument with standard padded base64.
+pub fn b64enc(args: &[Value]) -> Result<Value, String> {
+    encode("b64enc", &BASE64, args)
+}
+
+/// Decodes standard padded base64.
+pub fn b64dec(args: &[Value]) -> Result<Value, String> {
+    decode("b64dec", &BASE64, args)
+}
+
+/// Encodes its argument with standard padded base32.
+pub fn b32enc(args: &[Value]) -> Result<Value, String> {
+    encode("b32enc", &BASE32, args)
+}
+
+/// Decodes standard padded base32.
+pub fn b32dec(args: &[Value]) -> Result<Value, String> {
+    decode("b32dec", &BASE32, args)
+}
+
+/// Encodes its argument as lower case hexadecimal.
+pub fn hex(args: &[Value]) -> Result<Value, String> {
+    encode("hex", &HEXLOWER, args)
+}
+
+/// Decodes upper or lower case hexadecimal.
+pub fn unhex(args: &[Value]) -> Result<Value, String> {
+    decode("unhex", &HEXLOWER_PERMISSIVE, args)
+}
+
+/// Returns the hex encoded SHA-1 digest of its argument.
+pub fn sha1sum(args: &[Value]) -> Result<Value, String> {
+    digest::<Sha1>("sha1sum", args)
+}
+
+/// Returns the hex encoded SHA-256 digest of its argument.
+pub fn sha256sum(args: &[Value]) -> Result<Value, String> {
+    digest::<Sha256>("sha256sum", args)
+}
+
+/// Returns the hex encoded SHA-512 digest of its argument.
+pub fn sha512sum(args: &[Value]) -> Result<Value, String> {
+    digest::<Sha512>("sha512sum", args)
+}
+
+/// Returns the Adler-32 checksum of its argument as decimal string.
+pub fn adler32sum(args: &[Value]) -> Result<Value, String> {
+    check_args("adler32sum", args, 1)?;
+    let sum = adler32::adler32(strval(&args[0]).as_bytes()).map_err(|e| e.to_string())?;
+    Ok(Value::from(sum.to_string()))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    #[test]
+    fn test_base64_base32() {
+        assert_eq!(b64enc(&["gtmpl".into()]), Ok(Value::from("Z3RtcGw=")));
+        assert_eq!(b64dec(&["Z3RtcGw=".into()]), Ok(Value::from("gtmpl")));
+        assert!(b64dec(&["Z3RtcGw".into()]).is_err());
+        assert_eq!(b32enc(&["gtmpl".into()]), Ok(Value::from("M52G24DM")));
+        assert_eq!(b32dec(&["M52G24DM".into()]), Ok(Value::from("gtmpl")));
+        assert_eq!(b64enc(&[42.into()]), Ok(Value::from("NDI=")));
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex(&["Hi!".into()]), Ok(Value::from("486921")));
+        assert_eq!(unhex(&["48693F".into()]), Ok(Value::from("Hi?")));
+        assert!(unhex(&["486".into()]).is_err());
+    }
+
+    #[test]
+    fn test_sums() {
+        assert_eq!(
+            sha1sum(&["abc".into()]),
+            Ok(Value::from("a9993e364706816aba3e25717850c26c9cd0d89d"))
+        );
+        assert_eq!(
+            sha256sum(&["abc".into()]),
+            Ok(Value::from(
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            ))
+        );
+        assert_eq!(
+            sha512sum(&["".into()]),
+            Ok(Value::from(
+                "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                 47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"
+            ))
+        );
+        assert_eq!(
+            adler32sum(&["Wikipedia".into()]),
+            Ok(Value::from("300286872"))
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Environment and file system functions similar to
+//! [Sprig](https://masterminds.github.io/sprig/os.html) and
+//! [gomplate](https://docs.gomplate.ca/functions/file/).
+//!
+//! These functions expose the host to the template and are only usable after an explicit
+//! [`Template::allow_host_access`](../../struct.Template.html#method.allow_host_access).
+//! Paths are relative to the root directory given there. Absolute paths, `..` components and
+//! symbolic links pointing outside of the root directory are refused.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let root = std::env::temp_dir();
+//! std::fs::write(root.join("gtmpl-host-example.txt"), "hello").unwrap();
+//! let mut tmpl = Template::default();
+//! tmpl.allow_host_access(&root).unwrap();
+//! tmpl.parse(r#"{{ if fileExists "gtmpl-host-example.txt" }}{{ readFile "gtmpl-host-example.txt" }}{{ end }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "hello");
+//! ```
+extern crate glob;
+
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use gtmpl_value::{Func, Value};
+
+use self::glob::{glob_with, MatchOptions, Pattern};
+use crate::funcs::{check_args, with_state};
+use crate::utils::strval;
+
+/// All environment and file system functions of this module.
+pub static HOST: &[(&str, Func)] = &[
+    ("env", env as Func),
+    ("expandenv", expandenv as Func),
+    ("readFile", read_file as Func),
+    ("fileExists", file_exists as Func),
+    ("glob", glob as Func),
+];
+
+fn root(name: &str) -> Result<PathBuf, String> {
+    with_state(|s| s.and_then(|s| s.host_root.clone()))
+        .ok_or_else(|| format!("{}: host access is not allowed", name))
+}
+
+// Joins a relative path without `..` components to the root.
+fn resolve(name: &str, root: &Path, path: &str) -> Result<PathBuf, String> {
+    let rel = Path::new(path);
+    let confined = rel
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !confined {
+        return Err(format!(
+            "{}: {} is outside of the root directory",
+            name, path
+        ));
+    }
+    Ok(root.join(rel))
+}
+
+// Resolves symbolic links and checks the result is still inside of the root.
+fn canonicalize(name: &str, root: &Path, path: &Path) -> Result<PathBuf, String> {
+    let canonical = fs::canonicalize(path).map_err(|e| format!("{}: {}", name, e))?;
+    if !canonical.starts_with(root) {
+        return Err(format!(
+            "{}: {} is outside of the root directory",
+            name,
+            path.display()
+        ));
+    }
+    Ok(canonical)
+}
+
+// Returns the length of a variable name at the start of `s` like golang's `os.Expand`.
+fn shell_name(s: &str) -> (&str, usize) {
+    let b = s.as_bytes();
+    let special = |c: u8| b"*#$@!?-".contains(&c) || c.is_ascii_digit();
+    if b[0] == b'{' {
+        if b.len() > 2 && special(b[1]) && b[2] == b'}' {
+            return (&s[1..2], 3);
+        }
+        return match s.find('}') {
+            Some(1) => ("", 2),
+            Some(i) => (&s[1..i], i + 1),
+            None => ("", 1),
+        };
+    }
+    if special(b[0]) {
+        return (&s[..1], 1);
+    }
+    let n = b
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+        .count();
+    (&s[..n], n)
+}
+
+/// Returns the value of an environment variable or an empty string.
+pub fn env(args: &[Value]) -> Result<Value, String> {
+    check_args("env", args, 1)?;
+    root("env")?;
+    Ok(Value::from(env::var(strval(&args[0])).unwrap_or_default()))
+}
+
+/// Replaces `$VAR` and `${VAR}` with the values of environment variables.
+pub fn expandenv(args: &[Value]) -> Result<Value, String> {
+    check_args("expandenv", args, 1)?;
+    root("expandenv")?;
+    let s = strval(&args[0]);
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        if i + 1 == rest.len() {
+            out.push('$');
+            rest = "";
+            break;
+        }
+        let (name, w) = shell_name(&rest[i + 1..]);
+        if !name.is_empty() {
+            out.push_str(&env::var(name).unwrap_or_default());
+        } else if w == 0 {
+            // Not followed by a name, keep the dollar.
+            out.push('$');
+        }
+        rest = &rest[i + 1 + w..];
+    }
+    out.push_str(rest);
+    Ok(Value::from(out))
+}
+
+/// Returns the content of a file.
+pub fn read_file(args: &[Value]) -> Result<Value, String> {
+    check_args("readFile", args, 1)?;
+    let root = root("readFile")?;
+    let path = resolve("readFile", &root, &strval(&args[0]))?;
+    let bytes = fs::read(canonicalize("readFile", &root, &path)?)
+        .map_err(|e| format!("readFile: {}", e))?;
+    Ok(Value::from(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Tests whether a file or directory exists.
+pub fn file_exists(args: &[Value]) -> Result<Value, String> {
+    check_args("fileExists", args, 1)?;
+    let root = root("fileExists")?;
+    let path = resolve("fileExists", &root, &strval(&args[0]))?;
+    if !path.exists() {
+        return Ok(Value::from(false));
+    }
+    canonicalize("fileExists", &root, &path)?;
+    Ok(Value::from(true))
+}
+
+/// Returns the sorted paths matching a glob pattern like `conf.d/*.yaml`. Hidden files only
+/// match patterns starting with a dot.
+pub fn glob(args: &[Value]) -> Result<Value, String> {
+    check_args("glob", args, 1)?;
+    let root = root("glob")?;
+    let pattern = strval(&args[0]);
+    resolve("glob", &root, &pattern)?;
+    let full = format!("{}/{}", Pattern::escape(&root.to_string_lossy()), pattern);
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::new()
+    };
+    let paths = glob_with(&full, options).map_err(|e| format!("glob: {}", e))?;
+    let mut matches = vec![];
+    for path in paths {
+        let path = path.map_err(|e| format!("glob: {}", e))?;
+        // Silently skip links leading outside of the root.
+        if canonicalize("glob", &root, &path).is_err() {
+            continue;
+        }
+        if let Ok(rel) = path.strip_prefix(&root) {
+            matches.push(rel.to_string_lossy().into_owned());
+        }
+    }
+    matches.sort();
+    Ok(Value::Array(matches.into_iter().map(Value::from).collect()))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::funcs::{enter, FuncState};
+
+    fn setup(name: &str) -> (PathBuf, FuncState) {
+        let dir = env::temp_dir().join(format!("gtmpl-host-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("root/conf.d")).unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        fs::write(dir.join("root/a.txt"), "a").unwrap();
+        fs::write(dir.join("root/conf.d/b.yaml"), "b").unwrap();
+        fs::write(dir.join("root/conf.d/c.yaml"), "c").unwrap();
+        fs::write(dir.join("root/conf.d/.d.yaml"), "d").unwrap();
+        let root = fs::canonicalize(dir.join("root")).unwrap();
+        let state = FuncState {
+            host_root: Some(root),
+            ..Default::default()
+        };
+        (dir, state)
+    }
+
+    #[test]
+    fn test_not_allowed() {
+        assert!(env(&["HOME".into()]).is_err());
+        assert!(read_file(&["a.txt".into()]).is_err());
+    }
+
+    #[test]
+    fn test_env() {
+        let (dir, state) = setup("env");
+        let _guard = enter(&state);
+        env::set_var("GTMPL_HOST_TEST", "x");
+        assert_eq!(env(&["GTMPL_HOST_TEST".into()]), Ok(Value::from("x")));
+        assert_eq!(env(&["GTMPL_HOST_UNSET".into()]), Ok(Value::from("")));
+        assert_eq!(
+            expandenv(&["a=$GTMPL_HOST_TEST b=${GTMPL_HOST_TEST}c $GTMPL_HOST_UNSET$ $".into()]),
+            Ok(Value::from("a=x b=xc $ $"))
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_files() {
+        let (dir, state) = setup("files");
+        let _guard = enter(&state);
+        assert_eq!(read_file(&["a.txt".into()]), Ok(Value::from("a")));
+        assert_eq!(read_file(&["./conf.d/b.yaml".into()]), Ok(Value::from("b")));
+        assert!(read_file(&["../secret.txt".into()]).is_err());
+        assert!(read_file(&["conf.d/../../secret.txt".into()]).is_err());
+        assert!(read_file(&[dir.join("secret.txt").to_string_lossy().as_ref().into()]).is_err());
+        assert!(read_file(&["missing.txt".into()]).is_err());
+        assert_eq!(file_exists(&["conf.d".into()]), Ok(Value::from(true)));
+        assert_eq!(file_exists(&["missing.txt".into()]), Ok(Value::from(false)));
+        assert!(file_exists(&["../secret.txt".into()]).is_err());
+        assert_eq!(
+            glob(&["conf.d/*.yaml".into()]),
+            Ok(Value::from(vec!["conf.d/b.yaml", "conf.d/c.yaml"]))
+        );
+        assert!(glob(&["../*".into()]).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink() {
+        let (dir, state) = setup("symlink");
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("root/link.txt")).unwrap();
+        let _guard = enter(&state);
+        assert!(read_file(&["link.txt".into()]).is_err());
+        assert!(file_exists(&["link.txt".into()]).is_err());
+        assert_eq!(glob(&["*.txt".into()]), Ok(Value::from(vec!["a.txt"])));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
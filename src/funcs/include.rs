@@ -0,0 +1,44 @@
+//! Template functions compatible with
+//! [Helm](https://helm.sh/docs/howto/charts_tips_and_tricks/#using-the-include-function).
+//!
+//! `include NAME DATA` renders a template like `{{ template }}` does, but returns the output
+//! as a string, so it can be piped into other functions. `tpl TEXT DATA` parses a string as
+//! template and executes it with the functions and templates of the current template.
+//! Templates defined inside of `TEXT` are only visible to that `tpl` call.
+//!
+//! Both are handled by the executing template and take precedence over custom functions of
+//! the same name. Calling the functions of this module directly results in an error.
+//!
+//! Templates including themselves fail once the maximum nesting depth set with
+//! [`Template::set_max_depth`](../../struct.Template.html#method.set_max_depth) is exceeded,
+//! like they do with `{{ template }}`.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_include_funcs();
+//! tmpl.parse(r#"{{ define "greet" }}Hello {{ . }}{{ end }}{{ include "greet" . | printf "%q" }}"#)
+//!     .unwrap();
+//! let output = tmpl.render(&Context::from("World").unwrap());
+//! assert_eq!(&output.unwrap(), r#""Hello World""#);
+//! ```
+use gtmpl_value::{Func, Value};
+
+/// All template functions of this module.
+pub static INCLUDE: &[(&str, Func)] = &[("include", include as Func), ("tpl", tpl as Func)];
+
+/// `include NAME DATA` renders the template `NAME` with `DATA` into a string.
+pub fn include(_: &[Value]) -> Result<Value, String> {
+    Err(String::from(
+        "include can only be called by an executing template",
+    ))
+}
+
+/// `tpl TEXT DATA` renders the string `TEXT` as template with `DATA`.
+pub fn tpl(_: &[Value]) -> Result<Value, String> {
+    Err(String::from(
+        "tpl can only be called by an executing template",
+    ))
+}
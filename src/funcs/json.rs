@@ -0,0 +1,140 @@
+//! JSON functions compatible with [Sprig](https://masterminds.github.io/sprig/defaults.html).
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_json_funcs();
+//! tmpl.parse(r#"{{ $v := fromJson "{\"a\": [1, \"<b>\"]}" }}{{ toRawJson $v }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), r#"{"a":[1,"<b>"]}"#);
+//! ```
+extern crate serde_json;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::json::{json_to_value, value_to_json};
+use crate::utils::strval;
+
+/// All JSON functions of this module.
+pub static JSON: &[(&str, Func)] = &[
+    ("toJson", to_json as Func),
+    ("toPrettyJson", to_pretty_json as Func),
+    ("toRawJson", to_raw_json as Func),
+    ("fromJson", from_json as Func),
+    ("fromJsonArray", from_json_array as Func),
+];
+
+// Like golang characters that are special in HTML are escaped.
+fn escape_html(s: &str) -> String {
+    s.replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+/// Encodes its argument as JSON. Object keys are sorted and HTML characters escaped.
+pub fn to_json(args: &[Value]) -> Result<Value, String> {
+    check_args("toJson", args, 1)?;
+    let json = value_to_json(&args[0])?;
+    let s = serde_json::to_string(&json).map_err(|e| e.to_string())?;
+    Ok(Value::from(escape_html(&s)))
+}
+
+/// Encodes its argument as indented JSON.
+pub fn to_pretty_json(args: &[Value]) -> Result<Value, String> {
+    check_args("toPrettyJson", args, 1)?;
+    let json = value_to_json(&args[0])?;
+    let s = serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?;
+    Ok(Value::from(escape_html(&s)))
+}
+
+/// Encodes its argument as JSON without escaping HTML characters.
+pub fn to_raw_json(args: &[Value]) -> Result<Value, String> {
+    check_args("toRawJson", args, 1)?;
+    let json = value_to_json(&args[0])?;
+    serde_json::to_string(&json)
+        .map(Value::from)
+        .map_err(|e| e.to_string())
+}
+
+fn parse(name: &str, val: &Value) -> Result<serde_json::Value, String> {
+    serde_json::from_str(&strval(val)).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// Decodes a JSON object into a map.
+pub fn from_json(args: &[Value]) -> Result<Value, String> {
+    check_args("fromJson", args, 1)?;
+    match parse("fromJson", &args[0])? {
+        json @ serde_json::Value::Object(_) => Ok(json_to_value(json)),
+        _ => Err(String::from("fromJson requires a JSON object")),
+    }
+}
+
+/// Decodes a JSON array into a list.
+pub fn from_json_array(args: &[Value]) -> Result<Value, String> {
+    check_args("fromJsonArray", args, 1)?;
+    match parse("fromJsonArray", &args[0])? {
+        json @ serde_json::Value::Array(_) => Ok(json_to_value(json)),
+        _ => Err(String::from("fromJsonArray requires a JSON array")),
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_to_json() {
+        let mut m = HashMap::new();
+        m.insert("b".to_owned(), Value::from("<&>"));
+        m.insert("a".to_owned(), Value::Nil);
+        let args = [Value::Map(m)];
+        assert_eq!(
+            to_json(&args),
+            Ok(Value::from(r#"{"a":null,"b":"\u003c\u0026\u003e"}"#))
+        );
+        assert_eq!(
+            to_raw_json(&args),
+            Ok(Value::from(r#"{"a":null,"b":"<&>"}"#))
+        );
+        assert_eq!(
+            to_pretty_json(&args),
+            Ok(Value::from(
+                "{\n  \"a\": null,\n  \"b\": \"\\u003c\\u0026\\u003e\"\n}"
+            ))
+        );
+        assert!(to_json(&[Value::Function(gtmpl_value::Function { f: to_json })]).is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let v = from_json(&[r#"{"a": [1, true]}"#.into()]).unwrap();
+        let mut m = HashMap::new();
+        m.insert("a".to_owned(), Value::Array(vec![1.into(), true.into()]));
+        assert_eq!(v, Value::Map(m));
+        assert!(from_json(&["[1]".into()]).is_err());
+        assert!(from_json(&["{".into()]).is_err());
+        assert_eq!(
+            from_json_array(&["[1, 2]".into()]),
+            Ok(Value::from(vec![1, 2]))
+        );
+        assert!(from_json_array(&["{}".into()]).is_err());
+    }
+
+    #[test]
+    fn test_large_floats() {
+        let mut t = crate::Template::default();
+        t.add_json_funcs();
+        t.parse(r#"{{ fromJson "{\"a\": 1e300, \"b\": -1e30}" | toJson }}"#)
+            .unwrap();
+        assert_eq!(
+            t.render(&crate::Context::empty()).unwrap(),
+            r#"{"a":1e+300,"b":-1e+30}"#
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! List functions compatible with [Sprig](https://masterminds.github.io/sprig/lists.html).
+//!
+//! All functions operate on `Value::Array` and never modify their arguments, but return a new
+//! list instead.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_list_funcs();
+//! tmpl.parse(r#"{{ range list 3 1 2 1 | uniq | reverse }}{{ . }}{{ end }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "213");
+//! ```
+use std::cmp::Ordering;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::{check_args, cmp, get_item};
+use crate::utils::{is_true, strval, to_int};
+
+/// All list functions of this module.
+pub static LISTS: &[(&str, Func)] = &[
+    ("list", list as Func),
+    ("first", first as Func),
+    ("last", last as Func),
+    ("rest", rest as Func),
+    ("initial", initial as Func),
+    ("append", append as Func),
+    ("push", append as Func),
+    ("prepend", prepend as Func),
+    ("concat", concat as Func),
+    ("reverse", reverse as Func),
+    ("uniq", uniq as Func),
+    ("without", without as Func),
+    ("has", has as Func),
+    ("compact", compact as Func),
+    ("sortAlpha", sort_alpha as Func),
+    ("until", until as Func),
+    ("untilStep", until_step as Func),
+    ("chunk", chunk as Func),
+];
+
+fn list_arg<'a>(name: &str, val: &'a Value) -> Result<&'a [Value], String> {
+    match *val {
+        Value::Array(ref a) => Ok(a),
+        _ => Err(format!("{} requires a list, got {}", name, val)),
+    }
+}
+
+fn int_arg(name: &str, val: &Value) -> Result<i64, String> {
+    to_int(val).ok_or_else(|| format!("{} requires an integer, got {}", name, val))
+}
+
+/// Creates a list from its arguments.
+pub fn list(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Array(args.to_vec()))
+}
+
+/// Returns the first element of a list or nil for an empty list.
+pub fn first(args: &[Value]) -> Result<Value, String> {
+    check_args("first", args, 1)?;
+    if list_arg("first", &args[0])?.is_empty() {
+        return Ok(Value::Nil);
+    }
+    get_item(&args[0], &Value::from(0)).cloned()
+}
+
+/// Returns the last element of a list or nil for an empty list.
+pub fn last(args: &[Value]) -> Result<Value, String> {
+    check_args("last", args, 1)?;
+    let len = list_arg("last", &args[0])?.len();
+    if len == 0 {
+        return Ok(Value::Nil);
+    }
+    get_item(&args[0], &Value::from(len - 1)).cloned()
+}
+
+/// Returns all but the first element of a list.
+pub fn rest(args: &[Value]) -> Result<Value, String> {
+    check_args("rest", args, 1)?;
+    let l = list_arg("rest", &args[0])?;
+    Ok(Value::Array(l.iter().skip(1).cloned().collect()))
+}
+
+/// Returns all but the last element of a list.
+pub fn initial(args: &[Value]) -> Result<Value, String> {
+    check_args("initial", args, 1)?;
+    let l = list_arg("initial", &args[0])?;
+    Ok(Value::Array(l[..l.len().saturating_sub(1)].to_vec()))
+}
+
+/// `append LIST ELEMENT` returns a list with the element added at the end.
+pub fn append(args: &[Value]) -> Result<Value, String> {
+    check_args("append", args, 2)?;
+    let mut l = list_arg("append", &args[0])?.to_vec();
+    l.push(args[1].clone());
+    Ok(Value::Array(l))
+}
+
+/// `prepend LIST ELEMENT` returns a list with the element added at the front.
+pub fn prepend(args: &[Value]) -> Result<Value, String> {
+    check_args("prepend", args, 2)?;
+    let mut l = vec![args[1].clone()];
+    l.extend_from_slice(list_arg("prepend", &args[0])?);
+    Ok(Value::Array(l))
+}
+
+/// Concatenates all given lists.
+pub fn concat(args: &[Value]) -> Result<Value, String> {
+    let mut l = vec![];
+    for arg in args {
+        l.extend_from_slice(list_arg("concat", arg)?);
+    }
+    Ok(Value::Array(l))
+}
+
+/// Returns the list in reversed order.
+pub fn reverse(args: &[Value]) -> Result<Value, String> {
+    check_args("reverse", args, 1)?;
+    let mut l = list_arg("reverse", &args[0])?.to_vec();
+    l.reverse();
+    Ok(Value::Array(l))
+}
+
+/// Removes all duplicates from a list, keeping the first occurrence.
+pub fn uniq(args: &[Value]) -> Result<Value, String> {
+    check_args("uniq", args, 1)?;
+    let mut l: Vec<Value> = vec![];
+    for v in list_arg("uniq", &args[0])? {
+        if !l.contains(v) {
+            l.push(v.clone());
+        }
+    }
+    Ok(Value::Array(l))
+}
+
+/// `without LIST ELEMENT...` removes all given elements from a list.
+pub fn without(args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(String::from("without requires at least 1 argument"));
+    }
+    let omit = &args[1..];
+    let l = list_arg("without", &args[0])?;
+    Ok(Value::Array(
+        l.iter().filter(|v| !omit.contains(v)).cloned().collect(),
+    ))
+}
+
+/// `has ELEMENT LIST` tests whether a list contains the element.
+pub fn has(args: &[Value]) -> Result<Value, String> {
+    check_args("has", args, 2)?;
+    Ok(Value::from(list_arg("has", &args[1])?.contains(&args[0])))
+}
+
+/// Removes all empty elements from a list.
+pub fn compact(args: &[Value]) -> Result<Value, String> {
+    check_args("compact", args, 1)?;
+    let l = list_arg("compact", &args[0])?;
+    Ok(Value::Array(
+        l.iter().filter(|v| is_true(v)).cloned().collect(),
+    ))
+}
+
+/// Sorts a list alphabetically. All elements are converted to strings.
+pub fn sort_alpha(args: &[Value]) -> Result<Value, String> {
+    check_args("sortAlpha", args, 1)?;
+    let mut l: Vec<Value> = list_arg("sortAlpha", &args[0])?
+        .iter()
+        .map(|v| Value::from(strval(v)))
+        .collect();
+    l.sort_by(|a, b| cmp(a, b).unwrap_or(Ordering::Equal));
+    Ok(Value::Array(l))
+}
+
+/// `until COUNT` returns the integers from 0 up to, but not including, `COUNT`. A negative count
+/// counts down.
+pub fn until(args: &[Value]) -> Result<Value, String> {
+    check_args("until", args, 1)?;
+    let count = int_arg("until", &args[0])?;
+    step_range(0, count, if count < 0 { -1 } else { 1 })
+}
+
+/// `untilStep START STOP STEP` returns the integers from `START` up to, but not including,
+/// `STOP` in steps of `STEP`.
+pub fn until_step(args: &[Value]) -> Result<Value, String> {
+    check_args("untilStep", args, 3)?;
+    step_range(
+        int_arg("untilStep", &args[0])?,
+        int_arg("untilStep", &args[1])?,
+        int_arg("untilStep", &args[2])?,
+    )
+}
+
+fn step_range(start: i64, stop: i64, step: i64) -> Result<Value, String> {
+    let mut l = vec![];
+    let mut i = start;
+    if (stop < start && step < 0) || (stop > start && step > 0) {
+        while (step > 0 && i < stop) || (step < 0 && i > stop) {
+            l.push(Value::from(i));
+            i = match i.checked_add(step) {
+                Some(i) => i,
+                None => break,
+            };
+        }
+    }
+    Ok(Value::Array(l))
+}
+
+/// `chunk SIZE LIST` splits a list into lists of `SIZE` elements. The last chunk may be
+/// smaller.
+pub fn chunk(args: &[Value]) -> Result<Value, String> {
+    check_args("chunk", args, 2)?;
+    let size = int_arg("chunk", &args[0])?;
+    if size < 1 {
+        return Err(String::from("chunk size must be greater than 0"));
+    }
+    let l = list_arg("chunk", &args[1])?;
+    Ok(Value::Array(
+        l.chunks(size as usize)
+            .map(|c| Value::Array(c.to_vec()))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn l(v: Vec<i64>) -> Value {
+        Value::from(v)
+    }
+
+    #[test]
+    fn test_access() {
+        let a = [l(vec![1, 2, 3])];
+        assert_eq!(first(&a), Ok(Value::from(1)));
+        assert_eq!(last(&a), Ok(Value::from(3)));
+        assert_eq!(rest(&a), Ok(l(vec![2, 3])));
+        assert_eq!(initial(&a), Ok(l(vec![1, 2])));
+        assert_eq!(first(&[l(vec![])]), Ok(Value::Nil));
+        assert_eq!(initial(&[l(vec![])]), Ok(l(vec![])));
+        assert!(first(&["foo".into()]).is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        assert_eq!(list(&[1.into(), 2.into()]), Ok(l(vec![1, 2])));
+        assert_eq!(append(&[l(vec![1]), 2.into()]), Ok(l(vec![1, 2])));
+        assert_eq!(prepend(&[l(vec![1]), 2.into()]), Ok(l(vec![2, 1])));
+        assert_eq!(concat(&[l(vec![1]), l(vec![2, 3])]), Ok(l(vec![1, 2, 3])));
+        assert_eq!(reverse(&[l(vec![1, 2])]), Ok(l(vec![2, 1])));
+    }
+
+    #[test]
+    fn test_filter() {
+        let a = l(vec![1, 2, 1, 3]);
+        assert_eq!(uniq(&[l(vec![1, 2, 1, 3])]), Ok(l(vec![1, 2, 3])));
+        assert_eq!(without(&[a.clone(), 1.into(), 3.into()]), Ok(l(vec![2])));
+        assert_eq!(has(&[3.into(), a.clone()]), Ok(Value::from(true)));
+        assert_eq!(has(&[4.into(), a]), Ok(Value::from(false)));
+        let c = vec![Value::from(0), "".into(), "a".into(), Value::Nil, 1.into()];
+        assert_eq!(
+            compact(&[Value::Array(c)]),
+            Ok(Value::Array(vec!["a".into(), 1.into()]))
+        );
+    }
+
+    #[test]
+    fn test_sort_alpha() {
+        let a = Value::Array(vec!["b".into(), 10.into(), "a".into(), 2.into()]);
+        assert_eq!(sort_alpha(&[a]), Ok(Value::from(vec!["10", "2", "a", "b"])));
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(until(&[3.into()]), Ok(l(vec![0, 1, 2])));
+        assert_eq!(until(&[(-2).into()]), Ok(l(vec![0, -1])));
+        assert_eq!(
+            until_step(&[3.into(), 10.into(), 3.into()]),
+            Ok(l(vec![3, 6, 9]))
+        );
+        assert_eq!(until_step(&[0.into(), 10.into(), 0.into()]), Ok(l(vec![])));
+        assert_eq!(
+            until_step(&[0.into(), 10.into(), (-1).into()]),
+            Ok(l(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_chunk() {
+        assert_eq!(
+            chunk(&[2.into(), l(vec![1, 2, 3])]),
+            Ok(Value::Array(vec![l(vec![1, 2]), l(vec![3])]))
+        );
+        assert!(chunk(&[0.into(), l(vec![1])]).is_err());
+    }
+}
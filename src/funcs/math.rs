@@ -0,0 +1,350 @@
+//! Math functions compatible with [Sprig](https://masterminds.github.io/sprig/math.html).
+//!
+//! Unlike in Sprig there are no separate float variants (`addf`, ...). Integers are added
+//! as integers and fail on overflow, as soon as one argument is a float the result is a
+//! float.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_math_funcs();
+//! tmpl.parse(r#"{{ add 1 2 3 | mul 2 }} {{ div 7 2 }} {{ div 7.5 2 }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "12 3 3.75");
+//! ```
+use std::cmp::Ordering;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::utils::{strval, to_int, to_num, Num};
+
+/// All math functions of this module.
+pub static MATH: &[(&str, Func)] = &[
+    ("add", add as Func),
+    ("add1", add1 as Func),
+    ("sub", sub as Func),
+    ("mul", mul as Func),
+    ("div", div as Func),
+    ("mod", modulo as Func),
+    ("max", max as Func),
+    ("min", min as Func),
+    ("floor", floor as Func),
+    ("ceil", ceil as Func),
+    ("round", round as Func),
+    ("int", int as Func),
+    ("int64", int as Func),
+    ("float64", float64 as Func),
+    ("toString", to_string as Func),
+    ("atoi", atoi as Func),
+];
+
+fn num_arg(name: &str, val: &Value) -> Result<Num, String> {
+    match *val {
+        Value::Number(ref n) => Ok(to_num(n)),
+        Value::String(ref s) => {
+            let s = s.trim();
+            s.parse()
+                .map(Num::I)
+                .or_else(|_| s.parse().map(Num::U))
+                .or_else(|_| s.parse().map(Num::F))
+                .map_err(|_| format!("{} requires a number, got {}", name, s))
+        }
+        Value::Bool(b) => Ok(Num::U(b as u64)),
+        _ => Err(format!("{} requires a number, got {}", name, val)),
+    }
+}
+
+fn float_arg(name: &str, val: &Value) -> Result<f64, String> {
+    num_arg(name, val).map(to_f64)
+}
+
+fn to_f64(n: Num) -> f64 {
+    match n {
+        Num::U(u) => u as f64,
+        Num::I(i) => i as f64,
+        Num::F(f) => f,
+    }
+}
+
+fn to_i128(n: Num) -> Option<i128> {
+    match n {
+        Num::U(u) => Some(i128::from(u)),
+        Num::I(i) => Some(i128::from(i)),
+        Num::F(_) => None,
+    }
+}
+
+fn int_num(name: &str, i: Option<i128>) -> Result<Num, String> {
+    match i {
+        Some(i) if i >= 0 && i <= i128::from(u64::MAX) => Ok(Num::U(i as u64)),
+        Some(i) if i < 0 && i >= i128::from(i64::MIN) => Ok(Num::I(i as i64)),
+        _ => Err(format!("{}: integer overflow", name)),
+    }
+}
+
+/// Applies `int_op` if both numbers are integers and `float_op` otherwise.
+fn arith(
+    name: &str,
+    a: Num,
+    b: Num,
+    int_op: fn(i128, i128) -> Option<i128>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Num, String> {
+    match (to_i128(a), to_i128(b)) {
+        (Some(a), Some(b)) => int_num(name, int_op(a, b)),
+        _ => Ok(Num::F(float_op(to_f64(a), to_f64(b)))),
+    }
+}
+
+fn num_value(n: Num) -> Value {
+    match n {
+        Num::U(u) => Value::from(u),
+        Num::I(i) => Value::from(i),
+        Num::F(f) => Value::from(f),
+    }
+}
+
+fn fold(
+    name: &str,
+    args: &[Value],
+    int_op: fn(i128, i128) -> Option<i128>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(format!("{} requires at least 1 argument", name));
+    }
+    let mut acc = num_arg(name, &args[0])?;
+    for arg in &args[1..] {
+        acc = arith(name, acc, num_arg(name, arg)?, int_op, float_op)?;
+    }
+    Ok(num_value(acc))
+}
+
+/// Returns the sum of all arguments.
+pub fn add(args: &[Value]) -> Result<Value, String> {
+    fold("add", args, i128::checked_add, |a, b| a + b)
+}
+
+/// Increments the argument by one.
+pub fn add1(args: &[Value]) -> Result<Value, String> {
+    check_args("add1", args, 1)?;
+    fold(
+        "add1",
+        &[args[0].clone(), Value::from(1)],
+        i128::checked_add,
+        |a, b| a + b,
+    )
+}
+
+/// `sub A B` returns `A - B`.
+pub fn sub(args: &[Value]) -> Result<Value, String> {
+    check_args("sub", args, 2)?;
+    fold("sub", args, i128::checked_sub, |a, b| a - b)
+}
+
+/// Returns the product of all arguments.
+pub fn mul(args: &[Value]) -> Result<Value, String> {
+    fold("mul", args, i128::checked_mul, |a, b| a * b)
+}
+
+/// `div A B` returns `A / B`. Integer division truncates toward zero.
+pub fn div(args: &[Value]) -> Result<Value, String> {
+    check_args("div", args, 2)?;
+    if to_f64(num_arg("div", &args[1])?) == 0.0 {
+        return Err(String::from("div: divide by zero"));
+    }
+    fold("div", args, i128::checked_div, |a, b| a / b)
+}
+
+/// `mod A B` returns the remainder of `A / B` with the sign of `A`.
+pub fn modulo(args: &[Value]) -> Result<Value, String> {
+    check_args("mod", args, 2)?;
+    if to_f64(num_arg("mod", &args[1])?) == 0.0 {
+        return Err(String::from("mod: divide by zero"));
+    }
+    fold("mod", args, i128::checked_rem, |a, b| a % b)
+}
+
+fn cmp_num(a: Num, b: Num) -> Ordering {
+    match (to_i128(a), to_i128(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => to_f64(a).partial_cmp(&to_f64(b)).unwrap_or(Ordering::Equal),
+    }
+}
+
+fn select(name: &str, args: &[Value], keep: Ordering) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(format!("{} requires at least 1 argument", name));
+    }
+    let mut best = num_arg(name, &args[0])?;
+    for arg in &args[1..] {
+        let n = num_arg(name, arg)?;
+        if cmp_num(n, best) == keep {
+            best = n;
+        }
+    }
+    Ok(num_value(best))
+}
+
+/// Returns the largest argument.
+pub fn max(args: &[Value]) -> Result<Value, String> {
+    select("max", args, Ordering::Greater)
+}
+
+/// Returns the smallest argument.
+pub fn min(args: &[Value]) -> Result<Value, String> {
+    select("min", args, Ordering::Less)
+}
+
+/// Returns the greatest integer value less than or equal to the argument.
+pub fn floor(args: &[Value]) -> Result<Value, String> {
+    check_args("floor", args, 1)?;
+    Ok(Value::from(float_arg("floor", &args[0])?.floor()))
+}
+
+/// Returns the least integer value greater than or equal to the argument.
+pub fn ceil(args: &[Value]) -> Result<Value, String> {
+    check_args("ceil", args, 1)?;
+    Ok(Value::from(float_arg("ceil", &args[0])?.ceil()))
+}
+
+/// `round NUMBER PLACES [ROUND_ON]` rounds to the given number of decimal places. The
+/// fraction at which to round up defaults to `.5`.
+pub fn round(args: &[Value]) -> Result<Value, String> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(String::from("round requires 2 or 3 arguments"));
+    }
+    let val = float_arg("round", &args[0])?;
+    let places = float_arg("round", &args[1])?;
+    let round_on = match args.get(2) {
+        Some(r) => float_arg("round", r)?,
+        None => 0.5,
+    };
+    let pow = 10f64.powf(places);
+    let digit = pow * val;
+    let rounded = if digit.fract() >= round_on {
+        digit.ceil()
+    } else {
+        digit.floor()
+    };
+    Ok(Value::from(rounded / pow))
+}
+
+/// Converts the argument to an integer. Floats are truncated and strings are parsed.
+pub fn int(args: &[Value]) -> Result<Value, String> {
+    check_args("int", args, 1)?;
+    to_int(&args[0])
+        .map(Value::from)
+        .ok_or_else(|| format!("unable to convert {} to int", strval(&args[0])))
+}
+
+/// Converts the argument to a float. Strings are parsed.
+pub fn float64(args: &[Value]) -> Result<Value, String> {
+    check_args("float64", args, 1)?;
+    Ok(Value::from(float_arg("float64", &args[0])?))
+}
+
+/// Converts the argument to a string.
+pub fn to_string(args: &[Value]) -> Result<Value, String> {
+    check_args("toString", args, 1)?;
+    Ok(Value::from(strval(&args[0])))
+}
+
+/// Parses a string as integer.
+pub fn atoi(args: &[Value]) -> Result<Value, String> {
+    check_args("atoi", args, 1)?;
+    let s = strval(&args[0]);
+    s.parse::<i64>()
+        .map(Value::from)
+        .map_err(|e| format!("atoi: unable to parse {:?}: {}", s, e))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        assert_eq!(add(&[1.into(), 2.into(), 3.into()]), Ok(Value::from(6)));
+        assert_eq!(add(&[1.into(), 1.5.into()]), Ok(Value::from(2.5)));
+        assert_eq!(
+            add(&[u64::MAX.into(), (-1).into()]),
+            Ok(Value::from(u64::MAX - 1))
+        );
+        assert_eq!(add(&["2".into(), 3.into()]), Ok(Value::from(5)));
+        assert!(add(&[u64::MAX.into(), 1.into()]).is_err());
+        assert!(add(&["foo".into(), 1.into()]).is_err());
+        assert_eq!(add1(&[41.into()]), Ok(Value::from(42)));
+    }
+
+    #[test]
+    fn test_sub_mul() {
+        assert_eq!(sub(&[1.into(), 3.into()]), Ok(Value::from(-2)));
+        assert_eq!(
+            sub(&[i64::MIN.into(), (-1).into()]),
+            Ok(Value::from(i64::MIN + 1))
+        );
+        assert!(sub(&[i64::MIN.into(), 1.into()]).is_err());
+        assert_eq!(mul(&[2.into(), 3.into(), 0.5.into()]), Ok(Value::from(3)));
+        assert!(mul(&[u64::MAX.into(), u64::MAX.into()]).is_err());
+    }
+
+    #[test]
+    fn test_div_mod() {
+        assert_eq!(div(&[7.into(), 2.into()]), Ok(Value::from(3)));
+        assert_eq!(div(&[(-7).into(), 2.into()]), Ok(Value::from(-3)));
+        assert_eq!(div(&[7.into(), 2.5.into()]), Ok(Value::from(2.8)));
+        assert!(div(&[1.into(), 0.into()]).is_err());
+        assert!(div(&[1.5.into(), 0.into()]).is_err());
+        assert_eq!(modulo(&[7.into(), 3.into()]), Ok(Value::from(1)));
+        assert_eq!(modulo(&[(-7).into(), 3.into()]), Ok(Value::from(-1)));
+        assert!(modulo(&[7.into(), 0.into()]).is_err());
+    }
+
+    #[test]
+    fn test_min_max() {
+        let args = [3.into(), 1.5.into(), (-2).into(), u64::MAX.into()];
+        assert_eq!(max(&args), Ok(Value::from(u64::MAX)));
+        assert_eq!(min(&args), Ok(Value::from(-2)));
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(floor(&[1.5.into()]), Ok(Value::from(1)));
+        assert_eq!(ceil(&[1.2.into()]), Ok(Value::from(2)));
+        assert_eq!(round(&[1.2345.into(), 2.into()]), Ok(Value::from(1.23)));
+        assert_eq!(round(&[1.5.into(), 0.into()]), Ok(Value::from(2)));
+        assert_eq!(
+            round(&[1.4.into(), 0.into(), 0.3.into()]),
+            Ok(Value::from(2))
+        );
+    }
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!(int(&[1.9.into()]), Ok(Value::from(1)));
+        assert_eq!(int(&[" 42".into()]), Ok(Value::from(42)));
+        assert!(int(&[u64::MAX.into()]).is_err());
+        assert_eq!(float64(&["1.5".into()]), Ok(Value::from(1.5)));
+        assert_eq!(to_string(&[1.5.into()]), Ok(Value::from("1.5")));
+        assert_eq!(atoi(&["-3".into()]), Ok(Value::from(-3)));
+        assert!(atoi(&["3.5".into()]).is_err());
+    }
+
+    #[test]
+    fn test_large_floats() {
+        assert_eq!(add(&[1e300.into(), 1.into()]), Ok(Value::from(1e300)));
+        assert_eq!(mul(&[(-1e300).into(), 2.into()]), Ok(Value::from(-2e300)));
+        assert_eq!(
+            max(&[1e300.into(), u64::MAX.into()]),
+            Ok(Value::from(1e300))
+        );
+        assert_eq!(
+            int(&[1e300.into()]),
+            Err(String::from("unable to convert 1e+300 to int"))
+        );
+    }
+}
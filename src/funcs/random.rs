@@ -0,0 +1,212 @@
+//! Random functions compatible with
+//! [Sprig](https://masterminds.github.io/sprig/strings.html#randalphanum-randalpha-randnumeric-and-randascii).
+//!
+//! The functions draw from a random number generator created for every execution. It is
+//! seeded from the operating system unless a seed was given with
+//! [`Template::set_seed`](../../struct.Template.html#method.set_seed) or
+//! [`Context::with_seed`](../../struct.Context.html#method.with_seed), in which case every
+//! execution produces the same output.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_random_funcs();
+//! tmpl.parse(r#"{{ randAlpha 8 | len }} {{ randInt 1 2 }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "8 1");
+//! ```
+extern crate rand;
+extern crate rand_chacha;
+
+use std::sync::{Arc, Mutex};
+
+use gtmpl_value::{Func, Value};
+
+use self::rand::seq::SliceRandom;
+use self::rand::{Rng as _, SeedableRng};
+use self::rand_chacha::ChaCha8Rng;
+use crate::funcs::{check_args, with_state};
+use crate::utils::{strval, to_int};
+
+/// All random functions of this module.
+pub static RANDOM: &[(&str, Func)] = &[
+    ("uuidv4", uuidv4 as Func),
+    ("randAlphaNum", rand_alpha_num as Func),
+    ("randAlpha", rand_alpha as Func),
+    ("randNumeric", rand_numeric as Func),
+    ("randInt", rand_int as Func),
+    ("shuffle", shuffle as Func),
+];
+
+const ALPHA: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const NUMERIC: &[u8] = b"0123456789";
+const ALPHA_NUM: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Random number generator of a single execution.
+#[derive(Clone)]
+pub(crate) struct Rng(Arc<Mutex<ChaCha8Rng>>);
+
+impl Rng {
+    pub fn new(seed: Option<u64>) -> Rng {
+        let rng = match seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        Rng(Arc::new(Mutex::new(rng)))
+    }
+}
+
+// Functions called outside of an execution use a fresh generator.
+fn with_rng<R, F: FnOnce(&mut ChaCha8Rng) -> R>(f: F) -> R {
+    let rng = with_state(|s| s.and_then(|s| s.rng.clone())).unwrap_or_else(|| Rng::new(None));
+    let mut rng = rng.0.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut rng)
+}
+
+fn rand_string(name: &str, args: &[Value], chars: &[u8]) -> Result<Value, String> {
+    check_args(name, args, 1)?;
+    let n = to_int(&args[0])
+        .filter(|n| *n >= 0)
+        .ok_or_else(|| format!("{} requires a non-negative length", name))?;
+    let s = with_rng(|rng| {
+        (0..n)
+            .map(|_| *chars.choose(rng).unwrap_or(&b'0') as char)
+            .collect::<String>()
+    });
+    Ok(Value::from(s))
+}
+
+/// Returns a random version 4 UUID.
+pub fn uuidv4(args: &[Value]) -> Result<Value, String> {
+    check_args("uuidv4", args, 0)?;
+    let mut b = [0u8; 16];
+    with_rng(|rng| rng.fill(&mut b));
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex: String = b.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(Value::from(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )))
+}
+
+/// `randAlphaNum N` returns `N` random letters and digits.
+pub fn rand_alpha_num(args: &[Value]) -> Result<Value, String> {
+    rand_string("randAlphaNum", args, ALPHA_NUM)
+}
+
+/// `randAlpha N` returns `N` random letters.
+pub fn rand_alpha(args: &[Value]) -> Result<Value, String> {
+    rand_string("randAlpha", args, ALPHA)
+}
+
+/// `randNumeric N` returns `N` random digits.
+pub fn rand_numeric(args: &[Value]) -> Result<Value, String> {
+    rand_string("randNumeric", args, NUMERIC)
+}
+
+/// `randInt MIN MAX` returns a random integer from `MIN` up to, but not including, `MAX`.
+pub fn rand_int(args: &[Value]) -> Result<Value, String> {
+    check_args("randInt", args, 2)?;
+    let (min, max) = match (to_int(&args[0]), to_int(&args[1])) {
+        (Some(min), Some(max)) if min < max => (min, max),
+        _ => {
+            return Err(format!(
+                "randInt requires integers MIN < MAX, got {} and {}",
+                args[0], args[1]
+            ))
+        }
+    };
+    Ok(Value::from(with_rng(|rng| rng.gen_range(min..max))))
+}
+
+/// Shuffles the characters of a string or the items of a list.
+pub fn shuffle(args: &[Value]) -> Result<Value, String> {
+    check_args("shuffle", args, 1)?;
+    Ok(match args[0] {
+        Value::Array(ref items) => {
+            let mut items = items.clone();
+            with_rng(|rng| items.shuffle(rng));
+            Value::Array(items)
+        }
+        ref val => {
+            let mut chars: Vec<char> = strval(val).chars().collect();
+            with_rng(|rng| chars.shuffle(rng));
+            Value::from(chars.into_iter().collect::<String>())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::funcs::{enter, FuncState};
+
+    #[test]
+    fn test_rand_string() {
+        let s = strval(&rand_alpha_num(&[20.into()]).unwrap());
+        assert_eq!(s.len(), 20);
+        assert!(s.bytes().all(|c| c.is_ascii_alphanumeric()));
+        let s = strval(&rand_alpha(&[20.into()]).unwrap());
+        assert!(s.bytes().all(|c| c.is_ascii_alphabetic()));
+        let s = strval(&rand_numeric(&["20".into()]).unwrap());
+        assert!(s.bytes().all(|c| c.is_ascii_digit()));
+        assert_eq!(rand_alpha(&[0.into()]), Ok(Value::from("")));
+        assert!(rand_alpha(&[(-1).into()]).is_err());
+    }
+
+    #[test]
+    fn test_rand_int() {
+        for _ in 0..100 {
+            let n = to_int(&rand_int(&[(-2).into(), 3.into()]).unwrap()).unwrap();
+            assert!((-2..3).contains(&n));
+        }
+        assert_eq!(rand_int(&[7.into(), 8.into()]), Ok(Value::from(7)));
+        assert!(rand_int(&[3.into(), 3.into()]).is_err());
+    }
+
+    #[test]
+    fn test_uuidv4() {
+        let u = strval(&uuidv4(&[]).unwrap());
+        assert_eq!(u.len(), 36);
+        assert_eq!(&u[14..15], "4");
+        assert!("89ab".contains(&u[19..20]));
+        assert_ne!(uuidv4(&[]), uuidv4(&[]));
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let s = strval(&shuffle(&["hello".into()]).unwrap());
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.sort();
+        assert_eq!(chars, vec!['e', 'h', 'l', 'l', 'o']);
+        let list = shuffle(&[Value::from(vec![1, 2, 3])]).unwrap();
+        match list {
+            Value::Array(ref items) => assert_eq!(items.len(), 3),
+            _ => panic!("expected a list"),
+        }
+    }
+
+    #[test]
+    fn test_seed() {
+        let draw = || {
+            let state = FuncState {
+                rng: Some(Rng::new(Some(42))),
+                ..Default::default()
+            };
+            let _guard = enter(&state);
+            (
+                uuidv4(&[]).unwrap(),
+                rand_alpha_num(&[16.into()]).unwrap(),
+                shuffle(&["abcdefgh".into()]).unwrap(),
+            )
+        };
+        assert_eq!(draw(), draw());
+    }
+}
@@ -0,0 +1,213 @@
+//! Regular expression functions compatible with
+//! [Sprig](https://masterminds.github.io/sprig/strings.html#regexmatch-mustregexmatch).
+//!
+//! The pattern is always the first argument and uses the syntax of the
+//! [regex](https://docs.rs/regex) crate, which is very close to golang's RE2 syntax. Invalid
+//! patterns fail rendering.
+//!
+//! Compiled patterns are cached per `Template`, so using the same pattern inside a `range`
+//! compiles it only once.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_regex_funcs();
+//! tmpl.parse(r#"{{ regexReplaceAll "[^a-z0-9-]+" . "-" }}"#).unwrap();
+//! let output = tmpl.render(&Context::from("my app_v2").unwrap());
+//! assert_eq!(&output.unwrap(), "my-app-v2");
+//! ```
+extern crate regex;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use gtmpl_value::{Func, Value};
+
+use self::regex::{NoExpand, Regex};
+use crate::funcs::{check_args, with_state};
+use crate::utils::{strval, to_int};
+
+/// All regular expression functions of this module.
+pub static REGEX: &[(&str, Func)] = &[
+    ("regexMatch", regex_match as Func),
+    ("regexFind", regex_find as Func),
+    ("regexFindAll", regex_find_all as Func),
+    ("regexReplaceAll", regex_replace_all as Func),
+    ("regexReplaceAllLiteral", regex_replace_all_literal as Func),
+    ("regexSplit", regex_split as Func),
+];
+
+// Upper bound for the number of cached patterns. Patterns might come from the data passed
+// to the template, so the cache is cleared once it is full.
+const MAX_CACHED: usize = 1000;
+
+/// Cache of compiled patterns shared by all executions of a `Template`.
+#[derive(Clone, Default)]
+pub(crate) struct Cache(Arc<Mutex<HashMap<String, Regex>>>);
+
+fn compile(name: &str, pattern: &Value) -> Result<Regex, String> {
+    let pattern = strval(pattern);
+    let cache = with_state(|s| s.map(|s| s.regex_cache.clone()));
+    if let Some(Cache(ref cache)) = cache {
+        if let Some(re) = cache.lock().ok().and_then(|c| c.get(&pattern).cloned()) {
+            return Ok(re);
+        }
+    }
+    let re = Regex::new(&pattern).map_err(|e| format!("{}: {}", name, e))?;
+    if let Some(Cache(ref cache)) = cache {
+        if let Ok(mut cache) = cache.lock() {
+            if cache.len() >= MAX_CACHED {
+                cache.clear();
+            }
+            cache.insert(pattern, re.clone());
+        }
+    }
+    Ok(re)
+}
+
+fn limit(name: &str, n: &Value) -> Result<usize, String> {
+    let n = to_int(n).ok_or_else(|| format!("{} requires an integer limit", name))?;
+    Ok(if n < 0 { usize::MAX } else { n as usize })
+}
+
+/// `regexMatch REGEX STRING` tests whether the string contains a match of the pattern.
+pub fn regex_match(args: &[Value]) -> Result<Value, String> {
+    check_args("regexMatch", args, 2)?;
+    let re = compile("regexMatch", &args[0])?;
+    Ok(Value::from(re.is_match(&strval(&args[1]))))
+}
+
+/// `regexFind REGEX STRING` returns the first match or an empty string.
+pub fn regex_find(args: &[Value]) -> Result<Value, String> {
+    check_args("regexFind", args, 2)?;
+    let re = compile("regexFind", &args[0])?;
+    let s = strval(&args[1]);
+    Ok(Value::from(re.find(&s).map(|m| m.as_str()).unwrap_or("")))
+}
+
+/// `regexFindAll REGEX STRING N` returns up to `N` matches, all of them if `N` is negative.
+pub fn regex_find_all(args: &[Value]) -> Result<Value, String> {
+    check_args("regexFindAll", args, 3)?;
+    let re = compile("regexFindAll", &args[0])?;
+    let n = limit("regexFindAll", &args[2])?;
+    let s = strval(&args[1]);
+    Ok(Value::Array(
+        re.find_iter(&s)
+            .take(n)
+            .map(|m| Value::from(m.as_str()))
+            .collect(),
+    ))
+}
+
+/// `regexReplaceAll REGEX STRING REPLACEMENT` replaces all matches. `$1` or `${name}` in the
+/// replacement expand to the captured groups.
+pub fn regex_replace_all(args: &[Value]) -> Result<Value, String> {
+    check_args("regexReplaceAll", args, 3)?;
+    let re = compile("regexReplaceAll", &args[0])?;
+    let s = strval(&args[1]);
+    Ok(Value::from(
+        re.replace_all(&s, strval(&args[2]).as_str()).into_owned(),
+    ))
+}
+
+/// `regexReplaceAllLiteral REGEX STRING REPLACEMENT` replaces all matches without expanding
+/// the replacement.
+pub fn regex_replace_all_literal(args: &[Value]) -> Result<Value, String> {
+    check_args("regexReplaceAllLiteral", args, 3)?;
+    let re = compile("regexReplaceAllLiteral", &args[0])?;
+    let s = strval(&args[1]);
+    let repl = strval(&args[2]);
+    Ok(Value::from(
+        re.replace_all(&s, NoExpand(&repl)).into_owned(),
+    ))
+}
+
+/// `regexSplit REGEX STRING N` splits the string around matches into at most `N` substrings,
+/// all of them if `N` is negative.
+pub fn regex_split(args: &[Value]) -> Result<Value, String> {
+    check_args("regexSplit", args, 3)?;
+    let re = compile("regexSplit", &args[0])?;
+    let n = limit("regexSplit", &args[2])?;
+    let s = strval(&args[1]);
+    let parts: Vec<Value> = if n == usize::MAX {
+        re.split(&s).map(Value::from).collect()
+    } else {
+        re.splitn(&s, n).map(Value::from).collect()
+    };
+    Ok(Value::Array(parts))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    #[test]
+    fn test_match_find() {
+        assert_eq!(
+            regex_match(&["^[a-z]+$".into(), "abc".into()]),
+            Ok(Value::from(true))
+        );
+        assert_eq!(
+            regex_match(&["^[a-z]+$".into(), "aBc".into()]),
+            Ok(Value::from(false))
+        );
+        assert!(regex_match(&["(".into(), "abc".into()]).is_err());
+        assert_eq!(
+            regex_find(&["[0-9]+".into(), "ab12cd34".into()]),
+            Ok(Value::from("12"))
+        );
+        assert_eq!(
+            regex_find(&["[0-9]+".into(), "abcd".into()]),
+            Ok(Value::from(""))
+        );
+        assert_eq!(
+            regex_find_all(&["[0-9]".into(), "a1b2c3".into(), (-1).into()]),
+            Ok(Value::from(vec!["1", "2", "3"]))
+        );
+        assert_eq!(
+            regex_find_all(&["[0-9]".into(), "a1b2c3".into(), 2.into()]),
+            Ok(Value::from(vec!["1", "2"]))
+        );
+    }
+
+    #[test]
+    fn test_replace_split() {
+        assert_eq!(
+            regex_replace_all(&["a(x*)b".into(), "-ab-axxb-".into(), "${1}W".into()]),
+            Ok(Value::from("-W-xxW-"))
+        );
+        assert_eq!(
+            regex_replace_all_literal(&["a(x*)b".into(), "-ab-axxb-".into(), "${1}".into()]),
+            Ok(Value::from("-${1}-${1}-"))
+        );
+        assert_eq!(
+            regex_split(&["z+".into(), "pizza".into(), (-1).into()]),
+            Ok(Value::from(vec!["pi", "a"]))
+        );
+        assert_eq!(
+            regex_split(&[",".into(), "a,b,c".into(), 2.into()]),
+            Ok(Value::from(vec!["a", "b,c"]))
+        );
+        assert_eq!(
+            regex_split(&[",".into(), "a,b,c".into(), 0.into()]),
+            Ok(Value::Array(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let state = crate::funcs::FuncState::default();
+        let cache = state.regex_cache.clone();
+        {
+            let _guard = crate::funcs::enter(&state);
+            regex_match(&["a+".into(), "aa".into()]).unwrap();
+            regex_match(&["a+".into(), "ba".into()]).unwrap();
+            regex_find(&["b".into(), "ba".into()]).unwrap();
+        }
+        assert_eq!(cache.0.lock().unwrap().len(), 2);
+        regex_match(&["c".into(), "c".into()]).unwrap();
+        assert_eq!(cache.0.lock().unwrap().len(), 2);
+    }
+}
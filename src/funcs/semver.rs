@@ -0,0 +1,477 @@
+//! Semantic version functions compatible with
+//! [Sprig](https://masterminds.github.io/sprig/semver.html).
+//!
+//! Versions are parsed leniently like by
+//! [Masterminds/semver](https://github.com/Masterminds/semver): a leading `v` is allowed and
+//! missing minor or patch numbers are zero. Constraints support comparisons (`=`, `!=`, `>`,
+//! `<`, `>=`, `<=`), wildcards (`1.2.x`), hyphen ranges (`1.2 - 1.4.5`), tilde (`~1.2.3`) and
+//! caret (`^1.2.3`) ranges. Constraints separated by commas or spaces must all match,
+//! alternatives are separated by `||`. Versions with a prerelease only match constraints
+//! with a prerelease, so `>=1.21-0` matches `1.21.3-gke.100` but `>=1.21` does not.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_semver_funcs();
+//! tmpl.parse(r#"{{ if semverCompare ">=1.21-0" . }}v1{{ else }}v1beta1{{ end }}"#).unwrap();
+//! let output = tmpl.render(&Context::from("v1.21.3-gke.100").unwrap());
+//! assert_eq!(&output.unwrap(), "v1");
+//! ```
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::utils::strval;
+
+/// All semantic version functions of this module.
+pub static SEMVER: &[(&str, Func)] = &[
+    ("semver", semver as Func),
+    ("semverCompare", semver_compare as Func),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: String,
+    meta: String,
+}
+
+fn is_ident(s: &str) -> bool {
+    !s.is_empty()
+        && s.split('.')
+            .all(|p| !p.is_empty() && p.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-'))
+}
+
+fn parse_num(s: &str) -> Result<u64, String> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(format!("invalid version segment {:?}", s));
+    }
+    s.parse()
+        .map_err(|_| format!("version segment {:?} is too large", s))
+}
+
+impl Version {
+    fn parse(s: &str) -> Result<Version, String> {
+        let invalid = || format!("invalid semantic version {:?}", s);
+        let v = s.strip_prefix('v').unwrap_or(s);
+        let (v, meta) = match v.find('+') {
+            Some(i) => (&v[..i], Some(&v[i + 1..])),
+            None => (v, None),
+        };
+        let (v, pre) = match v.find('-') {
+            Some(i) => (&v[..i], Some(&v[i + 1..])),
+            None => (v, None),
+        };
+        if pre.map(|p| !is_ident(p)).unwrap_or(false) || meta.map(|m| !is_ident(m)).unwrap_or(false)
+        {
+            return Err(invalid());
+        }
+        let (pre, meta) = (pre.unwrap_or(""), meta.unwrap_or(""));
+        if pre
+            .split('.')
+            .any(|p| p.len() > 1 && p.starts_with('0') && p.bytes().all(|c| c.is_ascii_digit()))
+        {
+            return Err(format!("{:?}: version segment starts with 0", s));
+        }
+        let mut nums = v.split('.');
+        let major = parse_num(nums.next().unwrap_or("")).map_err(|_| invalid())?;
+        let minor = nums
+            .next()
+            .map(parse_num)
+            .unwrap_or(Ok(0))
+            .map_err(|_| invalid())?;
+        let patch = nums
+            .next()
+            .map(parse_num)
+            .unwrap_or(Ok(0))
+            .map_err(|_| invalid())?;
+        if nums.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            pre: pre.to_owned(),
+            meta: meta.to_owned(),
+        })
+    }
+
+    fn to_value(&self, original: &str) -> Value {
+        let mut m = HashMap::new();
+        m.insert("Major".to_owned(), Value::from(self.major));
+        m.insert("Minor".to_owned(), Value::from(self.minor));
+        m.insert("Patch".to_owned(), Value::from(self.patch));
+        m.insert("Prerelease".to_owned(), Value::from(self.pre.clone()));
+        m.insert("Metadata".to_owned(), Value::from(self.meta.clone()));
+        m.insert("Original".to_owned(), Value::from(original));
+        Value::Object(m)
+    }
+}
+
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        _ => {}
+    }
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        let ord = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => x.cmp(y),
+            },
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(
+            (self.major, self.minor, self.patch)
+                .cmp(&(other.major, other.minor, other.patch))
+                .then_with(|| compare_prerelease(&self.pre, &other.pre)),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    TildeOrEqual,
+    NotEqual,
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+    Tilde,
+    Caret,
+}
+
+#[derive(Debug)]
+struct Constraint {
+    op: Op,
+    con: Version,
+    // The whole version, the minor or the patch number was a wildcard or missing.
+    dirty: bool,
+    minor_dirty: bool,
+    patch_dirty: bool,
+}
+
+fn is_x(s: &str) -> bool {
+    s == "x" || s == "X" || s == "*"
+}
+
+impl Constraint {
+    fn parse(op: &str, ver: &str) -> Result<Constraint, String> {
+        let op = match op {
+            "" | "=" => Op::TildeOrEqual,
+            "!=" => Op::NotEqual,
+            ">" => Op::Greater,
+            "<" => Op::Less,
+            ">=" | "=>" => Op::GreaterEqual,
+            "<=" | "=<" => Op::LessEqual,
+            "~" | "~>" => Op::Tilde,
+            "^" => Op::Caret,
+            _ => return Err(format!("improper constraint: {}{}", op, ver)),
+        };
+        let v = ver.strip_prefix('v').unwrap_or(ver);
+        // Split off prerelease and metadata, wildcards are only allowed in the numbers.
+        let end = v.find(['-', '+']).unwrap_or(v.len());
+        let (nums, rest) = v.split_at(end);
+        let parts: Vec<&str> = nums.split('.').collect();
+        if parts.len() > 3 {
+            return Err(format!("improper constraint: {}{}", op_str(op), ver));
+        }
+        let part = |i: usize| parts.get(i).cloned().unwrap_or("");
+        let (mut dirty, mut minor_dirty, mut patch_dirty) = (false, false, false);
+        let normalized = if is_x(part(0)) || part(0).is_empty() {
+            dirty = true;
+            format!("0.0.0{}", rest)
+        } else if is_x(part(1)) || part(1).is_empty() {
+            dirty = true;
+            minor_dirty = true;
+            format!("{}.0.0{}", part(0), rest)
+        } else if is_x(part(2)) || part(2).is_empty() {
+            dirty = true;
+            patch_dirty = true;
+            format!("{}.{}.0{}", part(0), part(1), rest)
+        } else {
+            v.to_owned()
+        };
+        let con = Version::parse(&normalized)
+            .map_err(|_| format!("improper constraint: {}{}", op_str(op), ver))?;
+        Ok(Constraint {
+            op,
+            con,
+            dirty,
+            minor_dirty,
+            patch_dirty,
+        })
+    }
+
+    fn check(&self, v: &Version) -> bool {
+        let c = &self.con;
+        // Prereleases only match constraints that ask for them.
+        if !v.pre.is_empty() && c.pre.is_empty() {
+            return false;
+        }
+        match self.op {
+            Op::TildeOrEqual if self.dirty => self.tilde(v),
+            Op::TildeOrEqual => v.partial_cmp(c) == Some(Ordering::Equal),
+            Op::NotEqual => {
+                if self.dirty {
+                    if c.major != v.major {
+                        return true;
+                    }
+                    if self.minor_dirty {
+                        return false;
+                    }
+                    if c.minor != v.minor {
+                        return true;
+                    }
+                    if self.patch_dirty {
+                        return compare_prerelease(&v.pre, &c.pre) != Ordering::Equal;
+                    }
+                }
+                v.partial_cmp(c) != Some(Ordering::Equal)
+            }
+            Op::Greater => {
+                if !self.dirty || v.major != c.major {
+                    v > c
+                } else if self.minor_dirty {
+                    false
+                } else if self.patch_dirty {
+                    v.minor > c.minor
+                } else {
+                    v > c
+                }
+            }
+            Op::Less => v < c,
+            Op::GreaterEqual => v >= c,
+            Op::LessEqual => {
+                if !self.dirty {
+                    v <= c
+                } else {
+                    v.major < c.major
+                        || v.major == c.major && (self.minor_dirty || v.minor <= c.minor)
+                }
+            }
+            Op::Tilde => self.tilde(v),
+            Op::Caret => {
+                if v < c {
+                    false
+                } else if c.major > 0 || self.minor_dirty {
+                    v.major == c.major
+                } else if v.major > 0 {
+                    false
+                } else if c.minor > 0 || self.patch_dirty {
+                    v.minor == c.minor
+                } else {
+                    v.minor == 0 && v.patch == c.patch
+                }
+            }
+        }
+    }
+
+    fn tilde(&self, v: &Version) -> bool {
+        let c = &self.con;
+        if v < c {
+            return false;
+        }
+        // ~0.0.0 and ~* match everything.
+        if c.major == 0 && c.minor == 0 && c.patch == 0 && !self.minor_dirty && !self.patch_dirty {
+            return true;
+        }
+        v.major == c.major && (self.minor_dirty || v.minor == c.minor)
+    }
+}
+
+fn op_str(op: Op) -> &'static str {
+    match op {
+        Op::TildeOrEqual => "",
+        Op::NotEqual => "!=",
+        Op::Greater => ">",
+        Op::Less => "<",
+        Op::GreaterEqual => ">=",
+        Op::LessEqual => "<=",
+        Op::Tilde => "~",
+        Op::Caret => "^",
+    }
+}
+
+fn is_op_char(c: char) -> bool {
+    "<>=!~^".contains(c)
+}
+
+/// Parses alternatives separated by `||` of constraints that must all match.
+fn parse_constraints(s: &str) -> Result<Vec<Vec<Constraint>>, String> {
+    s.split("||")
+        .map(|group| {
+            let words: Vec<&str> = group
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|w| !w.is_empty())
+                .collect();
+            let mut constraints = vec![];
+            let mut i = 0;
+            while i < words.len() {
+                let word = words[i];
+                let (op, ver) = match word.find(|c: char| !is_op_char(c)) {
+                    Some(pos) => word.split_at(pos),
+                    None => {
+                        // The operator is separated from the version by spaces.
+                        i += 1;
+                        let ver = words
+                            .get(i)
+                            .ok_or_else(|| format!("improper constraint: {}", group.trim()))?;
+                        (word, *ver)
+                    }
+                };
+                if words.get(i + 1) == Some(&"-") {
+                    // A hyphen range `A - B` is `>=A, <=B`.
+                    let upper = words
+                        .get(i + 2)
+                        .ok_or_else(|| format!("improper constraint: {}", group.trim()))?;
+                    if !op.is_empty() {
+                        return Err(format!("improper constraint: {}", group.trim()));
+                    }
+                    constraints.push(Constraint::parse(">=", ver)?);
+                    constraints.push(Constraint::parse("<=", upper)?);
+                    i += 3;
+                    continue;
+                }
+                constraints.push(Constraint::parse(op, ver)?);
+                i += 1;
+            }
+            if constraints.is_empty() {
+                constraints.push(Constraint::parse("", "*")?);
+            }
+            Ok(constraints)
+        })
+        .collect()
+}
+
+/// Parses a semantic version into an object with the fields `Major`, `Minor`, `Patch`,
+/// `Prerelease`, `Metadata` and `Original`.
+pub fn semver(args: &[Value]) -> Result<Value, String> {
+    check_args("semver", args, 1)?;
+    let s = strval(&args[0]);
+    Ok(Version::parse(&s)?.to_value(&s))
+}
+
+/// `semverCompare CONSTRAINT VERSION` tests whether the version satisfies the constraint.
+pub fn semver_compare(args: &[Value]) -> Result<Value, String> {
+    check_args("semverCompare", args, 2)?;
+    let constraints = parse_constraints(&strval(&args[0]))?;
+    let v = Version::parse(&strval(&args[1]))?;
+    Ok(Value::from(
+        constraints
+            .iter()
+            .any(|group| group.iter().all(|c| c.check(&v))),
+    ))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn matches(c: &str, v: &str) -> bool {
+        semver_compare(&[c.into(), v.into()]) == Ok(Value::from(true))
+    }
+
+    #[test]
+    fn test_semver() {
+        let v = semver(&["v1.2.3-beta.1+build.5".into()]).unwrap();
+        if let Value::Object(ref o) = v {
+            assert_eq!(o["Major"], Value::from(1));
+            assert_eq!(o["Minor"], Value::from(2));
+            assert_eq!(o["Patch"], Value::from(3));
+            assert_eq!(o["Prerelease"], Value::from("beta.1"));
+            assert_eq!(o["Metadata"], Value::from("build.5"));
+            assert_eq!(o["Original"], Value::from("v1.2.3-beta.1+build.5"));
+        } else {
+            panic!("expected an object, got {}", v);
+        }
+        let v = semver(&["1.2".into()]).unwrap();
+        if let Value::Object(ref o) = v {
+            assert_eq!(o["Patch"], Value::from(0));
+        }
+        assert!(semver(&["1.2.3.4".into()]).is_err());
+        assert!(semver(&["1.x".into()]).is_err());
+        assert!(semver(&["1.2.3-01".into()]).is_err());
+        assert!(semver(&["1.2.3-".into()]).is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ];
+        for w in versions.windows(2) {
+            let (a, b) = (Version::parse(w[0]).unwrap(), Version::parse(w[1]).unwrap());
+            assert!(a < b, "{} < {}", w[0], w[1]);
+        }
+    }
+
+    #[test]
+    fn test_semver_compare() {
+        let tests = [
+            ("1.2.3", "1.2.3", true),
+            ("=1.2.3", "1.2.4", false),
+            ("!=1.2.3", "1.2.4", true),
+            ("1.2", "1.2.9", true),
+            ("1.2.x", "1.3.0", false),
+            ("*", "4.5.6", true),
+            ("", "4.5.6", true),
+            (">1.2", "1.2.9", false),
+            (">1.2", "1.3.0", true),
+            (">= 1.2, < 2", "1.9.9", true),
+            (">=1.2 <2", "2.0.0", false),
+            ("<=1.4", "1.4.9", true),
+            ("1.2 - 1.4.5", "1.4.5", true),
+            ("1.2 - 1.4.5", "1.4.6", false),
+            ("~1.2.3", "1.2.9", true),
+            ("~1.2.3", "1.3.0", false),
+            ("~1", "1.9.0", true),
+            ("^1.2.3", "1.9.0", true),
+            ("^1.2.3", "2.0.0", false),
+            ("^0.2.3", "0.2.9", true),
+            ("^0.2.3", "0.3.0", false),
+            ("^0.0.3", "0.0.4", false),
+            ("<1.0 || >=2.1", "2.2.0", true),
+            ("<1.0 || >=2.1", "1.5.0", false),
+            (">=1.21", "1.21.3-gke.100", false),
+            (">=1.21-0", "v1.21.3-gke.100", true),
+            (">=1.21-0", "1.20.9", false),
+            (">1.0.0-alpha", "1.0.0-beta", true),
+        ];
+        for &(c, v, expected) in &tests {
+            assert_eq!(matches(c, v), expected, "{} {}", c, v);
+        }
+        assert!(semver_compare(&[">=foo".into(), "1.0.0".into()]).is_err());
+        assert!(semver_compare(&[">=1.0".into(), "foo".into()]).is_err());
+    }
+}
@@ -0,0 +1,399 @@
+//! String functions compatible with [Sprig](https://masterminds.github.io/sprig/strings.html).
+//!
+//! Like in Sprig the string to operate on is always the last argument, so these functions
+//! can be used at the end of a pipeline.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_string_funcs();
+//! tmpl.parse(r#"{{ "  hello  " | trim | upper | quote }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), r#""HELLO""#);
+//! ```
+use std::collections::HashMap;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::utils::{quote_str, strval, to_int};
+
+/// All string functions of this module.
+pub static STRINGS: &[(&str, Func)] = &[
+    ("upper", upper as Func),
+    ("lower", lower as Func),
+    ("title", title as Func),
+    ("trim", trim as Func),
+    ("trimPrefix", trim_prefix as Func),
+    ("trimSuffix", trim_suffix as Func),
+    ("replace", replace as Func),
+    ("contains", contains as Func),
+    ("hasPrefix", has_prefix as Func),
+    ("split", split as Func),
+    ("splitList", split_list as Func),
+    ("join", join as Func),
+    ("repeat", repeat as Func),
+    ("substr", substr as Func),
+    ("trunc", trunc as Func),
+    ("abbrev", abbrev as Func),
+    ("quote", quote as Func),
+    ("squote", squote as Func),
+    ("indent", indent as Func),
+    ("nindent", nindent as Func),
+    ("wrap", wrap as Func),
+];
+
+fn int_arg(name: &str, val: &Value) -> Result<i64, String> {
+    to_int(val).ok_or_else(|| format!("{} requires an integer, got {}", name, val))
+}
+
+/// Converts the string to upper case.
+pub fn upper(args: &[Value]) -> Result<Value, String> {
+    check_args("upper", args, 1)?;
+    Ok(Value::from(strval(&args[0]).to_uppercase()))
+}
+
+/// Converts the string to lower case.
+pub fn lower(args: &[Value]) -> Result<Value, String> {
+    check_args("lower", args, 1)?;
+    Ok(Value::from(strval(&args[0]).to_lowercase()))
+}
+
+/// Converts the first letter of each word to upper case.
+pub fn title(args: &[Value]) -> Result<Value, String> {
+    check_args("title", args, 1)?;
+    let mut prev = ' ';
+    let s = strval(&args[0])
+        .chars()
+        .flat_map(|c| {
+            let sep = is_separator(prev);
+            prev = c;
+            let upper: Vec<char> = if sep {
+                c.to_uppercase().collect()
+            } else {
+                vec![c]
+            };
+            upper
+        })
+        .collect::<String>();
+    Ok(Value::from(s))
+}
+
+// Word boundaries like golang's `strings.Title`.
+fn is_separator(c: char) -> bool {
+    if c.is_ascii() {
+        return !(c.is_ascii_alphanumeric() || c == '_');
+    }
+    c.is_whitespace()
+}
+
+/// Removes leading and trailing white space.
+pub fn trim(args: &[Value]) -> Result<Value, String> {
+    check_args("trim", args, 1)?;
+    Ok(Value::from(strval(&args[0]).trim()))
+}
+
+/// `trimPrefix PREFIX STRING` removes the prefix if the string starts with it.
+pub fn trim_prefix(args: &[Value]) -> Result<Value, String> {
+    check_args("trimPrefix", args, 2)?;
+    let (prefix, s) = (strval(&args[0]), strval(&args[1]));
+    Ok(Value::from(s.strip_prefix(prefix.as_str()).unwrap_or(&s)))
+}
+
+/// `trimSuffix SUFFIX STRING` removes the suffix if the string ends with it.
+pub fn trim_suffix(args: &[Value]) -> Result<Value, String> {
+    check_args("trimSuffix", args, 2)?;
+    let (suffix, s) = (strval(&args[0]), strval(&args[1]));
+    Ok(Value::from(s.strip_suffix(suffix.as_str()).unwrap_or(&s)))
+}
+
+/// `replace OLD NEW STRING` replaces all occurrences of `OLD` by `NEW`.
+pub fn replace(args: &[Value]) -> Result<Value, String> {
+    check_args("replace", args, 3)?;
+    let (old, new, s) = (strval(&args[0]), strval(&args[1]), strval(&args[2]));
+    Ok(Value::from(s.replace(&old, &new)))
+}
+
+/// `contains SUBSTR STRING` tests whether the string contains `SUBSTR`.
+pub fn contains(args: &[Value]) -> Result<Value, String> {
+    check_args("contains", args, 2)?;
+    Ok(Value::from(strval(&args[1]).contains(&strval(&args[0]))))
+}
+
+/// `hasPrefix PREFIX STRING` tests whether the string starts with `PREFIX`.
+pub fn has_prefix(args: &[Value]) -> Result<Value, String> {
+    check_args("hasPrefix", args, 2)?;
+    Ok(Value::from(strval(&args[1]).starts_with(&strval(&args[0]))))
+}
+
+/// `split SEP STRING` splits the string into a map with the keys `_0`, `_1`, ...
+pub fn split(args: &[Value]) -> Result<Value, String> {
+    check_args("split", args, 2)?;
+    let (sep, s) = (strval(&args[0]), strval(&args[1]));
+    let m: HashMap<String, Value> = split_str(&s, &sep)
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| (format!("_{}", i), Value::from(part)))
+        .collect();
+    Ok(Value::Map(m))
+}
+
+/// `splitList SEP STRING` splits the string into a list.
+pub fn split_list(args: &[Value]) -> Result<Value, String> {
+    check_args("splitList", args, 2)?;
+    let (sep, s) = (strval(&args[0]), strval(&args[1]));
+    Ok(Value::Array(
+        split_str(&s, &sep).into_iter().map(Value::from).collect(),
+    ))
+}
+
+// Like golang an empty separator splits after each character.
+fn split_str<'a>(s: &'a str, sep: &str) -> Vec<&'a str> {
+    if sep.is_empty() {
+        s.char_indices()
+            .map(|(i, c)| &s[i..i + c.len_utf8()])
+            .collect()
+    } else {
+        s.split(sep).collect()
+    }
+}
+
+/// `join SEP LIST` joins the elements of a list into a single string.
+pub fn join(args: &[Value]) -> Result<Value, String> {
+    check_args("join", args, 2)?;
+    let sep = strval(&args[0]);
+    let parts: Vec<String> = match args[1] {
+        Value::Array(ref a) => a
+            .iter()
+            .filter(|v| !matches!(**v, Value::Nil | Value::NoValue))
+            .map(strval)
+            .collect(),
+        Value::Nil | Value::NoValue => vec![],
+        ref v => vec![strval(v)],
+    };
+    Ok(Value::from(parts.join(&sep)))
+}
+
+/// `repeat COUNT STRING` repeats the string `COUNT` times.
+pub fn repeat(args: &[Value]) -> Result<Value, String> {
+    check_args("repeat", args, 2)?;
+    let count = int_arg("repeat", &args[0])?;
+    if count < 0 {
+        return Err(String::from("repeat count must not be negative"));
+    }
+    Ok(Value::from(strval(&args[1]).repeat(count as usize)))
+}
+
+/// `substr START END STRING` returns the characters from `START` to `END`. A negative `START`
+/// means from the beginning and a negative `END` means till the end.
+pub fn substr(args: &[Value]) -> Result<Value, String> {
+    check_args("substr", args, 3)?;
+    let start = int_arg("substr", &args[0])?;
+    let end = int_arg("substr", &args[1])?;
+    let chars: Vec<char> = strval(&args[2]).chars().collect();
+    let len = chars.len() as i64;
+    let start = start.max(0).min(len);
+    let end = if end < 0 || end > len { len } else { end };
+    if start >= end {
+        return Ok(Value::from(""));
+    }
+    Ok(Value::from(
+        chars[start as usize..end as usize]
+            .iter()
+            .collect::<String>(),
+    ))
+}
+
+/// `trunc LENGTH STRING` truncates the string to `LENGTH` characters. A negative length
+/// keeps the last characters.
+pub fn trunc(args: &[Value]) -> Result<Value, String> {
+    check_args("trunc", args, 2)?;
+    let c = int_arg("trunc", &args[0])?;
+    let chars: Vec<char> = strval(&args[1]).chars().collect();
+    let len = chars.len() as i64;
+    let s: String = if c < 0 && len + c > 0 {
+        chars[(len + c) as usize..].iter().collect()
+    } else if c >= 0 && len > c {
+        chars[..c as usize].iter().collect()
+    } else {
+        chars.iter().collect()
+    };
+    Ok(Value::from(s))
+}
+
+/// `abbrev WIDTH STRING` truncates the string with an ellipsis to at most `WIDTH` characters.
+pub fn abbrev(args: &[Value]) -> Result<Value, String> {
+    check_args("abbrev", args, 2)?;
+    let width = int_arg("abbrev", &args[0])?;
+    let s = strval(&args[1]);
+    if width < 4 || s.chars().count() as i64 <= width {
+        return Ok(Value::from(s));
+    }
+    let abbr: String = s.chars().take(width as usize - 3).collect();
+    Ok(Value::from(abbr + "..."))
+}
+
+/// Wraps each argument in double quotes and joins them with a space.
+pub fn quote(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::from(quote_all(args, |s| quote_str(&s, false))))
+}
+
+/// Wraps each argument in single quotes and joins them with a space.
+pub fn squote(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::from(quote_all(args, |s| format!("'{}'", s))))
+}
+
+fn quote_all<F: Fn(String) -> String>(args: &[Value], f: F) -> String {
+    args.iter()
+        .filter(|v| !matches!(**v, Value::Nil | Value::NoValue))
+        .map(|v| f(strval(v)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `indent SPACES STRING` indents every line by the given number of spaces.
+pub fn indent(args: &[Value]) -> Result<Value, String> {
+    check_args("indent", args, 2)?;
+    Ok(Value::from(indent_str(
+        int_arg("indent", &args[0])?,
+        &strval(&args[1]),
+    )))
+}
+
+/// Like `indent` but prepends a new line.
+pub fn nindent(args: &[Value]) -> Result<Value, String> {
+    check_args("nindent", args, 2)?;
+    Ok(Value::from(format!(
+        "\n{}",
+        indent_str(int_arg("nindent", &args[0])?, &strval(&args[1]))
+    )))
+}
+
+fn indent_str(spaces: i64, s: &str) -> String {
+    let pad = " ".repeat(spaces.max(0) as usize);
+    format!("{}{}", pad, s.replace('\n', &format!("\n{}", pad)))
+}
+
+/// `wrap LENGTH STRING` wraps the text at word boundaries to lines of `LENGTH` characters.
+/// Words longer than `LENGTH` are not split.
+pub fn wrap(args: &[Value]) -> Result<Value, String> {
+    check_args("wrap", args, 2)?;
+    let length = int_arg("wrap", &args[0])?.max(1) as usize;
+    let s: Vec<char> = strval(&args[1]).chars().collect();
+    let mut wrapped = String::new();
+    let mut offset = 0;
+    while s.len() - offset > length {
+        if s[offset] == ' ' {
+            offset += 1;
+            continue;
+        }
+        let end = length + offset + 1;
+        let at = match s[offset..end].iter().rposition(|&c| c == ' ') {
+            Some(i) => Some(offset + i),
+            None => s[end - 1..]
+                .iter()
+                .position(|&c| c == ' ')
+                .map(|i| end - 1 + i),
+        };
+        match at {
+            Some(at) => {
+                wrapped.extend(&s[offset..at]);
+                wrapped.push('\n');
+                offset = at + 1;
+            }
+            None => break,
+        }
+    }
+    wrapped.extend(&s[offset..]);
+    Ok(Value::from(wrapped))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn call(f: Func, args: &[Value]) -> String {
+        f(args).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(call(upper, &["foo".into()]), "FOO");
+        assert_eq!(call(lower, &["FOO".into()]), "foo");
+        assert_eq!(
+            call(title, &["hello wORLD_x foo-bar".into()]),
+            "Hello WORLD_x Foo-Bar"
+        );
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(call(trim, &[" foo\n".into()]), "foo");
+        assert_eq!(call(trim_prefix, &["-".into(), "-foo-".into()]), "foo-");
+        assert_eq!(call(trim_suffix, &["-".into(), "-foo-".into()]), "-foo");
+        assert_eq!(call(trim_suffix, &["x".into(), "foo".into()]), "foo");
+    }
+
+    #[test]
+    fn test_search() {
+        let args = ["oo".into(), "foo".into()];
+        assert_eq!(call(contains, &args), "true");
+        assert_eq!(call(has_prefix, &args), "false");
+        let args = ["a".into(), "b".into(), "banana".into()];
+        assert_eq!(call(replace, &args), "bbnbnb");
+    }
+
+    #[test]
+    fn test_split_join() {
+        let m = split(&["$".into(), "a$b".into()]).unwrap();
+        if let Value::Map(ref m) = m {
+            assert_eq!(m["_0"], Value::from("a"));
+            assert_eq!(m["_1"], Value::from("b"));
+        } else {
+            panic!("split should return a map");
+        }
+        let l = split_list(&[",".into(), "a,b,c".into()]).unwrap();
+        assert_eq!(l, Value::from(vec!["a", "b", "c"]));
+        assert_eq!(call(join, &["-".into(), l]), "a-b-c");
+        assert_eq!(call(join, &["-".into(), vec![1, 2].into()]), "1-2");
+    }
+
+    #[test]
+    fn test_substr_trunc() {
+        let s = Value::from("hällo");
+        assert_eq!(call(substr, &[1.into(), 3.into(), s.clone()]), "äl");
+        assert_eq!(call(substr, &[(-1).into(), 2.into(), s.clone()]), "hä");
+        assert_eq!(call(substr, &[3.into(), 99.into(), s.clone()]), "lo");
+        assert_eq!(call(trunc, &[2.into(), s.clone()]), "hä");
+        assert_eq!(call(trunc, &[(-2).into(), s.clone()]), "lo");
+        assert_eq!(call(abbrev, &[4.into(), s.clone()]), "h...");
+        assert_eq!(call(abbrev, &[5.into(), s]), "hällo");
+        assert_eq!(call(repeat, &[3.into(), "ab".into()]), "ababab");
+        assert!(repeat(&[(-1).into(), "ab".into()]).is_err());
+    }
+
+    #[test]
+    fn test_quote() {
+        let args = ["foo".into(), Value::Nil, 1.into(), "\"".into()];
+        assert_eq!(call(quote, &args), r#""foo" "1" "\"""#);
+        assert_eq!(call(squote, &args), r#"'foo' '1' '"'"#);
+    }
+
+    #[test]
+    fn test_indent_wrap() {
+        assert_eq!(call(indent, &[2.into(), "a\nb".into()]), "  a\n  b");
+        assert_eq!(call(nindent, &[1.into(), "a".into()]), "\n a");
+        let s = Value::from("the quick brown fox");
+        assert_eq!(call(wrap, &[10.into(), s]), "the quick\nbrown fox");
+        let s = Value::from("a verylongword b");
+        assert_eq!(call(wrap, &[3.into(), s]), "a\nverylongword\nb");
+    }
+
+    #[test]
+    fn test_arguments() {
+        assert!(upper(&[]).is_err());
+        assert!(substr(&["x".into(), 1.into(), "foo".into()]).is_err());
+    }
+}
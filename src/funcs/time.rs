@@ -0,0 +1,288 @@
+//! Date and time functions compatible with
+//! [Sprig](https://masterminds.github.io/sprig/date.html).
+//!
+//! `Value` has no time type, so times are represented as
+//! [RFC 3339](https://tools.ietf.org/html/rfc3339) strings with up to nanosecond precision,
+//! e.g. `2006-01-02T15:04:05.999999999-07:00`. Functions expecting a time also accept such
+//! strings from the context as well as integers, which are seconds since the Unix epoch.
+//!
+//! Formats are golang's reference layouts like `"2006-01-02"`, see
+//! [time.Layout](https://golang.org/pkg/time/#pkg-constants). There is no local time zone:
+//! `now` and Unix timestamps are UTC and times are formatted in their own offset. Use
+//! `dateInZone` to format a time in a named zone.
+//!
+//! `now` uses the system clock unless a clock was set with
+//! [`Template::set_clock`](../../struct.Template.html#method.set_clock).
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_time_funcs();
+//! tmpl.parse(r#"{{ toDate "2006-01-02" "2017-12-31" | date "Jan 2, 2006" }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "Dec 31, 2017");
+//! ```
+extern crate chrono;
+extern crate chrono_tz;
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use gtmpl_value::{Func, Value};
+
+use self::chrono::{DateTime, FixedOffset, SecondsFormat, TimeZone, Utc};
+use self::chrono_tz::{OffsetName, Tz};
+use crate::funcs::{check_args, with_state};
+use crate::layout;
+use crate::utils::{strval, to_int};
+
+/// All date and time functions of this module.
+pub static TIME: &[(&str, Func)] = &[
+    ("now", now as Func),
+    ("date", date as Func),
+    ("dateInZone", date_in_zone as Func),
+    ("unixEpoch", unix_epoch as Func),
+    ("duration", duration as Func),
+    ("ago", ago as Func),
+    ("toDate", to_date as Func),
+];
+
+/// A clock replacing the system clock.
+pub(crate) type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+fn current_time() -> DateTime<FixedOffset> {
+    let now = with_state(|s| s.and_then(|s| s.clock.as_ref().map(|clock| clock())))
+        .unwrap_or_else(SystemTime::now);
+    DateTime::<Utc>::from(now).fixed_offset()
+}
+
+fn to_time(name: &str, val: &Value) -> Result<DateTime<FixedOffset>, String> {
+    match *val {
+        Value::String(ref s) => DateTime::parse_from_rfc3339(s)
+            .map_err(|e| format!("{} requires a time, {:?} is invalid: {}", name, s, e)),
+        Value::Number(_) => to_int(val)
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .map(|t| t.fixed_offset())
+            .ok_or_else(|| format!("{} requires a time, {} is out of range", name, val)),
+        _ => Err(format!("{} requires a time, got {}", name, val)),
+    }
+}
+
+fn from_time<T: TimeZone>(t: &DateTime<T>) -> Value {
+    Value::from(
+        t.fixed_offset()
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    )
+}
+
+fn zone_name(t: &DateTime<FixedOffset>) -> &'static str {
+    if t.offset().local_minus_utc() == 0 {
+        "UTC"
+    } else {
+        ""
+    }
+}
+
+/// Formats a duration in nanoseconds like golang's `time.Duration`, e.g. `1h2m0.5s`.
+fn format_duration(d: i128) -> String {
+    fn frac(v: i128, prec: u32) -> (String, i128) {
+        let scale = 10i128.pow(prec);
+        let digits = format!("{:0width$}", v % scale, width = prec as usize);
+        let digits = digits.trim_end_matches('0');
+        let frac = if digits.is_empty() {
+            String::new()
+        } else {
+            format!(".{}", digits)
+        };
+        (frac, v / scale)
+    }
+    if d == 0 {
+        return String::from("0s");
+    }
+    let u = d.abs();
+    let sign = if d < 0 { "-" } else { "" };
+    if u < NANOS_PER_SEC {
+        let (prec, unit) = match u {
+            _ if u < 1_000 => (0, "ns"),
+            _ if u < 1_000_000 => (3, "µs"),
+            _ => (6, "ms"),
+        };
+        let (frac, int) = frac(u, prec);
+        return format!("{}{}{}{}", sign, int, frac, unit);
+    }
+    let (frac, secs) = frac(u, 9);
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match (h, m) {
+        (0, 0) => format!("{}{}{}s", sign, s, frac),
+        (0, _) => format!("{}{}m{}{}s", sign, m, s, frac),
+        _ => format!("{}{}h{}m{}{}s", sign, h, m, s, frac),
+    }
+}
+
+/// Rounds nanoseconds to the nearest second, halfway values away from zero.
+fn round_to_second(d: i128) -> i128 {
+    let r = d % NANOS_PER_SEC;
+    let r = if r.abs() * 2 < NANOS_PER_SEC {
+        -r
+    } else if d < 0 {
+        -NANOS_PER_SEC - r
+    } else {
+        NANOS_PER_SEC - r
+    };
+    d + r
+}
+
+fn nanos(t: &DateTime<FixedOffset>) -> i128 {
+    i128::from(t.timestamp()) * NANOS_PER_SEC + i128::from(t.timestamp_subsec_nanos())
+}
+
+/// Returns the current time.
+pub fn now(args: &[Value]) -> Result<Value, String> {
+    check_args("now", args, 0)?;
+    Ok(from_time(&current_time()))
+}
+
+/// `date FORMAT TIME` formats the time with a golang layout.
+pub fn date(args: &[Value]) -> Result<Value, String> {
+    check_args("date", args, 2)?;
+    let t = to_time("date", &args[1])?;
+    Ok(Value::from(layout::format(
+        &t,
+        zone_name(&t),
+        &strval(&args[0]),
+    )))
+}
+
+/// `dateInZone FORMAT TIME ZONE` formats the time in a zone from the IANA time zone database
+/// like `Europe/Berlin`.
+pub fn date_in_zone(args: &[Value]) -> Result<Value, String> {
+    check_args("dateInZone", args, 3)?;
+    let t = to_time("dateInZone", &args[1])?;
+    let zone = match strval(&args[2]).as_str() {
+        "" | "Local" => Tz::UTC,
+        zone => zone
+            .parse::<Tz>()
+            .map_err(|_| format!("dateInZone: unknown time zone {}", zone))?,
+    };
+    let t = t.with_timezone(&zone);
+    let name = t.offset().abbreviation().unwrap_or("").to_owned();
+    Ok(Value::from(layout::format(
+        &t.fixed_offset(),
+        &name,
+        &strval(&args[0]),
+    )))
+}
+
+/// Returns the seconds since the Unix epoch of a time as string.
+pub fn unix_epoch(args: &[Value]) -> Result<Value, String> {
+    check_args("unixEpoch", args, 1)?;
+    let t = to_time("unixEpoch", &args[0])?;
+    Ok(Value::from(t.timestamp().to_string()))
+}
+
+/// Formats a number of seconds as duration like `1h2m3s`.
+pub fn duration(args: &[Value]) -> Result<Value, String> {
+    check_args("duration", args, 1)?;
+    let secs =
+        to_int(&args[0]).ok_or_else(|| format!("duration requires seconds, got {}", args[0]))?;
+    Ok(Value::from(format_duration(
+        i128::from(secs) * NANOS_PER_SEC,
+    )))
+}
+
+/// Returns the time elapsed since a time, rounded to seconds.
+pub fn ago(args: &[Value]) -> Result<Value, String> {
+    check_args("ago", args, 1)?;
+    let t = to_time("ago", &args[0])?;
+    let d = nanos(&current_time()) - nanos(&t);
+    Ok(Value::from(format_duration(round_to_second(d))))
+}
+
+/// `toDate FORMAT STRING` parses a string with a golang layout into a time.
+pub fn to_date(args: &[Value]) -> Result<Value, String> {
+    check_args("toDate", args, 2)?;
+    let t = layout::parse(&strval(&args[0]), &strval(&args[1]))?;
+    Ok(from_time(&t))
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::funcs::{enter, FuncState};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_date() {
+        let t = Value::from("2017-12-31T23:59:58.5-01:30");
+        assert_eq!(
+            date(&["2006-01-02 15:04:05.0 MST".into(), t.clone()]),
+            Ok(Value::from("2017-12-31 23:59:58.5 -0130"))
+        );
+        assert_eq!(
+            date_in_zone(&["Jan 2 15:04 MST".into(), t.clone(), "Europe/Berlin".into()]),
+            Ok(Value::from("Jan 1 02:29 CET"))
+        );
+        assert!(date_in_zone(&["15:04".into(), t.clone(), "Mars/Olympus".into()]).is_err());
+        assert_eq!(
+            date(&["02.01.2006 15:04 MST".into(), 0.into()]),
+            Ok(Value::from("01.01.1970 00:00 UTC"))
+        );
+        assert_eq!(
+            date(&["2006年01月02日".into(), 0.into()]),
+            Ok(Value::from("1970年01月01日"))
+        );
+        assert_eq!(unix_epoch(&[t]), Ok(Value::from("1514770198")));
+        assert!(date(&["2006".into(), "yesterday".into()]).is_err());
+    }
+
+    #[test]
+    fn test_to_date() {
+        assert_eq!(
+            to_date(&["2006-01-02".into(), "2017-12-31".into()]),
+            Ok(Value::from("2017-12-31T00:00:00Z"))
+        );
+        assert_eq!(
+            to_date(&[
+                "02 Jan 06 15:04 -0700".into(),
+                "05 Feb 21 10:30 +0100".into()
+            ]),
+            Ok(Value::from("2021-02-05T10:30:00+01:00"))
+        );
+        assert!(to_date(&["2006-01-02".into(), "2017-12-32".into()]).is_err());
+        assert_eq!(
+            to_date(&["2006年01月02日".into(), "2017年12月31日".into()]),
+            Ok(Value::from("2017-12-31T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(&[0.into()]), Ok(Value::from("0s")));
+        assert_eq!(duration(&[95.into()]), Ok(Value::from("1m35s")));
+        assert_eq!(duration(&["3600".into()]), Ok(Value::from("1h0m0s")));
+        assert_eq!(duration(&[(-7201).into()]), Ok(Value::from("-2h0m1s")));
+        assert_eq!(format_duration(1_500), "1.5µs");
+        assert_eq!(format_duration(2_500_000_001), "2.500000001s");
+        assert_eq!(round_to_second(1_500_000_000), 2_000_000_000);
+        assert_eq!(round_to_second(-1_499_999_999), -1_000_000_000);
+    }
+
+    #[test]
+    fn test_clock() {
+        let state = FuncState {
+            clock: Some(Arc::new(|| UNIX_EPOCH + Duration::from_secs(86_400))),
+            ..Default::default()
+        };
+        let _guard = enter(&state);
+        assert_eq!(now(&[]), Ok(Value::from("1970-01-02T00:00:00Z")));
+        assert_eq!(ago(&[3_600.into()]), Ok(Value::from("23h0m0s")));
+        assert_eq!(
+            ago(&["1970-01-01T23:59:59.5Z".into()]),
+            Ok(Value::from("1s"))
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! YAML functions compatible with [Helm](https://helm.sh/docs/chart_template_guide/function_list/#type-conversion-functions).
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.add_yaml_funcs();
+//! tmpl.parse(r#"{{ $v := fromYaml "b: 2\na: [x]" }}{{ toYaml $v }}"#).unwrap();
+//! let output = tmpl.render(&Context::empty());
+//! assert_eq!(&output.unwrap(), "a:\n- x\nb: 2");
+//! ```
+extern crate serde_yaml;
+
+use gtmpl_value::{Func, Value};
+
+use crate::funcs::check_args;
+use crate::utils::strval;
+use crate::yaml::{value_to_yaml, yaml_to_value};
+
+/// All YAML functions of this module.
+pub static YAML: &[(&str, Func)] = &[
+    ("toYaml", to_yaml as Func),
+    ("fromYaml", from_yaml as Func),
+    ("fromYamlArray", from_yaml_array as Func),
+];
+
+/// Encodes its argument as YAML. Mapping keys are sorted and the trailing new line is
+/// removed.
+pub fn to_yaml(args: &[Value]) -> Result<Value, String> {
+    check_args("toYaml", args, 1)?;
+    let yaml = value_to_yaml(&args[0])?;
+    let s = serde_yaml::to_string(&yaml).map_err(|e| e.to_string())?;
+    Ok(Value::from(s.trim_end_matches('\n')))
+}
+
+fn parse(name: &str, val: &Value) -> Result<serde_yaml::Value, String> {
+    serde_yaml::from_str(&strval(val)).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// Decodes a YAML mapping into a map.
+pub fn from_yaml(args: &[Value]) -> Result<Value, String> {
+    check_args("fromYaml", args, 1)?;
+    match parse("fromYaml", &args[0])? {
+        yaml @ serde_yaml::Value::Mapping(_) => Ok(yaml_to_value(yaml)),
+        _ => Err(String::from("fromYaml requires a YAML mapping")),
+    }
+}
+
+/// Decodes a YAML sequence into a list.
+pub fn from_yaml_array(args: &[Value]) -> Result<Value, String> {
+    check_args("fromYamlArray", args, 1)?;
+    match parse("fromYamlArray", &args[0])? {
+        yaml @ serde_yaml::Value::Sequence(_) => Ok(yaml_to_value(yaml)),
+        _ => Err(String::from("fromYamlArray requires a YAML sequence")),
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_to_yaml() {
+        let mut m = HashMap::new();
+        m.insert("b".to_owned(), Value::from(vec!["x", "y"]));
+        m.insert("a".to_owned(), Value::from("true"));
+        assert_eq!(
+            to_yaml(&[Value::Map(m)]),
+            Ok(Value::from("a: 'true'\nb:\n- x\n- y"))
+        );
+        assert_eq!(to_yaml(&[Value::Nil]), Ok(Value::from("null")));
+        assert_eq!(
+            to_yaml(&[Value::from(vec![1e300, -1e300])]),
+            Ok(Value::from("- 1e300\n- -1e300"))
+        );
+    }
+
+    #[test]
+    fn test_from_yaml() {
+        let v = from_yaml(&["a:\n  - 1\n  - true".into()]).unwrap();
+        let mut m = HashMap::new();
+        m.insert("a".to_owned(), Value::Array(vec![1.into(), true.into()]));
+        assert_eq!(v, Value::Map(m));
+        assert!(from_yaml(&["- 1".into()]).is_err());
+        assert_eq!(
+            from_yaml_array(&["[1, 2]".into()]),
+            Ok(Value::from(vec![1, 2]))
+        );
+        assert!(from_yaml_array(&["a: 1".into()]).is_err());
+    }
+}
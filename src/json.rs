@@ -0,0 +1,145 @@
+//! Conversions between `serde_json::Value` and `gtmpl::Value`.
+//!
+//! JSON objects become `Value::Map`, `null` becomes `Value::Nil` and numbers keep their
+//! integer or float representation.
+//!
+//! ## Example
+//! ```rust
+//! extern crate gtmpl;
+//! #[macro_use]
+//! extern crate serde_json;
+//!
+//! use gtmpl::{Context, Template};
+//!
+//! fn main() {
+//!     let mut tmpl = Template::default();
+//!     tmpl.parse("{{ .name }} {{ index .ids 1 }}").unwrap();
+//!     let ctx: Context = json!({"name": "foo", "ids": [1, 18446744073709551615u64]}).into();
+//!     let output = tmpl.render(&ctx);
+//!     assert_eq!(&output.unwrap(), "foo 18446744073709551615");
+//! }
+//! ```
+extern crate serde_json;
+
+use std::collections::HashMap;
+
+use gtmpl_value::Value;
+
+use self::serde_json::{Map, Number, Value as Json};
+use crate::exec::Context;
+use crate::utils::{to_num, Num};
+
+/// Converts a JSON value into a template value.
+pub fn json_to_value(json: Json) -> Value {
+    match json {
+        Json::Null => Value::Nil,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else {
+                Value::from(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        Json::String(s) => Value::String(s),
+        Json::Array(a) => Value::Array(a.into_iter().map(json_to_value).collect()),
+        Json::Object(o) => Value::Map(
+            o.into_iter()
+                .map(|(k, v)| (k, json_to_value(v)))
+                .collect::<HashMap<String, Value>>(),
+        ),
+    }
+}
+
+/// Converts a template value into a JSON value. Functions and non finite floats can not be
+/// represented in JSON and fail.
+pub fn value_to_json(val: &Value) -> Result<Json, String> {
+    Ok(match *val {
+        Value::NoValue | Value::Nil => Json::Null,
+        Value::Bool(b) => Json::Bool(b),
+        Value::String(ref s) => Json::String(s.clone()),
+        Value::Number(ref n) => match to_num(n) {
+            Num::U(u) => Json::Number(Number::from(u)),
+            Num::I(i) => Json::Number(Number::from(i)),
+            Num::F(f) => Json::Number(
+                Number::from_f64(f).ok_or_else(|| format!("unsupported value in JSON: {}", f))?,
+            ),
+        },
+        Value::Array(ref a) => Json::Array(
+            a.iter()
+                .map(value_to_json)
+                .collect::<Result<Vec<_>, String>>()?,
+        ),
+        Value::Object(ref o) | Value::Map(ref o) => Json::Object(
+            o.iter()
+                .map(|(k, v)| value_to_json(v).map(|v| (k.clone(), v)))
+                .collect::<Result<Map<String, Json>, String>>()?,
+        ),
+        Value::Function(_) => return Err(String::from("unsupported type in JSON: func")),
+    })
+}
+
+impl From<Json> for Context {
+    fn from(json: Json) -> Context {
+        Context::from_value(json_to_value(json))
+    }
+}
+
+impl Context {
+    /// Converts the context back into a JSON value.
+    pub fn to_json(&self) -> Result<Json, String> {
+        value_to_json(&self.dot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_json_to_value() {
+        let json: Json =
+            serde_json::from_str(r#"{"a": null, "b": [true, "x"], "c": -1, "d": 1.5}"#).unwrap();
+        let val = json_to_value(json);
+        let mut m = HashMap::new();
+        m.insert("a".to_owned(), Value::Nil);
+        m.insert("b".to_owned(), Value::Array(vec![true.into(), "x".into()]));
+        m.insert("c".to_owned(), (-1).into());
+        m.insert("d".to_owned(), 1.5.into());
+        assert_eq!(val, Value::Map(m));
+    }
+
+    #[test]
+    fn test_precision() {
+        let json: Json =
+            serde_json::from_str("[18446744073709551615, -9223372036854775808]").unwrap();
+        let val = json_to_value(json.clone());
+        assert_eq!(
+            val,
+            Value::from(vec![u64::MAX.into(), Value::from(i64::MIN)])
+        );
+        assert_eq!(value_to_json(&val), Ok(json));
+
+        let json: Json = serde_json::from_str("[1e300, -1e30, 18446744073709551616.0]").unwrap();
+        let val = json_to_value(json.clone());
+        assert_eq!(value_to_json(&val), Ok(json));
+        assert_eq!(
+            serde_json::to_string(&value_to_json(&val).unwrap()).unwrap(),
+            "[1e+300,-1e+30,1.8446744073709552e+19]"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let json: Json = serde_json::from_str(r#"{"a": {"b": [1, 2.5, "c", null]}}"#).unwrap();
+        let ctx: Context = json.clone().into();
+        assert_eq!(ctx.to_json(), Ok(json));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(value_to_json(&Value::from(f64::NAN)).is_err());
+    }
+}
@@ -0,0 +1,885 @@
+//! Formatting and parsing of times with golang's reference layouts.
+//!
+//! A layout shows how the reference time `Mon Jan 2 15:04:05 MST 2006` would be
+//! represented, see [time.Layout](https://golang.org/pkg/time/#pkg-constants).
+extern crate chrono;
+
+use self::chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone, Timelike};
+
+use crate::utils::quote_str;
+
+static LONG_DAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+static SHORT_DAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+static LONG_MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+static SHORT_MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Std {
+    LongMonth,
+    Month,
+    NumMonth,
+    ZeroMonth,
+    LongWeekDay,
+    WeekDay,
+    Day,
+    UnderDay,
+    ZeroDay,
+    UnderYearDay,
+    ZeroYearDay,
+    Hour,
+    Hour12,
+    ZeroHour12,
+    Minute,
+    ZeroMinute,
+    Second,
+    ZeroSecond,
+    LongYear,
+    Year,
+    PM,
+    Pm,
+    Tz,
+    Iso8601Tz,
+    Iso8601SecondsTz,
+    Iso8601ShortTz,
+    Iso8601ColonTz,
+    Iso8601ColonSecondsTz,
+    NumTz,
+    NumSecondsTz,
+    NumShortTz,
+    NumColonTz,
+    NumColonSecondsTz,
+    /// Fixed number of fractional second digits and the separator.
+    FracSecond0(usize, u8),
+    /// Fractional second digits with trailing zeros trimmed and the separator.
+    FracSecond9(usize, u8),
+}
+
+static STD_0X: [Std; 6] = [
+    Std::ZeroMonth,
+    Std::ZeroDay,
+    Std::ZeroHour12,
+    Std::ZeroMinute,
+    Std::ZeroSecond,
+    Std::Year,
+];
+
+static TZ_CHUNKS: [(&str, Std, Std); 5] = [
+    ("070000", Std::NumSecondsTz, Std::Iso8601SecondsTz),
+    (
+        "07:00:00",
+        Std::NumColonSecondsTz,
+        Std::Iso8601ColonSecondsTz,
+    ),
+    ("0700", Std::NumTz, Std::Iso8601Tz),
+    ("07:00", Std::NumColonTz, Std::Iso8601ColonTz),
+    ("07", Std::NumShortTz, Std::Iso8601ShortTz),
+];
+
+fn starts_with_lower(s: &str) -> bool {
+    s.bytes()
+        .next()
+        .map(|c| c.is_ascii_lowercase())
+        .unwrap_or(false)
+}
+
+fn is_digit(s: &str, i: usize) -> bool {
+    s.as_bytes()
+        .get(i)
+        .map(|c| c.is_ascii_digit())
+        .unwrap_or(false)
+}
+
+/// Finds the first standard chunk in `layout` and returns the text before it, the chunk and
+/// the text after it.
+fn next_std_chunk(layout: &str) -> (&str, Option<(Std, &str, &str)>) {
+    let b = layout.as_bytes();
+    for i in 0..b.len() {
+        // Chunks are ASCII, continuation bytes of other characters can't start one.
+        if !layout.is_char_boundary(i) {
+            continue;
+        }
+        let rest = &layout[i..];
+        let found = match b[i] {
+            b'J' if rest.starts_with("Jan") => {
+                if rest.starts_with("January") {
+                    Some((Std::LongMonth, 7))
+                } else if !starts_with_lower(&rest[3..]) {
+                    Some((Std::Month, 3))
+                } else {
+                    None
+                }
+            }
+            b'M' if rest.starts_with("Mon") => {
+                if rest.starts_with("Monday") {
+                    Some((Std::LongWeekDay, 6))
+                } else if !starts_with_lower(&rest[3..]) {
+                    Some((Std::WeekDay, 3))
+                } else {
+                    None
+                }
+            }
+            b'M' if rest.starts_with("MST") => Some((Std::Tz, 3)),
+            b'0' if b.len() > i + 1 && (b'1'..=b'6').contains(&b[i + 1]) => {
+                Some((STD_0X[(b[i + 1] - b'1') as usize], 2))
+            }
+            b'0' if rest.starts_with("002") => Some((Std::ZeroYearDay, 3)),
+            b'1' if rest.starts_with("15") => Some((Std::Hour, 2)),
+            b'1' => Some((Std::NumMonth, 1)),
+            b'2' if rest.starts_with("2006") => Some((Std::LongYear, 4)),
+            b'2' => Some((Std::Day, 1)),
+            // _2006 is really a literal _, followed by the long year.
+            b'_' if rest.starts_with("_2006") => {
+                return (
+                    &layout[..i + 1],
+                    Some((Std::LongYear, "2006", &layout[i + 5..])),
+                );
+            }
+            b'_' if rest.starts_with("_2") => Some((Std::UnderDay, 2)),
+            b'_' if rest.starts_with("__2") => Some((Std::UnderYearDay, 3)),
+            b'3' => Some((Std::Hour12, 1)),
+            b'4' => Some((Std::Minute, 1)),
+            b'5' => Some((Std::Second, 1)),
+            b'P' if rest.starts_with("PM") => Some((Std::PM, 2)),
+            b'p' if rest.starts_with("pm") => Some((Std::Pm, 2)),
+            b'-' | b'Z' => TZ_CHUNKS
+                .iter()
+                .find(|&&(tz, _, _)| rest[1..].starts_with(tz))
+                .map(|&(tz, num, iso)| (if b[i] == b'-' { num } else { iso }, tz.len() + 1)),
+            b'.' | b',' if b.len() > i + 1 && (b[i + 1] == b'0' || b[i + 1] == b'9') => {
+                let ch = b[i + 1];
+                let mut j = i + 1;
+                while j < b.len() && b[j] == ch {
+                    j += 1;
+                }
+                // The digits must end here to be a fractional second.
+                if is_digit(layout, j) {
+                    None
+                } else if ch == b'0' {
+                    Some((Std::FracSecond0(j - i - 1, b[i]), j - i))
+                } else {
+                    Some((Std::FracSecond9(j - i - 1, b[i]), j - i))
+                }
+            }
+            _ => None,
+        };
+        if let Some((std, len)) = found {
+            return (
+                &layout[..i],
+                Some((std, &layout[i..i + len], &layout[i + len..])),
+            );
+        }
+    }
+    (layout, None)
+}
+
+/// Appends `x` padded with zeros to at least `width` digits.
+fn append_int(b: &mut String, x: i64, width: usize) {
+    if x < 0 {
+        b.push('-');
+    }
+    b.push_str(&format!("{:0width$}", x.unsigned_abs(), width = width));
+}
+
+fn append_nano(b: &mut String, nanos: u32, n: usize, trim: bool, sep: u8) {
+    let digits = format!("{:09}", nanos);
+    let mut digits = &digits[..n.min(9)];
+    if trim {
+        digits = digits.trim_end_matches('0');
+        if digits.is_empty() {
+            return;
+        }
+    }
+    b.push(sep as char);
+    b.push_str(digits);
+}
+
+/// Formats `t` according to `layout`. `zone` is the name of the time zone, an empty name is
+/// printed as numeric offset.
+pub fn format(t: &DateTime<FixedOffset>, zone: &str, layout: &str) -> String {
+    let mut b = String::with_capacity(layout.len() + 10);
+    let offset = i64::from(t.offset().local_minus_utc());
+    let year = i64::from(t.year());
+    let hour = i64::from(t.hour());
+    let mut layout = layout;
+    loop {
+        let (prefix, chunk) = next_std_chunk(layout);
+        b.push_str(prefix);
+        let (std, suffix) = match chunk {
+            Some((std, _, suffix)) => (std, suffix),
+            None => break,
+        };
+        layout = suffix;
+        match std {
+            Std::Year => append_int(&mut b, year % 100, 2),
+            Std::LongYear => append_int(&mut b, year, 4),
+            Std::Month => b.push_str(SHORT_MONTH_NAMES[t.month0() as usize]),
+            Std::LongMonth => b.push_str(LONG_MONTH_NAMES[t.month0() as usize]),
+            Std::NumMonth => append_int(&mut b, i64::from(t.month()), 0),
+            Std::ZeroMonth => append_int(&mut b, i64::from(t.month()), 2),
+            Std::WeekDay => {
+                b.push_str(SHORT_DAY_NAMES[t.weekday().num_days_from_sunday() as usize])
+            }
+            Std::LongWeekDay => {
+                b.push_str(LONG_DAY_NAMES[t.weekday().num_days_from_sunday() as usize])
+            }
+            Std::Day => append_int(&mut b, i64::from(t.day()), 0),
+            Std::UnderDay => {
+                if t.day() < 10 {
+                    b.push(' ');
+                }
+                append_int(&mut b, i64::from(t.day()), 0);
+            }
+            Std::ZeroDay => append_int(&mut b, i64::from(t.day()), 2),
+            Std::UnderYearDay => b.push_str(&format!("{:>3}", t.ordinal())),
+            Std::ZeroYearDay => append_int(&mut b, i64::from(t.ordinal()), 3),
+            Std::Hour => append_int(&mut b, hour, 2),
+            Std::Hour12 | Std::ZeroHour12 => {
+                let hr = if hour % 12 == 0 { 12 } else { hour % 12 };
+                append_int(&mut b, hr, if std == Std::Hour12 { 0 } else { 2 });
+            }
+            Std::Minute => append_int(&mut b, i64::from(t.minute()), 0),
+            Std::ZeroMinute => append_int(&mut b, i64::from(t.minute()), 2),
+            Std::Second => append_int(&mut b, i64::from(t.second()), 0),
+            Std::ZeroSecond => append_int(&mut b, i64::from(t.second()), 2),
+            Std::PM => b.push_str(if hour >= 12 { "PM" } else { "AM" }),
+            Std::Pm => b.push_str(if hour >= 12 { "pm" } else { "am" }),
+            Std::Iso8601Tz
+            | Std::Iso8601SecondsTz
+            | Std::Iso8601ShortTz
+            | Std::Iso8601ColonTz
+            | Std::Iso8601ColonSecondsTz
+                if offset == 0 =>
+            {
+                b.push('Z')
+            }
+            Std::Iso8601Tz
+            | Std::Iso8601SecondsTz
+            | Std::Iso8601ShortTz
+            | Std::Iso8601ColonTz
+            | Std::Iso8601ColonSecondsTz
+            | Std::NumTz
+            | Std::NumSecondsTz
+            | Std::NumShortTz
+            | Std::NumColonTz
+            | Std::NumColonSecondsTz => {
+                b.push(if offset < 0 { '-' } else { '+' });
+                let abs = offset.abs();
+                append_int(&mut b, abs / 3600, 2);
+                let colon = match std {
+                    Std::Iso8601ColonTz
+                    | Std::NumColonTz
+                    | Std::Iso8601ColonSecondsTz
+                    | Std::NumColonSecondsTz => {
+                        b.push(':');
+                        true
+                    }
+                    _ => false,
+                };
+                if std != Std::NumShortTz && std != Std::Iso8601ShortTz {
+                    append_int(&mut b, abs / 60 % 60, 2);
+                }
+                match std {
+                    Std::Iso8601SecondsTz
+                    | Std::NumSecondsTz
+                    | Std::Iso8601ColonSecondsTz
+                    | Std::NumColonSecondsTz => {
+                        if colon {
+                            b.push(':');
+                        }
+                        append_int(&mut b, abs % 60, 2);
+                    }
+                    _ => {}
+                }
+            }
+            Std::Tz => {
+                if !zone.is_empty() {
+                    b.push_str(zone);
+                } else {
+                    b.push(if offset < 0 { '-' } else { '+' });
+                    let minutes = offset.abs() / 60;
+                    append_int(&mut b, minutes / 60, 2);
+                    append_int(&mut b, minutes % 60, 2);
+                }
+            }
+            Std::FracSecond0(n, sep) => {
+                append_nano(&mut b, t.nanosecond() % 1_000_000_000, n, false, sep)
+            }
+            Std::FracSecond9(n, sep) => {
+                append_nano(&mut b, t.nanosecond() % 1_000_000_000, n, true, sep)
+            }
+        }
+    }
+    b
+}
+
+enum Bad {
+    Parse,
+    Range(&'static str),
+}
+
+#[derive(Default)]
+struct Fields<'a> {
+    year: i32,
+    month: Option<u32>,
+    day: Option<u32>,
+    yday: Option<u32>,
+    hour: u32,
+    min: u32,
+    sec: u32,
+    nsec: u32,
+    pm: Option<bool>,
+    utc: bool,
+    offset: Option<i32>,
+    zone: Option<&'a str>,
+}
+
+// Consumes `prefix` from `value`. A space in the prefix matches one or more spaces.
+fn skip<'a>(mut value: &'a str, mut prefix: &str) -> Result<&'a str, Bad> {
+    while let Some(c) = prefix.chars().next() {
+        if c == ' ' {
+            if !value.is_empty() && !value.starts_with(' ') {
+                return Err(Bad::Parse);
+            }
+            prefix = prefix.trim_start_matches(' ');
+            value = value.trim_start_matches(' ');
+            continue;
+        }
+        if !value.starts_with(c) {
+            return Err(Bad::Parse);
+        }
+        prefix = &prefix[c.len_utf8()..];
+        value = &value[c.len_utf8()..];
+    }
+    Ok(value)
+}
+
+// Parses one or two digits, exactly two if `fixed` is set.
+fn getnum(s: &str, fixed: bool) -> Result<(u32, &str), Bad> {
+    let b = s.as_bytes();
+    if !is_digit(s, 0) {
+        return Err(Bad::Parse);
+    }
+    if !is_digit(s, 1) {
+        if fixed {
+            return Err(Bad::Parse);
+        }
+        return Ok((u32::from(b[0] - b'0'), &s[1..]));
+    }
+    Ok((
+        u32::from(b[0] - b'0') * 10 + u32::from(b[1] - b'0'),
+        &s[2..],
+    ))
+}
+
+// Parses up to three digits, exactly three if `fixed` is set.
+fn getnum3(s: &str, fixed: bool) -> Result<(u32, &str), Bad> {
+    let n = s.bytes().take(3).take_while(|c| c.is_ascii_digit()).count();
+    if n == 0 || (fixed && n != 3) {
+        return Err(Bad::Parse);
+    }
+    Ok((s[..n].parse().map_err(|_| Bad::Parse)?, &s[n..]))
+}
+
+fn atoi(s: &str) -> Result<i32, Bad> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(Bad::Parse);
+    }
+    s.parse().map_err(|_| Bad::Parse)
+}
+
+// Case insensitive search of a prefix of `value` in `tab`.
+fn lookup<'a>(tab: &[&str], value: &'a str) -> Result<(usize, &'a str), Bad> {
+    for (i, name) in tab.iter().enumerate() {
+        if let Some(v) = value.get(..name.len()) {
+            if v.eq_ignore_ascii_case(name) {
+                return Ok((i, &value[name.len()..]));
+            }
+        }
+    }
+    Err(Bad::Parse)
+}
+
+fn comma_or_period(c: u8) -> bool {
+    c == b'.' || c == b','
+}
+
+// Parses the fraction of `value[..nbytes]` including the leading separator as nanoseconds.
+fn parse_nanoseconds(value: &str, nbytes: usize) -> Result<u32, Bad> {
+    if !comma_or_period(value.as_bytes()[0]) {
+        return Err(Bad::Parse);
+    }
+    let nbytes = nbytes.min(10);
+    let digits = value.get(1..nbytes).ok_or(Bad::Parse)?;
+    let ns = atoi(digits).map_err(|_| Bad::Range("fractional second"))?;
+    Ok(ns as u32 * 10u32.pow(10 - nbytes as u32))
+}
+
+// Returns the length of a time zone abbreviation at the start of `value`.
+fn parse_time_zone(value: &str) -> Option<usize> {
+    let b = value.as_bytes();
+    if b.len() < 3 {
+        return None;
+    }
+    if value.starts_with("ChST") || value.starts_with("MeST") {
+        return Some(4);
+    }
+    if let Some(offset) = value.strip_prefix("GMT") {
+        return Some(3 + parse_signed_offset(offset));
+    }
+    if b[0] == b'+' || b[0] == b'-' {
+        let n = parse_signed_offset(value);
+        return if n > 0 { Some(n) } else { None };
+    }
+    match b
+        .iter()
+        .take(6)
+        .take_while(|c| c.is_ascii_uppercase())
+        .count()
+    {
+        5 if b[4] == b'T' => Some(5),
+        4 if b[3] == b'T' || value.starts_with("WITA") => Some(4),
+        3 => Some(3),
+        _ => None,
+    }
+}
+
+// Returns the length of a signed hour offset like `+3` or `-11`, 0 if there is none.
+fn parse_signed_offset(value: &str) -> usize {
+    let b = value.as_bytes();
+    if b.is_empty() || (b[0] != b'+' && b[0] != b'-') {
+        return 0;
+    }
+    let n = b[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+    match value[1..1 + n].parse::<u32>() {
+        Ok(h) if h <= 23 => 1 + n,
+        _ => 0,
+    }
+}
+
+impl<'a> Fields<'a> {
+    fn parse_chunk(&mut self, std: Std, value: &'a str, layout: &str) -> Result<&'a str, Bad> {
+        let b = value.as_bytes();
+        let mut value = value;
+        match std {
+            Std::Year => {
+                let y = atoi(value.get(..2).ok_or(Bad::Parse)?)?;
+                self.year = if y >= 69 { y + 1900 } else { y + 2000 };
+                value = &value[2..];
+            }
+            Std::LongYear => {
+                self.year = atoi(value.get(..4).ok_or(Bad::Parse)?)?;
+                value = &value[4..];
+            }
+            Std::Month | Std::LongMonth => {
+                let tab = if std == Std::Month {
+                    &SHORT_MONTH_NAMES
+                } else {
+                    &LONG_MONTH_NAMES
+                };
+                let (m, v) = lookup(tab, value)?;
+                self.month = Some(m as u32 + 1);
+                value = v;
+            }
+            Std::NumMonth | Std::ZeroMonth => {
+                let (m, v) = getnum(value, std == Std::ZeroMonth)?;
+                if m == 0 || m > 12 {
+                    return Err(Bad::Range("month"));
+                }
+                self.month = Some(m);
+                value = v;
+            }
+            Std::WeekDay | Std::LongWeekDay => {
+                let tab = if std == Std::WeekDay {
+                    &SHORT_DAY_NAMES
+                } else {
+                    &LONG_DAY_NAMES
+                };
+                value = lookup(tab, value)?.1;
+            }
+            Std::Day | Std::UnderDay | Std::ZeroDay => {
+                if std == Std::UnderDay && value.starts_with(' ') {
+                    value = &value[1..];
+                }
+                let (d, v) = getnum(value, std == Std::ZeroDay)?;
+                self.day = Some(d);
+                value = v;
+            }
+            Std::UnderYearDay | Std::ZeroYearDay => {
+                for _ in 0..2 {
+                    if std == Std::UnderYearDay && value.starts_with(' ') {
+                        value = &value[1..];
+                    }
+                }
+                let (d, v) = getnum3(value, std == Std::ZeroYearDay)?;
+                if d == 0 || d > 366 {
+                    return Err(Bad::Range("day-of-year"));
+                }
+                self.yday = Some(d);
+                value = v;
+            }
+            Std::Hour => {
+                let (h, v) = getnum(value, false)?;
+                if h >= 24 {
+                    return Err(Bad::Range("hour"));
+                }
+                self.hour = h;
+                value = v;
+            }
+            Std::Hour12 | Std::ZeroHour12 => {
+                let (h, v) = getnum(value, std == Std::ZeroHour12)?;
+                if h > 12 {
+                    return Err(Bad::Range("hour"));
+                }
+                self.hour = h;
+                value = v;
+            }
+            Std::Minute | Std::ZeroMinute => {
+                let (m, v) = getnum(value, std == Std::ZeroMinute)?;
+                if m >= 60 {
+                    return Err(Bad::Range("minute"));
+                }
+                self.min = m;
+                value = v;
+            }
+            Std::Second | Std::ZeroSecond => {
+                let (s, v) = getnum(value, std == Std::ZeroSecond)?;
+                if s >= 60 {
+                    return Err(Bad::Range("second"));
+                }
+                self.sec = s;
+                value = v;
+                // A fractional second in the value but not in the layout is accepted.
+                let vb = value.as_bytes();
+                if vb.len() >= 2 && comma_or_period(vb[0]) && vb[1].is_ascii_digit() {
+                    match next_std_chunk(layout).1 {
+                        Some((Std::FracSecond0(..), _, _)) | Some((Std::FracSecond9(..), _, _)) => {
+                        }
+                        _ => {
+                            let n = 2 + vb[2..].iter().take_while(|c| c.is_ascii_digit()).count();
+                            self.nsec = parse_nanoseconds(value, n)?;
+                            value = &value[n..];
+                        }
+                    }
+                }
+            }
+            Std::PM | Std::Pm => {
+                let (am, pm) = if std == Std::PM {
+                    ("AM", "PM")
+                } else {
+                    ("am", "pm")
+                };
+                match value.get(..2) {
+                    Some(p) if p == pm => self.pm = Some(true),
+                    Some(a) if a == am => self.pm = Some(false),
+                    _ => return Err(Bad::Parse),
+                }
+                value = &value[2..];
+            }
+            Std::Iso8601Tz
+            | Std::Iso8601SecondsTz
+            | Std::Iso8601ShortTz
+            | Std::Iso8601ColonTz
+            | Std::Iso8601ColonSecondsTz
+                if value.starts_with('Z') =>
+            {
+                self.utc = true;
+                value = &value[1..];
+            }
+            Std::Iso8601Tz
+            | Std::Iso8601SecondsTz
+            | Std::Iso8601ShortTz
+            | Std::Iso8601ColonTz
+            | Std::Iso8601ColonSecondsTz
+            | Std::NumTz
+            | Std::NumSecondsTz
+            | Std::NumShortTz
+            | Std::NumColonTz
+            | Std::NumColonSecondsTz => {
+                let field = |from: usize| value.get(from..from + 2).ok_or(Bad::Parse);
+                let (hh, mm, ss, len) = match std {
+                    Std::Iso8601ShortTz | Std::NumShortTz => (field(1)?, "00", "00", 3),
+                    Std::Iso8601ColonTz | Std::NumColonTz => {
+                        if b.get(3) != Some(&b':') {
+                            return Err(Bad::Parse);
+                        }
+                        (field(1)?, field(4)?, "00", 6)
+                    }
+                    Std::Iso8601ColonSecondsTz | Std::NumColonSecondsTz => {
+                        if b.get(3) != Some(&b':') || b.get(6) != Some(&b':') {
+                            return Err(Bad::Parse);
+                        }
+                        (field(1)?, field(4)?, field(7)?, 9)
+                    }
+                    Std::Iso8601SecondsTz | Std::NumSecondsTz => {
+                        (field(1)?, field(3)?, field(5)?, 7)
+                    }
+                    _ => (field(1)?, field(3)?, "00", 5),
+                };
+                let offset = (atoi(hh)? * 60 + atoi(mm)?) * 60 + atoi(ss)?;
+                self.offset = Some(match b[0] {
+                    b'+' => offset,
+                    b'-' => -offset,
+                    _ => return Err(Bad::Parse),
+                });
+                value = &value[len..];
+            }
+            Std::Tz => {
+                if value.starts_with("UTC") {
+                    self.utc = true;
+                    value = &value[3..];
+                } else {
+                    let n = parse_time_zone(value).ok_or(Bad::Parse)?;
+                    self.zone = Some(&value[..n]);
+                    value = &value[n..];
+                }
+            }
+            Std::FracSecond0(n, _) => {
+                if value.len() < n + 1 {
+                    return Err(Bad::Parse);
+                }
+                self.nsec = parse_nanoseconds(value, n + 1)?;
+                value = &value[n + 1..];
+            }
+            Std::FracSecond9(..) => {
+                if b.len() < 2 || !comma_or_period(b[0]) || !b[1].is_ascii_digit() {
+                    // The fractional second is optional.
+                    return Ok(value);
+                }
+                let n = 1 + b[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+                self.nsec = parse_nanoseconds(value, n)?;
+                value = &value[n..];
+            }
+        }
+        Ok(value)
+    }
+
+    fn time(&self) -> Result<DateTime<FixedOffset>, Bad> {
+        let mut hour = self.hour;
+        match self.pm {
+            Some(true) if hour < 12 => hour += 12,
+            Some(false) if hour == 12 => hour = 0,
+            _ => {}
+        }
+        let date = match self.yday {
+            Some(yday) => {
+                let date =
+                    NaiveDate::from_yo_opt(self.year, yday).ok_or(Bad::Range("day-of-year"))?;
+                if self.month.map(|m| m != date.month()).unwrap_or(false) {
+                    return Err(Bad::Range("day-of-year does not match month"));
+                }
+                if self.day.map(|d| d != date.day()).unwrap_or(false) {
+                    return Err(Bad::Range("day-of-year does not match day"));
+                }
+                date
+            }
+            None => {
+                NaiveDate::from_ymd_opt(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1))
+                    .ok_or(Bad::Range("day"))?
+            }
+        };
+        let naive = date
+            .and_hms_nano_opt(hour, self.min, self.sec, self.nsec)
+            .ok_or(Bad::Parse)?;
+        let offset = if self.utc {
+            0
+        } else if let Some(offset) = self.offset {
+            offset
+        } else {
+            // Unknown zone names get a zero offset, except for GMT with an hour offset.
+            self.zone
+                .filter(|z| z.len() > 3 && z.starts_with("GMT"))
+                .and_then(|z| z[3..].parse::<i32>().ok())
+                .map(|h| h * 3600)
+                .unwrap_or(0)
+        };
+        let offset = FixedOffset::east_opt(offset).ok_or(Bad::Range("time zone offset"))?;
+        offset
+            .from_local_datetime(&naive)
+            .single()
+            .ok_or(Bad::Parse)
+    }
+}
+
+/// Parses `value` according to `layout`. Times without time zone information are UTC.
+pub fn parse(layout: &str, value: &str) -> Result<DateTime<FixedOffset>, String> {
+    let range_err = |what: &str| {
+        format!(
+            "parsing time {}: {} out of range",
+            quote_str(value, false),
+            what
+        )
+    };
+    let mut fields = Fields::default();
+    let (mut l, mut v) = (layout, value);
+    loop {
+        let (prefix, chunk) = next_std_chunk(l);
+        let rest = skip(v, prefix).map_err(|_| {
+            format!(
+                "parsing time {} as {}: cannot parse {} as {}",
+                quote_str(value, false),
+                quote_str(layout, false),
+                quote_str(v, false),
+                quote_str(prefix, false)
+            )
+        })?;
+        v = rest;
+        let (std, elem, suffix) = match chunk {
+            Some(chunk) => chunk,
+            None => break,
+        };
+        l = suffix;
+        v = match fields.parse_chunk(std, v, l) {
+            Ok(rest) => rest,
+            Err(Bad::Range(what)) => return Err(range_err(what)),
+            Err(Bad::Parse) => {
+                return Err(format!(
+                    "parsing time {} as {}: cannot parse {} as {}",
+                    quote_str(value, false),
+                    quote_str(layout, false),
+                    quote_str(v, false),
+                    quote_str(elem, false)
+                ))
+            }
+        };
+    }
+    if !v.is_empty() {
+        return Err(format!(
+            "parsing time {}: extra text: {}",
+            quote_str(value, false),
+            quote_str(v, false)
+        ));
+    }
+    fields.time().map_err(|e| match e {
+        Bad::Range(what) => range_err(what),
+        Bad::Parse => format!("parsing time {}: invalid time", quote_str(value, false)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn t(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    #[test]
+    fn test_format() {
+        let ref_time = t("2006-01-02T15:04:05.123400-07:00");
+        let tests = [
+            ("Mon Jan _2 15:04:05 2006", "Mon Jan  2 15:04:05 2006"),
+            (
+                "Monday, 02-Jan-06 15:04:05 MST",
+                "Monday, 02-Jan-06 15:04:05 MST",
+            ),
+            (
+                "2006-01-02T15:04:05.999999999Z07:00",
+                "2006-01-02T15:04:05.1234-07:00",
+            ),
+            ("3:04PM", "3:04PM"),
+            ("03:04:05.000 pm", "03:04:05.123 pm"),
+            ("January 2, 2006 -0700 Z07", "January 2, 2006 -0700 -07"),
+            ("__2 002 _2006", "  2 002 _2006"),
+            ("Month Jan", "Month Jan"),
+            ("15:04:05,00", "15:04:05,12"),
+            ("2006年01月02日 Mon", "2006年01月02日 Mon"),
+        ];
+        for &(layout, expected) in &tests {
+            assert_eq!(format(&ref_time, "MST", layout), expected, "{}", layout);
+        }
+        let utc = t("2020-11-30T00:00:00Z");
+        assert_eq!(
+            format(&utc, "", "02 Jan 06 15:04 -0700 MST Z07:00"),
+            "30 Nov 20 00:00 +0000 +0000 Z"
+        );
+        assert_eq!(format(&utc, "", "3PM .999"), "12AM ");
+    }
+
+    #[test]
+    fn test_parse() {
+        let tests = [
+            ("2006-01-02", "2017-12-31", "2017-12-31T00:00:00+00:00"),
+            (
+                "2006-01-02T15:04:05Z07:00",
+                "2017-12-31T23:59:58.5-01:30",
+                "2018-01-01T01:29:58.500+00:00",
+            ),
+            (
+                "Jan _2 15:04:05.000",
+                "Feb  3 04:05:06.007",
+                "0000-02-03T04:05:06.007+00:00",
+            ),
+            ("3:04pm MST", "3:04pm PDT", "0000-01-01T15:04:00+00:00"),
+            (
+                "Mon, 02 Jan 2006 15:04:05 -0700",
+                "Thu, 04 Feb 2010 21:00:57 +0800",
+                "2010-02-04T13:00:57+00:00",
+            ),
+            ("2006 002", "2020 060", "2020-02-29T00:00:00+00:00"),
+            (
+                "2006年01月02日",
+                "2017年12月31日",
+                "2017-12-31T00:00:00+00:00",
+            ),
+        ];
+        for &(layout, value, expected) in &tests {
+            let parsed = parse(layout, value).unwrap();
+            assert_eq!(parsed, t(expected), "{} {}", layout, value);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("2006-01-02", "2017-13-01"),
+            Err(String::from(
+                r#"parsing time "2017-13-01": month out of range"#
+            ))
+        );
+        assert_eq!(
+            parse("2006-01-02", "2017-02-30"),
+            Err(String::from(
+                r#"parsing time "2017-02-30": day out of range"#
+            ))
+        );
+        assert_eq!(
+            parse("2006-01-02", "2017/01/02"),
+            Err(String::from(
+                r#"parsing time "2017/01/02" as "2006-01-02": cannot parse "/01/02" as "-""#
+            ))
+        );
+        assert_eq!(
+            parse("2006", "2006 x"),
+            Err(String::from(r#"parsing time "2006 x": extra text: " x""#))
+        );
+        assert!(parse("2006-01-02", "2017年01-02").is_err());
+        assert!(parse("2006年01月", "2017-01").is_err());
+        assert!(parse("Jan 2006", "Ján 2006").is_err());
+    }
+}
@@ -109,6 +109,8 @@ struct LexerStateMachine {
     items_sender: Sender<Item>, // channel of scanned items
     paren_depth: usize,         // nesting depth of ( ) exprs
     line: usize,                // 1+number of newlines seen
+    left_delim: String,         // start of an action
+    right_delim: String,        // end of an action
 }
 
 #[derive(Debug)]
@@ -154,6 +156,11 @@ impl Iterator for Lexer {
 
 impl Lexer {
     pub fn new(input: String) -> Lexer {
+        Lexer::with_delims(input, LEFT_DELIM, RIGHT_DELIM)
+    }
+
+    /// Creates a lexer for actions between `left` and `right` instead of `{{` and `}}`.
+    pub fn with_delims(input: String, left: &str, right: &str) -> Lexer {
         let (tx, rx) = channel();
         let mut l = LexerStateMachine {
             input,
@@ -164,6 +171,8 @@ impl Lexer {
             items_sender: tx,
             paren_depth: 0,
             line: 1,
+            left_delim: left.to_owned(),
+            right_delim: right.to_owned(),
         };
         thread::spawn(move || l.run());
         Lexer {
@@ -288,11 +297,11 @@ impl LexerStateMachine {
 
     fn lex_text(&mut self) -> State {
         self.width = 0;
-        let x = self.input[self.pos..].find(&LEFT_DELIM);
+        let x = self.input[self.pos..].find(self.left_delim.as_str());
         match x {
             Some(x) => {
                 self.pos += x;
-                let ld = self.pos + LEFT_DELIM.len();
+                let ld = self.pos + self.left_delim.len();
                 let trim = if self.input[ld..].starts_with(LEFT_TRIM_MARKER) {
                     rtrim_len(&self.input[self.start..self.pos])
                 } else {
@@ -318,17 +327,18 @@ impl LexerStateMachine {
     }
 
     fn at_right_delim(&mut self) -> (bool, bool) {
-        if self.input[self.pos..].starts_with(&RIGHT_DELIM) {
+        if self.input[self.pos..].starts_with(self.right_delim.as_str()) {
             return (true, false);
         }
-        if self.input[self.pos..].starts_with(&format!("{}{}", RIGHT_TRIM_MARKER, RIGHT_DELIM)) {
+        if self.input[self.pos..].starts_with(&format!("{}{}", RIGHT_TRIM_MARKER, self.right_delim))
+        {
             return (true, true);
         }
         (false, false)
     }
 
     fn lex_left_delim(&mut self) -> State {
-        self.pos += LEFT_DELIM.len();
+        self.pos += self.left_delim.len();
         let trim = self.input[self.pos..].starts_with(LEFT_TRIM_MARKER);
         let after_marker = if trim { LEFT_TRIM_MARKER.len() } else { 0 };
         if self.input[(self.pos + after_marker)..].starts_with(LEFT_COMMENT) {
@@ -364,7 +374,7 @@ impl LexerStateMachine {
             self.pos += RIGHT_TRIM_MARKER.len();
         }
 
-        self.pos += RIGHT_DELIM.len();
+        self.pos += self.right_delim.len();
 
         if trim {
             self.pos += ltrim_len(&self.input[self.pos..]);
@@ -380,7 +390,7 @@ impl LexerStateMachine {
             self.pos += RIGHT_TRIM_MARKER.len();
             self.ignore();
         }
-        self.pos += RIGHT_DELIM.len();
+        self.pos += self.right_delim.len();
         self.emit(ItemType::ItemRightDelim);
         if trim {
             self.pos += ltrim_len(&self.input[self.pos..]);
@@ -513,7 +523,7 @@ impl LexerStateMachine {
                 match c {
                     '.' | ',' | '|' | ':' | ')' | '(' | ' ' | '\t' | '\r' | '\n' => true,
                     // this is what golang does to detect a delimiter
-                    _ => RIGHT_DELIM.starts_with(c),
+                    _ => self.right_delim.starts_with(c),
                 }
             }
             None => false,
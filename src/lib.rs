@@ -14,22 +14,47 @@ extern crate gtmpl_value;
 extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
+pub mod analyze;
+pub mod check;
 mod exec;
+mod files;
 #[doc(inlne)]
 pub mod funcs;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "time")]
+mod layout;
 mod lexer;
+pub mod lint;
+pub mod loader;
 mod node;
 mod parse;
+mod precompiled;
 mod print_verb;
 mod printf;
+pub mod registry;
 mod template;
 mod utils;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
 #[doc(inline)]
 pub use crate::template::Template;
 
 #[doc(inline)]
-pub use crate::exec::Context;
+pub use crate::exec::{Context, MissingKey, RenderError};
+
+#[doc(inline)]
+pub use crate::files::TemplateNaming;
+
+#[doc(inline)]
+pub use crate::lint::lint;
+
+#[doc(inline)]
+pub use crate::loader::Loader;
+
+#[doc(inline)]
+pub use crate::registry::TemplateRegistry;
 
 #[doc(inline)]
 pub use gtmpl_value::Func;
@@ -39,6 +64,28 @@ pub use gtmpl_value::from_value;
 
 pub use gtmpl_value::Value;
 
+/// Validates a template for `gtmpl_macros`. Returns the line, the column and the message of
+/// the first error.
+#[doc(hidden)]
+pub fn __validate(text: &str, funcs: &[&str]) -> Result<(), (usize, usize, String)> {
+    let names = funcs::BUILTINS
+        .iter()
+        .map(|&(name, _)| name)
+        .chain(funcs.iter().cloned())
+        .map(String::from)
+        .collect();
+    parse::parse_with_pos(String::from("gtmpl"), text.to_owned(), names)
+        .map(|_| ())
+        .map_err(|(e, pos)| {
+            let before = text.get(..pos).unwrap_or(text);
+            let line = before.matches('\n').count() + 1;
+            let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+            // Drop the `template: NAME:LINE:` prefix.
+            let msg = e.splitn(4, ':').nth(3).unwrap_or(&e).to_owned();
+            (line, col, msg)
+        })
+}
+
 /// Provides simple basic templating given just a template sting and context.
 ///
 /// ## Example
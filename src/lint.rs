@@ -0,0 +1,380 @@
+//! Finding mistakes in templates without executing them.
+//!
+//! [`lint`](fn.lint.html) checks all parsed templates for
+//!
+//! * `{{ template }}` and `include` calls of undefined templates,
+//! * variables which are declared but never used,
+//! * `if` and `with` conditions which are constant, leaving a branch unreachable,
+//! * `printf` format strings which don't match the number of arguments and
+//! * unknown functions.
+//!
+//! Variables starting with `$_` are never reported as unused.
+//! [`lint_deprecated`](fn.lint_deprecated.html) also reports calls of functions which are
+//! deprecated by the application.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::lint::Severity;
+//! use gtmpl::Template;
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.parse(r#"{{ $name := .name }}{{ printf "%s: %d" .key }}"#).unwrap();
+//! let diagnostics = gtmpl::lint(&tmpl);
+//! assert_eq!(diagnostics.len(), 2);
+//! assert_eq!(diagnostics[0].severity, Severity::Warning);
+//! assert_eq!(diagnostics[0].message, "variable $name is declared but not used");
+//! ```
+use std::fmt;
+
+use crate::node::*;
+use crate::printf::tokenize;
+use crate::template::Template;
+use crate::utils::is_true;
+
+/// How severe a [`Diagnostic`](struct.Diagnostic.html) is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The template fails when the code is executed.
+    Error,
+    /// The template is likely not doing what was intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found by [`lint`](fn.lint.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the template containing the problem.
+    pub template: String,
+    /// Byte offset of the problem in the text the template was parsed from.
+    pub pos: Pos,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.template, self.pos, self.severity, self.message
+        )
+    }
+}
+
+/// Checks all templates of `template`, see [`lint`](index.html). Diagnostics are ordered by
+/// template name and position.
+pub fn lint(template: &Template) -> Vec<Diagnostic> {
+    lint_deprecated(template, &[])
+}
+
+/// Like [`lint`](fn.lint.html), but also warns about calls of the functions in `deprecated`,
+/// given as pairs of a function and its replacement.
+///
+/// ## Example
+/// ```rust
+/// use gtmpl::lint::lint_deprecated;
+/// use gtmpl::{Template, Value};
+///
+/// let mut tmpl = Template::default();
+/// tmpl.add_func("fmtDate", |_| Ok(Value::Nil));
+/// tmpl.parse("{{ fmtDate . }}").unwrap();
+/// let diagnostics = lint_deprecated(&tmpl, &[("fmtDate", "date")]);
+/// assert_eq!(diagnostics[0].message, "function fmtDate is deprecated, use date");
+/// ```
+pub fn lint_deprecated(template: &Template, deprecated: &[(&str, &str)]) -> Vec<Diagnostic> {
+    let mut names: Vec<&String> = template.tree_set.keys().collect();
+    names.sort();
+    let mut linter = Linter {
+        template,
+        deprecated,
+        name: String::new(),
+        vars: vec![],
+        diagnostics: vec![],
+    };
+    for name in names {
+        if let Some(ref root) = template.tree_set[name].root {
+            linter.name = name.clone();
+            linter.walk(root);
+            linter.pop_vars(0);
+        }
+    }
+    linter
+        .diagnostics
+        .sort_by(|a, b| (&a.template, a.pos).cmp(&(&b.template, b.pos)));
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    template: &'a Template,
+    deprecated: &'a [(&'a str, &'a str)],
+    name: String,
+    // Declared variables with their position and whether they are used.
+    vars: Vec<(String, Pos, bool)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, severity: Severity, pos: Pos, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            template: self.name.clone(),
+            pos,
+            message,
+        });
+    }
+
+    fn pop_vars(&mut self, mark: usize) {
+        let unused: Vec<_> = self
+            .vars
+            .drain(mark..)
+            .filter(|(name, _, used)| !used && !name.starts_with("$_"))
+            .collect();
+        for (name, pos, _) in unused {
+            let message = format!("variable {} is declared but not used", name);
+            self.report(Severity::Warning, pos, message);
+        }
+    }
+
+    fn check_template(&mut self, name: &str, pos: Pos) {
+        if self.template.tree_set.contains_key(name) {
+            return;
+        }
+        match self.template.load_template(name) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                let message = format!("template {} is not defined", name);
+                self.report(Severity::Error, pos, message);
+            }
+            Err(e) => self.report(Severity::Error, pos, e),
+        }
+    }
+
+    fn walk(&mut self, node: &Nodes) {
+        match *node {
+            Nodes::List(ref list) => {
+                for n in &list.nodes {
+                    self.walk(n);
+                }
+            }
+            Nodes::Action(ref action) => self.pipe(&action.pipe),
+            Nodes::If(ref branch) | Nodes::With(ref branch) | Nodes::Range(ref branch) => {
+                let mark = self.vars.len();
+                self.pipe(&branch.pipe);
+                if *branch.typ() != NodeType::Range {
+                    self.check_condition(branch);
+                }
+                for n in &branch.list.nodes {
+                    self.walk(n);
+                }
+                if let Some(ref else_list) = branch.else_list {
+                    for n in &else_list.nodes {
+                        self.walk(n);
+                    }
+                }
+                self.pop_vars(mark);
+            }
+            Nodes::Template(ref template) => {
+                match template.name {
+                    PipeOrString::String(ref name) => self.check_template(name, template.pos()),
+                    PipeOrString::Pipe(ref pipe) => self.pipe(pipe),
+                }
+                if let Some(ref pipe) = template.pipe {
+                    self.pipe(pipe);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_condition(&mut self, branch: &BranchNode) {
+        if !branch.pipe.decl.is_empty() || branch.pipe.cmds.len() != 1 {
+            return;
+        }
+        let args = &branch.pipe.cmds[0].args;
+        let value = match args.first() {
+            Some(Nodes::Bool(ref n)) if args.len() == 1 => &n.value,
+            Some(Nodes::Number(ref n)) if args.len() == 1 => &n.value,
+            Some(Nodes::String(ref n)) if args.len() == 1 => &n.value,
+            _ => return,
+        };
+        if is_true(value) {
+            if let Some(ref else_list) = branch.else_list {
+                let message =
+                    String::from("else branch is unreachable, the condition is always true");
+                self.report(Severity::Warning, else_list.pos(), message);
+            }
+        } else {
+            let message = String::from("branch is unreachable, the condition is always false");
+            self.report(Severity::Warning, branch.list.pos(), message);
+        }
+    }
+
+    fn pipe(&mut self, pipe: &PipeNode) {
+        for (i, cmd) in pipe.cmds.iter().enumerate() {
+            self.command(cmd, i > 0);
+        }
+        for var in &pipe.decl {
+            self.vars.push((var.ident[0].clone(), var.pos(), false));
+        }
+    }
+
+    fn command(&mut self, cmd: &CommandNode, piped: bool) {
+        for arg in &cmd.args {
+            self.value(arg);
+        }
+        let func = match cmd.args.first() {
+            Some(Nodes::Identifier(ref f)) => f.ident.as_str(),
+            _ => return,
+        };
+        match (func, cmd.args.get(1)) {
+            ("printf", Some(Nodes::String(ref format))) => {
+                let args = cmd.args.len() - 2 + if piped { 1 } else { 0 };
+                self.check_printf(format, args);
+            }
+            ("include", Some(Nodes::String(ref name))) => {
+                self.check_template(&name.value.to_string(), name.pos());
+            }
+            _ => {}
+        }
+    }
+
+    fn check_printf(&mut self, format: &StringNode, args: usize) {
+        let format_str = format.value.to_string();
+        let verbs = match tokenize(&format_str) {
+            Ok(verbs) => verbs,
+            Err(e) => {
+                let message = format!("invalid printf format: {}", e);
+                return self.report(Severity::Warning, format.pos(), message);
+            }
+        };
+        let mut wanted = 0;
+        for verb in verbs {
+            let spec = &format_str[verb.start..verb.end];
+            // Explicit argument indexes are not checked.
+            if spec.contains('[') {
+                return;
+            }
+            wanted += 1 + spec.matches('*').count();
+        }
+        if wanted != args {
+            let message = format!(
+                "printf format {} reads {} arguments, but the call has {}",
+                format.quoted, wanted, args
+            );
+            self.report(Severity::Warning, format.pos(), message);
+        }
+    }
+
+    fn value(&mut self, node: &Nodes) {
+        match *node {
+            Nodes::Identifier(ref f) => {
+                if !self.template.funcs.contains_key(&f.ident) {
+                    let message = format!("function {} is not defined", f.ident);
+                    self.report(Severity::Error, f.pos(), message);
+                } else if let Some(&(_, new)) =
+                    self.deprecated.iter().find(|&&(old, _)| old == f.ident)
+                {
+                    let message = format!("function {} is deprecated, use {}", f.ident, new);
+                    self.report(Severity::Warning, f.pos(), message);
+                }
+            }
+            Nodes::Variable(ref var) => {
+                if let Some(v) = self.vars.iter_mut().rev().find(|v| v.0 == var.ident[0]) {
+                    v.2 = true;
+                }
+            }
+            Nodes::Chain(ref chain) => self.value(&chain.node),
+            Nodes::Pipe(ref pipe) => self.pipe(pipe),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use gtmpl_value::Value;
+
+    fn messages(text: &str) -> Vec<String> {
+        let mut t = Template::default();
+        t.parse(text).unwrap();
+        lint(&t).iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_templates() {
+        assert_eq!(
+            messages(r#"{{ define "a" }}{{ end }}{{ template "a" }}{{ template "b" . }}"#),
+            vec![":55: error: template b is not defined"]
+        );
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        assert_eq!(
+            messages("{{ $a := 1 }}{{ $b := 2 }}{{ $b }}{{ range $_, $v := . }}{{ end }}"),
+            vec![
+                ":3: warning: variable $a is declared but not used",
+                ":47: warning: variable $v is declared but not used"
+            ]
+        );
+        assert!(messages("{{ with $x := . }}{{ else }}{{ $x }}{{ end }}").is_empty());
+        assert!(messages("{{ $x := 1 }}{{ if . }}{{ $x }}{{ end }}").is_empty());
+    }
+
+    #[test]
+    fn test_constant_conditions() {
+        assert_eq!(
+            messages("{{ if 1 }}a{{ else }}b{{ end }}{{ with \"\" }}c{{ end }}{{ if . }}{{ else }}{{ end }}"),
+            vec![
+                ":21: warning: else branch is unreachable, the condition is always true",
+                ":44: warning: branch is unreachable, the condition is always false"
+            ]
+        );
+        assert!(messages("{{ if true }}a{{ end }}").is_empty());
+    }
+
+    #[test]
+    fn test_printf() {
+        assert!(messages(r#"{{ printf "%s %*d %%" 1 2 3 }}{{ 1 | printf "%d" }}"#).is_empty());
+        assert!(messages(r#"{{ printf "%[2]s %[1]s" 1 }}"#).is_empty());
+        assert_eq!(
+            messages(r#"{{ printf "%s %s" 1 }}{{ . | printf "%s" 1 }}{{ printf "%" }}"#),
+            vec![
+                r#":10: warning: printf format "%s %s" reads 2 arguments, but the call has 1"#,
+                r#":36: warning: printf format "%s" reads 1 arguments, but the call has 2"#,
+                ":55: warning: invalid printf format: unable to terminate format arg: %",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_funcs() {
+        let mut t = Template::default();
+        t.add_func("old", |_| Ok(Value::Nil));
+        t.add_func("gone", |_| Ok(Value::Nil));
+        t.parse("{{ old }}{{ gone }}").unwrap();
+        t.funcs.remove("gone");
+        let messages: Vec<String> = lint_deprecated(&t, &[("old", "new")])
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ":3: warning: function old is deprecated, use new",
+                ":12: error: function gone is not defined"
+            ]
+        );
+        assert_eq!(lint(&t).len(), 1);
+    }
+}
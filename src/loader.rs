@@ -0,0 +1,238 @@
+//! Loading templates on demand.
+//!
+//! A [`Loader`](trait.Loader.html) set with
+//! [`Template::set_loader`](../struct.Template.html#method.set_loader) is asked for templates
+//! which are not part of the template when they are executed by `{{ template }}` or
+//! `include`. Loaded templates are parsed once and cached, including the templates they
+//! define, so large libraries of partials are only parsed when they are used.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::loader::MapLoader;
+//! use gtmpl::{Context, Template};
+//!
+//! let mut partials = MapLoader::new();
+//! partials.insert("greeting", "Hello {{ . }}!");
+//! let mut tmpl = Template::default();
+//! tmpl.set_loader(partials);
+//! tmpl.parse(r#"{{ template "greeting" . }}"#).unwrap();
+//! let output = tmpl.render(&Context::from("World").unwrap());
+//! assert_eq!(&output.unwrap(), "Hello World!");
+//! ```
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::parse::Tree;
+use crate::template::Template;
+
+/// Source of templates which are loaded on demand.
+pub trait Loader: Send + Sync {
+    /// Returns the text of the template `name` or `None` if there is no such template.
+    fn load(&self, name: &str) -> Result<Option<String>, Error>;
+}
+
+/// Loads templates from files below a root directory. Template names are paths relative
+/// to the root with `/` as separator like `partials/header.tmpl`.
+pub struct FsLoader {
+    root: PathBuf,
+}
+
+impl FsLoader {
+    /// Creates a loader for the files below `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> FsLoader {
+        FsLoader { root: root.into() }
+    }
+}
+
+impl Loader for FsLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, Error> {
+        let path = Path::new(name);
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a relative path below the root", name),
+            ));
+        }
+        match fs::read_to_string(self.root.join(path)) {
+            Ok(text) => Ok(Some(text)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Loads templates from a map of names to template texts.
+#[derive(Default)]
+pub struct MapLoader {
+    templates: HashMap<String, String>,
+}
+
+impl MapLoader {
+    /// Creates an empty loader.
+    pub fn new() -> MapLoader {
+        MapLoader::default()
+    }
+
+    /// Adds the template `name`.
+    pub fn insert<N: Into<String>, T: Into<String>>(&mut self, name: N, text: T) {
+        self.templates.insert(name.into(), text.into());
+    }
+}
+
+impl From<HashMap<String, String>> for MapLoader {
+    fn from(templates: HashMap<String, String>) -> MapLoader {
+        MapLoader { templates }
+    }
+}
+
+impl Loader for MapLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, Error> {
+        Ok(self.templates.get(name).cloned())
+    }
+}
+
+/// Loads templates embedded into the binary, e.g. with `include_str!`.
+///
+/// ## Example
+/// ```rust
+/// use gtmpl::loader::EmbeddedLoader;
+/// use gtmpl::{Context, Template};
+///
+/// // Usually `include_str!("partials/header.tmpl")`.
+/// static PARTIALS: &[(&str, &str)] = &[("header", "<h1>{{ . }}</h1>")];
+///
+/// let mut tmpl = Template::default();
+/// tmpl.set_loader(EmbeddedLoader::new(PARTIALS));
+/// tmpl.parse(r#"{{ template "header" . }}"#).unwrap();
+/// let output = tmpl.render(&Context::from("Title").unwrap());
+/// assert_eq!(&output.unwrap(), "<h1>Title</h1>");
+/// ```
+pub struct EmbeddedLoader {
+    templates: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedLoader {
+    /// Creates a loader for a static list of names and template texts.
+    pub fn new(templates: &'static [(&'static str, &'static str)]) -> EmbeddedLoader {
+        EmbeddedLoader { templates }
+    }
+}
+
+impl Loader for EmbeddedLoader {
+    fn load(&self, name: &str) -> Result<Option<String>, Error> {
+        Ok(self
+            .templates
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, text)| text.to_owned()))
+    }
+}
+
+impl Template {
+    /// Sets the loader asked for templates missing at execution, see [`loader`].
+    ///
+    /// [`loader`]: loader/index.html
+    pub fn set_loader<L: Loader + 'static>(&mut self, loader: L) {
+        self.loader = Some(Arc::new(loader));
+        if let Ok(mut loaded) = self.loaded.write() {
+            loaded.clear();
+        }
+    }
+
+    /// Returns the loaded template `name`, loading and parsing it on first use.
+    pub(crate) fn load_template(&self, name: &str) -> Result<Option<Arc<Tree>>, String> {
+        let loader = match self.loader {
+            Some(ref loader) => loader,
+            None => return Ok(None),
+        };
+        if let Some(tree) = self.loaded.read().ok().and_then(|l| l.get(name).cloned()) {
+            return Ok(Some(tree));
+        }
+        let text = match loader
+            .load(name)
+            .map_err(|e| format!("unable to load template {}: {}", name, e))?
+        {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let tree_set = self.parse_trees(name.to_owned(), text)?;
+        let mut loaded = self
+            .loaded
+            .write()
+            .map_err(|_| String::from("template cache is poisoned"))?;
+        for (n, tree) in tree_set {
+            loaded.entry(n).or_insert_with(|| Arc::new(tree));
+        }
+        Ok(loaded.get(name).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::exec::Context;
+    use crate::template::Template;
+
+    static EMBEDDED: &[(&str, &str)] = &[(
+        "row",
+        r#"{{ define "cell" }}[{{ . }}]{{ end }}{{ range . }}{{ template "cell" . }}{{ end }}"#,
+    )];
+
+    #[test]
+    fn test_fs_loader() {
+        let dir = std::env::temp_dir().join(format!("gtmpl-loader-{}", std::process::id()));
+        fs::create_dir_all(dir.join("partials")).unwrap();
+        fs::write(dir.join("partials/a.tmpl"), "a").unwrap();
+        let loader = FsLoader::new(&dir);
+        assert_eq!(
+            loader.load("partials/a.tmpl").unwrap(),
+            Some(String::from("a"))
+        );
+        assert_eq!(loader.load("partials/b.tmpl").unwrap(), None);
+        assert!(loader.load("../a.tmpl").is_err());
+        assert!(loader.load("/etc/passwd").is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_lazy_template() {
+        let mut t = Template::default();
+        t.set_loader(EmbeddedLoader::new(EMBEDDED));
+        assert!(t
+            .parse(r#"{{ template "row" . }}{{ template "cell" 3 }}"#)
+            .is_ok());
+        assert!(t.loaded.read().unwrap().is_empty());
+        let out = t.render(&Context::from(vec![1, 2]).unwrap());
+        assert_eq!(out, Ok(String::from("[1][2][3]")));
+        assert_eq!(t.loaded.read().unwrap().len(), 2);
+
+        assert!(t.parse(r#"{{ template "missing" }}"#).is_ok());
+        assert!(t.render(&Context::empty()).is_err());
+    }
+
+    #[test]
+    fn test_loaded_parse_error() {
+        let mut loader = MapLoader::new();
+        loader.insert("broken", "{{ if }}");
+        let mut t = Template::default();
+        t.set_loader(loader);
+        assert!(t.parse(r#"{{ template "broken" }}"#).is_ok());
+        assert!(t.render(&Context::empty()).is_err());
+    }
+
+    #[cfg(feature = "include")]
+    #[test]
+    fn test_include_loaded() {
+        let mut loader = MapLoader::new();
+        loader.insert("name", "<{{ . }}>");
+        let mut t = Template::default();
+        t.add_include_funcs();
+        t.set_loader(loader);
+        assert!(t.parse(r#"{{ include "name" . | len }}"#).is_ok());
+        let out = t.render(&Context::from("ab").unwrap());
+        assert_eq!(out, Ok(String::from("4")));
+    }
+}
@@ -9,6 +9,7 @@ pub struct Parser {
     pub funcs: HashSet<String>,
     lex: Option<Lexer>,
     line: usize,
+    pos: usize,
     token: VecDeque<Item>,
     peek_count: usize,
     pub tree_set: HashMap<String, Tree>,
@@ -19,8 +20,8 @@ pub struct Parser {
 }
 
 pub struct Tree {
-    name: String,
-    id: TreeId,
+    pub(crate) name: String,
+    pub(crate) id: TreeId,
     pub root: Option<Nodes>,
     vars: Vec<String>,
 }
@@ -32,6 +33,7 @@ impl Parser {
             funcs: HashSet::new(),
             lex: None,
             line: 0,
+            pos: 0,
             token: VecDeque::new(),
             peek_count: 0,
             tree_set: HashMap::new(),
@@ -44,7 +46,7 @@ impl Parser {
 }
 
 impl Tree {
-    fn new(name: String, id: TreeId) -> Tree {
+    pub(crate) fn new(name: String, id: TreeId) -> Tree {
         Tree {
             name,
             id,
@@ -58,15 +60,39 @@ impl Tree {
     }
 }
 
-pub fn parse(
+/// Parses `text` into templates, returning the byte offset of the token an error occurred at
+/// on failure.
+pub fn parse_with_pos(
     name: String,
     text: String,
     funcs: HashSet<String>,
+) -> Result<HashMap<String, Tree>, (String, usize)> {
+    parse_lexer(name, Lexer::new(text), funcs)
+}
+
+/// Parses `text` into templates with actions between `left` and `right` instead of `{{` and
+/// `}}`.
+pub fn parse_with_delims(
+    name: String,
+    text: String,
+    funcs: HashSet<String>,
+    left: &str,
+    right: &str,
 ) -> Result<HashMap<String, Tree>, String> {
+    parse_lexer(name, Lexer::with_delims(text, left, right), funcs).map_err(|(e, _)| e)
+}
+
+fn parse_lexer(
+    name: String,
+    lex: Lexer,
+    funcs: HashSet<String>,
+) -> Result<HashMap<String, Tree>, (String, usize)> {
     let mut p = Parser::new(name);
     p.funcs = funcs;
-    p.lex = Some(Lexer::new(text));
-    p.parse_tree()?;
+    p.lex = Some(lex);
+    if let Err(e) = p.parse_tree() {
+        return Err((e, p.pos));
+    }
     Ok(p.tree_set)
 }
 
@@ -752,6 +778,7 @@ impl Iterator for Parser {
         match item {
             Some(item) => {
                 self.line = item.line;
+                self.pos = item.pos;
                 Some(item)
             }
             _ => None,
@@ -797,6 +824,7 @@ mod tests_mocked {
             funcs: funcs.iter().map(|&k| k.to_owned()).collect(),
             lex: Some(lex),
             line: 0,
+            pos: 0,
             token: VecDeque::new(),
             peek_count: 0,
             tree_set: HashMap::new(),
@@ -807,6 +835,14 @@ mod tests_mocked {
         }
     }
 
+    #[test]
+    fn test_parse_with_pos() {
+        let funcs = ["eq".to_owned()].iter().cloned().collect();
+        let text = String::from("a\nb {{ eq 1 1 }} {{ foo 2 }}");
+        let r = parse_with_pos(String::from("foo"), text, funcs);
+        assert_eq!(r.err().map(|(_, pos)| pos), Some(20));
+    }
+
     #[test]
     fn test_iter() {
         let mut p = make_parser();
@@ -876,7 +912,8 @@ mod tests_mocked {
     #[test]
     fn test_display() {
         let raw = r#"{{if .}}2000{{else}} 3000 {{end}}"#;
-        let mut ts = parse(String::default(), String::from(raw), HashSet::default()).unwrap();
+        let mut ts =
+            parse_with_pos(String::default(), String::from(raw), HashSet::default()).unwrap();
         let tree = ts.get_mut("").unwrap();
         if let Some(ref root) = tree.root {
             assert_eq!(raw, format!("{}", root))
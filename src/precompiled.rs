@@ -0,0 +1,627 @@
+//! A compact binary format for parsed templates.
+//!
+//! The format starts with the magic bytes `GTMPL` and a version. Integers are stored as
+//! LEB128 and strings as their length followed by UTF-8. Every node is stored as its
+//! `NodeType` tag, position, tree id and fields. Values of literals are rebuilt from their
+//! text on load.
+use std::collections::HashMap;
+
+use crate::lexer::ItemType;
+use crate::node::*;
+use crate::parse::Tree;
+use crate::template::Template;
+use gtmpl_value::Value;
+
+const MAGIC: &[u8] = b"GTMPL";
+const VERSION: u64 = 1;
+
+const LIST: u8 = 0;
+const TEXT: u8 = 1;
+const PIPE: u8 = 2;
+const ACTION: u8 = 3;
+const COMMAND: u8 = 4;
+const IDENTIFIER: u8 = 5;
+const VARIABLE: u8 = 6;
+const DOT: u8 = 7;
+const NIL: u8 = 8;
+const FIELD: u8 = 9;
+const CHAIN: u8 = 10;
+const BOOL: u8 = 11;
+const NUMBER: u8 = 12;
+const STRING: u8 = 13;
+const END: u8 = 14;
+const ELSE: u8 = 15;
+const IF: u8 = 16;
+const WITH: u8 = 17;
+const RANGE: u8 = 18;
+const TEMPLATE: u8 = 19;
+
+// Maximum nesting of nodes, deeper templates are rejected instead of overflowing the stack.
+const MAX_NESTING: usize = 100;
+
+struct Writer {
+    buf: Vec<u8>,
+    depth: usize,
+}
+
+impl Writer {
+    fn byte(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    fn uint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.buf.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.buf.push(n as u8);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.uint(s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn strs(&mut self, strs: &[String]) {
+        self.uint(strs.len() as u64);
+        for s in strs {
+            self.str(s);
+        }
+    }
+
+    fn head(&mut self, tag: u8, node: &dyn Node) {
+        self.byte(tag);
+        self.uint(node.pos() as u64);
+        self.uint(node.tree() as u64);
+    }
+
+    fn list(&mut self, list: &ListNode) -> Result<(), String> {
+        self.head(LIST, list);
+        self.uint(list.nodes.len() as u64);
+        for node in &list.nodes {
+            self.node(node)?;
+        }
+        Ok(())
+    }
+
+    fn pipe(&mut self, pipe: &PipeNode) -> Result<(), String> {
+        self.head(PIPE, pipe);
+        self.uint(pipe.decl.len() as u64);
+        for var in &pipe.decl {
+            self.head(VARIABLE, var);
+            self.strs(&var.ident);
+        }
+        self.uint(pipe.cmds.len() as u64);
+        for cmd in &pipe.cmds {
+            self.command(cmd)?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, cmd: &CommandNode) -> Result<(), String> {
+        self.head(COMMAND, cmd);
+        self.uint(cmd.args.len() as u64);
+        for arg in &cmd.args {
+            self.node(arg)?;
+        }
+        Ok(())
+    }
+
+    fn branch(&mut self, tag: u8, branch: &BranchNode) -> Result<(), String> {
+        self.head(tag, branch);
+        self.pipe(&branch.pipe)?;
+        self.list(&branch.list)?;
+        match branch.else_list {
+            Some(ref else_list) => {
+                self.byte(1);
+                self.list(else_list)
+            }
+            None => {
+                self.byte(0);
+                Ok(())
+            }
+        }
+    }
+
+    // Nodes are counted like `Reader::node` does, so everything written can be loaded.
+    fn node(&mut self, node: &Nodes) -> Result<(), String> {
+        if self.depth >= MAX_NESTING {
+            return Err(String::from("nested too deeply to precompile"));
+        }
+        self.depth += 1;
+        let result = self.write_node(node);
+        self.depth -= 1;
+        result
+    }
+
+    fn write_node(&mut self, node: &Nodes) -> Result<(), String> {
+        match *node {
+            Nodes::List(ref n) => self.list(n)?,
+            Nodes::Text(ref n) => {
+                self.head(TEXT, n);
+                self.str(&n.text);
+            }
+            Nodes::Pipe(ref n) => self.pipe(n)?,
+            Nodes::Action(ref n) => {
+                self.head(ACTION, n);
+                self.pipe(&n.pipe)?;
+            }
+            Nodes::Command(ref n) => self.command(n)?,
+            Nodes::Identifier(ref n) => {
+                self.head(IDENTIFIER, n);
+                self.str(&n.ident);
+            }
+            Nodes::Variable(ref n) => {
+                self.head(VARIABLE, n);
+                self.strs(&n.ident);
+            }
+            Nodes::Dot(ref n) => self.head(DOT, n),
+            Nodes::Nil(ref n) => self.head(NIL, n),
+            Nodes::Field(ref n) => {
+                self.head(FIELD, n);
+                self.strs(&n.ident);
+            }
+            Nodes::Chain(ref n) => {
+                self.head(CHAIN, n);
+                self.node(&n.node)?;
+                self.strs(&n.field);
+            }
+            Nodes::Bool(ref n) => {
+                self.head(BOOL, n);
+                self.byte(if n.value == Value::Bool(true) { 1 } else { 0 });
+            }
+            Nodes::Number(ref n) => {
+                self.head(NUMBER, n);
+                self.byte(match n.number_typ {
+                    NumberType::Char => 1,
+                    _ => 0,
+                });
+                self.str(&n.text);
+            }
+            Nodes::String(ref n) => {
+                self.head(STRING, n);
+                self.str(&n.quoted);
+                match n.value {
+                    Value::String(ref s) => self.str(s),
+                    _ => self.str(""),
+                }
+            }
+            Nodes::End(ref n) => self.head(END, n),
+            Nodes::Else(ref n) => self.head(ELSE, n),
+            Nodes::If(ref n) => self.branch(IF, n)?,
+            Nodes::With(ref n) => self.branch(WITH, n)?,
+            Nodes::Range(ref n) => self.branch(RANGE, n)?,
+            Nodes::Template(ref n) => {
+                self.head(TEMPLATE, n);
+                match n.name {
+                    PipeOrString::String(ref s) => {
+                        self.byte(0);
+                        self.str(s);
+                    }
+                    PipeOrString::Pipe(ref p) => {
+                        self.byte(1);
+                        self.pipe(p)?;
+                    }
+                }
+                match n.pipe {
+                    Some(ref p) => {
+                        self.byte(1);
+                        self.pipe(p)?;
+                    }
+                    None => self.byte(0),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    funcs: &'a HashMap<String, gtmpl_value::Func>,
+    depth: usize,
+}
+
+fn invalid(what: &str) -> String {
+    format!("invalid precompiled template: {}", what)
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let b = *self
+            .buf
+            .get(self.pos)
+            .ok_or_else(|| invalid("unexpected end"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn uint(&mut self) -> Result<u64, String> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(invalid("integer overflow"))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        let n = self.uint()? as usize;
+        // Every element takes at least one byte.
+        if n > self.buf.len() - self.pos {
+            return Err(invalid("unexpected end"));
+        }
+        Ok(n)
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let n = self.len()?;
+        let s = std::str::from_utf8(&self.buf[self.pos..self.pos + n])
+            .map_err(|_| invalid("string is not UTF-8"))?;
+        self.pos += n;
+        Ok(s.to_owned())
+    }
+
+    fn strs(&mut self) -> Result<Vec<String>, String> {
+        let n = self.len()?;
+        (0..n).map(|_| self.str()).collect()
+    }
+
+    fn head(&mut self, tag: u8) -> Result<(Pos, TreeId), String> {
+        if self.byte()? != tag {
+            return Err(invalid("unexpected node"));
+        }
+        self.pos_tree()
+    }
+
+    fn pos_tree(&mut self) -> Result<(Pos, TreeId), String> {
+        Ok((self.uint()? as Pos, self.uint()? as TreeId))
+    }
+
+    fn list(&mut self) -> Result<ListNode, String> {
+        let (pos, tr) = self.head(LIST)?;
+        let mut list = ListNode::new(tr, pos);
+        for _ in 0..self.len()? {
+            list.append(self.node()?);
+        }
+        Ok(list)
+    }
+
+    fn pipe(&mut self) -> Result<PipeNode, String> {
+        let (pos, tr) = self.head(PIPE)?;
+        let mut decl = vec![];
+        for _ in 0..self.len()? {
+            let (pos, tr) = self.head(VARIABLE)?;
+            let mut var = VariableNode::new(tr, pos, "");
+            var.ident = self.strs()?;
+            decl.push(var);
+        }
+        let mut pipe = PipeNode::new(tr, pos, decl);
+        for _ in 0..self.len()? {
+            pipe.append(self.command()?);
+        }
+        Ok(pipe)
+    }
+
+    fn command(&mut self) -> Result<CommandNode, String> {
+        let (pos, tr) = self.head(COMMAND)?;
+        let mut cmd = CommandNode::new(tr, pos);
+        for _ in 0..self.len()? {
+            cmd.append(self.node()?);
+        }
+        Ok(cmd)
+    }
+
+    fn else_list(&mut self) -> Result<Option<ListNode>, String> {
+        match self.byte()? {
+            0 => Ok(None),
+            _ => self.list().map(Some),
+        }
+    }
+
+    fn node(&mut self) -> Result<Nodes, String> {
+        if self.depth >= MAX_NESTING {
+            return Err(invalid("nesting too deep"));
+        }
+        self.depth += 1;
+        let node = self.read_node();
+        self.depth -= 1;
+        node
+    }
+
+    fn read_node(&mut self) -> Result<Nodes, String> {
+        let tag = self.buf.get(self.pos).cloned();
+        if tag == Some(LIST) {
+            return self.list().map(Nodes::List);
+        }
+        if tag == Some(PIPE) {
+            return self.pipe().map(Nodes::Pipe);
+        }
+        if tag == Some(COMMAND) {
+            return self.command().map(Nodes::Command);
+        }
+        let tag = self.byte()?;
+        let (pos, tr) = self.pos_tree()?;
+        Ok(match tag {
+            TEXT => Nodes::Text(TextNode::new(tr, pos, self.str()?)),
+            ACTION => Nodes::Action(ActionNode::new(tr, pos, self.pipe()?)),
+            IDENTIFIER => {
+                let ident = self.str()?;
+                if !self.funcs.contains_key(&ident) {
+                    return Err(format!("function {} not defined", ident));
+                }
+                let mut node = IdentifierNode::new(ident);
+                node.set_pos(pos);
+                node.set_tree(tr);
+                Nodes::Identifier(node)
+            }
+            VARIABLE => {
+                let mut node = VariableNode::new(tr, pos, "");
+                node.ident = self.strs()?;
+                Nodes::Variable(node)
+            }
+            DOT => Nodes::Dot(DotNode::new(tr, pos)),
+            NIL => Nodes::Nil(NilNode::new(tr, pos)),
+            FIELD => {
+                let mut node = FieldNode::new(tr, pos, "");
+                node.ident = self.strs()?;
+                Nodes::Field(node)
+            }
+            CHAIN => {
+                let mut node = ChainNode::new(tr, pos, self.node()?);
+                node.field = self.strs()?;
+                Nodes::Chain(node)
+            }
+            BOOL => Nodes::Bool(BoolNode::new(tr, pos, self.byte()? == 1)),
+            NUMBER => {
+                let typ = match self.byte()? {
+                    1 => ItemType::ItemCharConstant,
+                    _ => ItemType::ItemNumber,
+                };
+                let text = self.str()?;
+                let node =
+                    NumberNode::new(tr, pos, text, &typ).map_err(|_| invalid("bad number"))?;
+                Nodes::Number(node)
+            }
+            STRING => {
+                let quoted = self.str()?;
+                Nodes::String(StringNode::new(tr, pos, quoted, self.str()?))
+            }
+            END => Nodes::End(EndNode::new(tr, pos)),
+            ELSE => Nodes::Else(ElseNode::new(tr, pos)),
+            IF => Nodes::If(IfNode::new_if(
+                tr,
+                pos,
+                self.pipe()?,
+                self.list()?,
+                self.else_list()?,
+            )),
+            WITH => Nodes::With(WithNode::new_with(
+                tr,
+                pos,
+                self.pipe()?,
+                self.list()?,
+                self.else_list()?,
+            )),
+            RANGE => Nodes::Range(RangeNode::new_range(
+                tr,
+                pos,
+                self.pipe()?,
+                self.list()?,
+                self.else_list()?,
+            )),
+            TEMPLATE => {
+                let name = match self.byte()? {
+                    0 => PipeOrString::String(self.str()?),
+                    _ => PipeOrString::Pipe(self.pipe()?),
+                };
+                let pipe = match self.byte()? {
+                    0 => None,
+                    _ => Some(self.pipe()?),
+                };
+                Nodes::Template(TemplateNode::new(tr, pos, name, pipe))
+            }
+            _ => return Err(invalid("unknown node")),
+        })
+    }
+}
+
+impl Template {
+    /// Returns the parsed templates in a compact binary format which is loaded by
+    /// [`load_bytes`](#method.load_bytes) without parsing the templates again, e.g. to
+    /// parse templates in a build script. Functions are not part of the output. Fails for
+    /// templates nested too deeply to be loaded again.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::{Context, Template};
+    ///
+    /// let mut tmpl = Template::default();
+    /// tmpl.parse(r#"{{ define "name" }}<{{ . }}>{{ end }}Hello {{ template "name" . }}!"#)
+    ///     .unwrap();
+    /// let bytes = tmpl.to_bytes().unwrap();
+    ///
+    /// let mut loaded = Template::default();
+    /// loaded.load_bytes(&bytes).unwrap();
+    /// let output = loaded.render(&Context::from("World").unwrap());
+    /// assert_eq!(&output.unwrap(), "Hello <World>!");
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut w = Writer {
+            buf: MAGIC.to_vec(),
+            depth: 0,
+        };
+        w.uint(VERSION);
+        w.str(&self.name);
+        let mut names: Vec<&String> = self.tree_set.keys().collect();
+        names.sort();
+        w.uint(names.len() as u64);
+        for name in names {
+            let tree = &self.tree_set[name];
+            w.str(&tree.name);
+            w.uint(tree.id as u64);
+            match tree.root {
+                Some(ref root) => {
+                    w.byte(1);
+                    w.node(root)
+                        .map_err(|e| format!("template {}: {}", tree.name, e))?;
+                }
+                None => w.byte(0),
+            }
+        }
+        Ok(w.buf)
+    }
+
+    /// Adds the templates written by [`to_bytes`](#method.to_bytes). Functions used by the
+    /// templates must be added before. A template without a name takes the name of the
+    /// saved template.
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(invalid("missing header"));
+        }
+        let mut r = Reader {
+            buf: bytes,
+            pos: MAGIC.len(),
+            funcs: &self.funcs,
+            depth: 0,
+        };
+        let version = r.uint()?;
+        if version != VERSION {
+            return Err(format!(
+                "unsupported precompiled template version {}, expected {}",
+                version, VERSION
+            ));
+        }
+        let name = r.str()?;
+        let mut tree_set = HashMap::new();
+        for _ in 0..r.len()? {
+            let mut tree = Tree::new(r.str()?, r.uint()? as TreeId);
+            if r.byte()? == 1 {
+                tree.root = Some(r.node()?);
+            }
+            tree_set.insert(tree.name.clone(), tree);
+        }
+        if r.pos != bytes.len() {
+            return Err(invalid("trailing bytes"));
+        }
+        self.tree_set.extend(tree_set);
+        if self.name.is_empty() {
+            self.name = name;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+    use crate::exec::Context;
+
+    static TEXT: &str = r#"{{ define "row" }}{{ range $i, $v := . }}{{ if $i }}, {{ end }}{{ $v.name | printf "%q" }}{{ end }}{{ end -}}
+{{ with .items }}{{ template "row" . }}{{ else }}none{{ end }} {{ (index .items 0).name }} {{ .n }} {{ 'a' }} {{ -3 }} {{ true }} {{ .n | eq 2 }}"#;
+
+    fn ctx() -> Context {
+        let mut item = HashMap::new();
+        item.insert("name".to_owned(), Value::from("a"));
+        let mut data = HashMap::new();
+        data.insert("items".to_owned(), Value::from(vec![Value::from(item)]));
+        data.insert("n".to_owned(), Value::from(2));
+        Context::from(data).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut t = Template::default();
+        t.parse(TEXT).unwrap();
+        let bytes = t.to_bytes().unwrap();
+        assert!(bytes.starts_with(MAGIC));
+
+        let mut loaded = Template::default();
+        assert!(loaded.load_bytes(&bytes).is_ok());
+        let mut names: Vec<_> = loaded.tree_set.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["", "row"]);
+        assert_eq!(t.render(&ctx()), loaded.render(&ctx()));
+        assert_eq!(
+            loaded.render(&ctx()),
+            Ok(String::from(r#""a" a 2 97 -3 true true"#))
+        );
+        assert_eq!(loaded.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut t = Template::default();
+        t.add_func("custom", |_| Ok(Value::Nil));
+        t.parse("{{ custom }}").unwrap();
+        let bytes = t.to_bytes().unwrap();
+
+        let mut loaded = Template::default();
+        assert_eq!(
+            loaded.load_bytes(&bytes),
+            Err(String::from("function custom not defined"))
+        );
+        for end in 0..bytes.len() {
+            assert!(loaded.load_bytes(&bytes[..end]).is_err());
+        }
+        assert!(loaded.load_bytes(b"{{ . }}").is_err());
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = 2;
+        assert!(loaded.load_bytes(&newer).unwrap_err().contains("version 2"));
+        loaded.add_func("custom", |_| Ok(Value::Nil));
+        assert!(loaded.load_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_nesting() {
+        let mut bytes = MAGIC.to_vec();
+        // Version, name, one tree named "" with id 0 and a root node.
+        bytes.extend_from_slice(&[1, 0, 1, 0, 0, 1]);
+        for _ in 0..1_000_000 {
+            bytes.extend_from_slice(&[CHAIN, 0, 0]);
+        }
+        let mut loaded = Template::default();
+        assert_eq!(
+            loaded.load_bytes(&bytes),
+            Err(String::from(
+                "invalid precompiled template: nesting too deep"
+            ))
+        );
+
+        let mut t = Template::default();
+        t.parse(format!("{{{{ {}.{} }}}}", "(".repeat(50), ")".repeat(50)))
+            .unwrap();
+        assert!(loaded.load_bytes(&t.to_bytes().unwrap()).is_ok());
+
+        let nested = |n: usize| {
+            let mut t = Template::default();
+            t.parse(format!(
+                "{}{}",
+                "{{ if . }}".repeat(n),
+                "{{ end }}".repeat(n)
+            ))
+            .unwrap();
+            t
+        };
+        let deepest = (1..MAX_NESTING)
+            .take_while(|&n| nested(n).to_bytes().is_ok())
+            .last()
+            .unwrap();
+        assert!(deepest >= MAX_NESTING / 2 - 1);
+        let bytes = nested(deepest).to_bytes().unwrap();
+        let mut loaded = Template::default();
+        assert!(loaded.load_bytes(&bytes).is_ok());
+        assert_eq!(nested(deepest).render(&ctx()), loaded.render(&ctx()));
+        for n in &[deepest + 1, MAX_NESTING + 10] {
+            assert_eq!(
+                nested(*n).to_bytes(),
+                Err(String::from("template : nested too deeply to precompile"))
+            );
+        }
+    }
+}
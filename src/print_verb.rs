@@ -1,299 +1,583 @@
 use std::char;
-use std::fmt;
+use std::collections::HashMap;
+use std::fmt::Write;
 
-use crate::printf::{params_to_chars, FormatParams};
+use crate::printf::FormatParams;
+use crate::utils::{is_print, quote_char, quote_str, to_num, Num};
 
 use gtmpl_value::Value;
 
+static LDIGITS: &[u8] = b"0123456789abcdefx";
+static UDIGITS: &[u8] = b"0123456789ABCDEFX";
+
 /// Print a verb like golang's printf.
-pub fn print(p: &FormatParams, typ: char, val: &Value) -> Result<String, String> {
+///
+/// Verbs that don't fit the value are not an error but printed like golang does, e.g.
+/// `%!s(int=5)`.
+pub fn print(p: &FormatParams, verb: char, val: &Value) -> String {
+    if verb == 'T' {
+        return fmt_s(p, type_name(val));
+    }
     match *val {
-        Value::Number(ref n) if n.as_u64().is_some() => {
-            let u = n.as_u64().unwrap();
-            Ok(match typ {
-                'b' => printf_b(p, u),
-                'd' | 'v' => printf_generic(p, u),
-                'o' => printf_o(p, u),
-                'c' => {
-                    let c = char::from_u32(u as u32)
-                        .ok_or_else(|| format!("{:X} is not a valid char", u))?;
-                    printf_generic(p, c)
-                }
-                'q' => {
-                    let c = char::from_u32(u as u32)
-                        .ok_or_else(|| format!("{:X} is not a valid char", u))?;
-                    printf_generic(p, format!("'{}'", escape_char(c)))
-                }
-                'x' => printf_x(p, u),
-                'X' => printf_xx(p, u),
-                'U' => printf_generic(p, format!("U+{:X}", u)),
-                _ => return Err(format!("unable to format {} as %{}", val, typ)),
-            })
-        }
-        Value::Number(ref n) if n.as_i64().is_some() => {
-            let i = n.as_i64().unwrap();
-            Ok(match typ {
-                'b' => printf_b(p, i),
-                'd' => printf_generic(p, i),
-                'o' => printf_o(p, i),
-                'c' => {
-                    let c = char::from_u32(i as u32)
-                        .ok_or_else(|| format!("{:X} is not a valid char", i))?;
-                    printf_generic(p, c)
-                }
-                'q' => {
-                    let c = char::from_u32(i as u32)
-                        .ok_or_else(|| format!("{:X} is not a valid char", i))?;
-                    printf_generic(p, format!("'{}'", escape_char(c)))
-                }
-                'x' => printf_x(p, i),
-                'X' => printf_xx(p, i),
-                'U' => printf_generic(p, format!("U+{:X}", i)),
-                _ => return Err(format!("unable to format {} as %{}", val, typ)),
-            })
+        Value::Bool(b) => match verb {
+            't' | 'v' => pad(p, if b { "true" } else { "false" }),
+            _ => bad_verb(p, verb, val),
+        },
+        Value::Number(ref n) => match to_num(n) {
+            Num::U(u) => fmt_integer_verb(p, verb, false, u, val),
+            Num::I(i) => fmt_integer_verb(p, verb, i < 0, i.unsigned_abs(), val),
+            Num::F(f) => fmt_float_verb(p, verb, f, val),
+        },
+        Value::String(ref s) => match verb {
+            'v' if p.sharp_v => pad(p, &quote_str(s, false)),
+            'v' | 's' => fmt_s(p, s),
+            'q' => fmt_q(p, s),
+            'x' => fmt_sbx(p, s, LDIGITS),
+            'X' => fmt_sbx(p, s, UDIGITS),
+            _ => bad_verb(p, verb, val),
+        },
+        Value::Array(ref a) => fmt_array(p, verb, a),
+        Value::Map(ref m) => fmt_map(p, verb, m),
+        Value::Object(ref o) => fmt_object(p, verb, o),
+        Value::Nil | Value::NoValue => match verb {
+            'v' => pad(p, "<nil>"),
+            _ => bad_verb(p, verb, val),
+        },
+        Value::Function(ref f) => match verb {
+            'v' => pad(p, &f.to_string()),
+            _ => bad_verb(p, verb, val),
+        },
+    }
+}
+
+/// Returns the name golang would use for the type of `val`.
+pub fn type_name(val: &Value) -> &'static str {
+    match *val {
+        Value::NoValue | Value::Nil => "<nil>",
+        Value::Bool(_) => "bool",
+        Value::String(_) => "string",
+        Value::Number(ref n) => match to_num(n) {
+            Num::U(u) if u > i64::MAX as u64 => "uint64",
+            Num::U(_) | Num::I(_) => "int",
+            Num::F(_) => "float64",
+        },
+        Value::Array(_) => "[]interface {}",
+        Value::Map(_) => "map[string]interface {}",
+        Value::Object(_) => "struct",
+        Value::Function(_) => "func",
+    }
+}
+
+fn bad_verb(p: &FormatParams, verb: char, val: &Value) -> String {
+    match *val {
+        Value::Nil | Value::NoValue => format!("%!{}(<nil>)", verb),
+        _ => format!("%!{}({}={})", verb, type_name(val), print(p, 'v', val)),
+    }
+}
+
+fn fmt_integer_verb(p: &FormatParams, verb: char, neg: bool, u: u64, val: &Value) -> String {
+    match verb {
+        'v' if p.sharp_v && u > i64::MAX as u64 => {
+            let p = FormatParams {
+                sharp: true,
+                ..p.clone()
+            };
+            fmt_integer(&p, verb, neg, u, 16, LDIGITS)
         }
-        Value::Number(ref n) if n.as_f64().is_some() => {
-            let f = n.as_f64().unwrap();
-            Ok(match typ {
-                'e' => printf_e(p, f),
-                'E' => printf_ee(p, f),
-                'f' | 'F' => printf_generic(p, f),
-                _ => return Err(format!("unable to format {} as %{}", val, typ)),
-            })
+        'v' | 'd' => fmt_integer(p, verb, neg, u, 10, LDIGITS),
+        'b' => fmt_integer(p, verb, neg, u, 2, LDIGITS),
+        'o' | 'O' => fmt_integer(p, verb, neg, u, 8, LDIGITS),
+        'x' => fmt_integer(p, verb, neg, u, 16, LDIGITS),
+        'X' => fmt_integer(p, verb, neg, u, 16, UDIGITS),
+        'c' => pad(p, to_char(neg, u).encode_utf8(&mut [0; 4])),
+        'q' => pad(p, &quote_char(to_char(neg, u), p.plus)),
+        'U' => fmt_unicode(p, if neg { u.wrapping_neg() } else { u }),
+        // gtmpl_value stores integral floats as integers, so `3.0` has to be accepted by the
+        // float verbs.
+        'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+            fmt_float(p, verb, if neg { -(u as f64) } else { u as f64 })
         }
-        Value::Bool(ref b) => Ok(match typ {
-            'v' | 't' => printf_generic(p, b),
-            _ => return Err(format!("unable to format {} as %{}", val, typ)),
-        }),
-        Value::String(ref s) => Ok(match typ {
-            's' | 'v' => printf_generic(p, s),
-            'x' => printf_x(p, Hexer::from(s.as_str())),
-            'X' => printf_xx(p, Hexer::from(s.as_str())),
-            'q' => {
-                let s = s
-                    .chars()
-                    .map(|c| c.escape_default().to_string())
-                    .collect::<String>();
-                printf_generic(p, s)
-            }
-            _ => return Err(format!("unable to format {} as %{}", val, typ)),
-        }),
-        _ => Err(format!("unable to format {} as %{}", val, typ)),
+        _ => bad_verb(p, verb, val),
     }
 }
 
-fn printf_b<B: fmt::Binary>(p: &FormatParams, u: B) -> String {
-    match params_to_chars(p) {
-        ('#', '_', '+', '_', _) => format!("{:+#width$b}", u, width = p.width),
-        ('_', '_', '+', '_', _) => format!("{:+width$b}", u, width = p.width),
-        ('#', '_', '_', '_', _) => format!("{:#width$b}", u, width = p.width),
-        ('#', '0', '+', '_', _) => format!("{:+#0width$b}", u, width = p.width),
-        ('_', '0', '+', '_', _) => format!("{:+0width$b}", u, width = p.width),
-        ('#', '0', '_', '_', _) => format!("{:#0width$b}", u, width = p.width),
-        ('#', '_', '+', '-', _) => format!("{:<+#width$b}", u, width = p.width),
-        ('_', '_', '+', '-', _) => format!("{:<+width$b}", u, width = p.width),
-        ('#', '_', '_', '-', _) => format!("{:<#width$b}", u, width = p.width),
-        ('#', '0', '+', '-', _) => format!("{:<+#0width$b}", u, width = p.width),
-        ('_', '0', '+', '-', _) => format!("{:<+0width$b}", u, width = p.width),
-        ('#', '0', '_', '-', _) => format!("{:<#0width$b}", u, width = p.width),
-        (_, _, _, _, _) => format!("{:width$b}", u, width = p.width),
+fn fmt_float_verb(p: &FormatParams, verb: char, f: f64, val: &Value) -> String {
+    match verb {
+        'v' | 'b' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'x' | 'X' => fmt_float(p, verb, f),
+        _ => bad_verb(p, verb, val),
     }
 }
 
-fn printf_o<B: fmt::Octal>(p: &FormatParams, u: B) -> String {
-    match params_to_chars(p) {
-        ('#', '_', '+', '_', _) => format!("{:+#width$o}", u, width = p.width),
-        ('_', '_', '+', '_', _) => format!("{:+width$o}", u, width = p.width),
-        ('#', '_', '_', '_', _) => format!("{:#width$o}", u, width = p.width),
-        ('#', '0', '+', '_', _) => format!("{:+#0width$o}", u, width = p.width),
-        ('_', '0', '+', '_', _) => format!("{:+0width$o}", u, width = p.width),
-        ('#', '0', '_', '_', _) => format!("{:#0width$o}", u, width = p.width),
-        ('#', '_', '+', '-', _) => format!("{:<+#width$o}", u, width = p.width),
-        ('_', '_', '+', '-', _) => format!("{:<+width$o}", u, width = p.width),
-        ('#', '_', '_', '-', _) => format!("{:<#width$o}", u, width = p.width),
-        ('#', '0', '+', '-', _) => format!("{:<+#0width$o}", u, width = p.width),
-        ('_', '0', '+', '-', _) => format!("{:<+0width$o}", u, width = p.width),
-        ('#', '0', '_', '-', _) => format!("{:<#0width$o}", u, width = p.width),
-        (_, _, _, _, _) => format!("{:width$o}", u, width = p.width),
+fn to_char(neg: bool, u: u64) -> char {
+    if neg || u > u64::from(u32::MAX) {
+        return char::REPLACEMENT_CHARACTER;
     }
+    char::from_u32(u as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
 }
 
-fn printf_x<B: fmt::LowerHex>(p: &FormatParams, u: B) -> String {
-    match params_to_chars(p) {
-        ('#', '_', '+', '_', _) => format!("{:+#width$x}", u, width = p.width),
-        ('_', '_', '+', '_', _) => format!("{:+width$x}", u, width = p.width),
-        ('#', '_', '_', '_', _) => format!("{:#width$x}", u, width = p.width),
-        ('#', '0', '+', '_', _) => format!("{:+#0width$x}", u, width = p.width),
-        ('_', '0', '+', '_', _) => format!("{:+0width$x}", u, width = p.width),
-        ('#', '0', '_', '_', _) => format!("{:#0width$x}", u, width = p.width),
-        ('#', '_', '+', '-', _) => format!("{:<+#width$x}", u, width = p.width),
-        ('_', '_', '+', '-', _) => format!("{:<+width$x}", u, width = p.width),
-        ('#', '_', '_', '-', _) => format!("{:<#width$x}", u, width = p.width),
-        ('#', '0', '+', '-', _) => format!("{:<+#0width$x}", u, width = p.width),
-        ('_', '0', '+', '-', _) => format!("{:<+0width$x}", u, width = p.width),
-        ('#', '0', '_', '-', _) => format!("{:<#0width$x}", u, width = p.width),
-        (_, _, _, _, _) => format!("{:width$x}", u, width = p.width),
+/// Pads `s` to the requested width. The width is counted in characters, not in bytes.
+fn pad(p: &FormatParams, s: &str) -> String {
+    pad_with(p, s, p.zero)
+}
+
+fn pad_with(p: &FormatParams, s: &str, zero: bool) -> String {
+    let len = s.chars().count();
+    if p.width <= len {
+        return s.to_owned();
+    }
+    let fill = if zero { "0" } else { " " }.repeat(p.width - len);
+    if p.minus {
+        format!("{}{}", s, fill)
+    } else {
+        format!("{}{}", fill, s)
     }
 }
 
-fn printf_xx<B: fmt::UpperHex>(p: &FormatParams, u: B) -> String {
-    match params_to_chars(p) {
-        ('#', '_', '+', '_', _) => format!("{:+#width$X}", u, width = p.width),
-        ('_', '_', '+', '_', _) => format!("{:+width$X}", u, width = p.width),
-        ('#', '_', '_', '_', _) => format!("{:#width$X}", u, width = p.width),
-        ('#', '0', '+', '_', _) => format!("{:+#0width$X}", u, width = p.width),
-        ('_', '0', '+', '_', _) => format!("{:+0width$X}", u, width = p.width),
-        ('#', '0', '_', '_', _) => format!("{:#0width$X}", u, width = p.width),
-        ('#', '_', '+', '-', _) => format!("{:<+#width$X}", u, width = p.width),
-        ('_', '_', '+', '-', _) => format!("{:<+width$X}", u, width = p.width),
-        ('#', '_', '_', '-', _) => format!("{:<#width$X}", u, width = p.width),
-        ('#', '0', '+', '-', _) => format!("{:<+#0width$X}", u, width = p.width),
-        ('_', '0', '+', '-', _) => format!("{:<+0width$X}", u, width = p.width),
-        ('#', '0', '_', '-', _) => format!("{:<#0width$X}", u, width = p.width),
-        (_, _, _, _, _) => format!("{:width$X}", u, width = p.width),
+/// Truncates `s` to the precision and pads it.
+fn fmt_s(p: &FormatParams, s: &str) -> String {
+    match p.precision {
+        Some(pr) => pad(p, &s.chars().take(pr).collect::<String>()),
+        None => pad(p, s),
     }
 }
 
-fn printf_generic<D: fmt::Display>(p: &FormatParams, c: D) -> String {
-    if let Some(pr) = p.precision {
-        match params_to_chars(p) {
-            ('#', '_', '+', '_', _) => format!("{:+#width$.pr$}", c, width = p.width, pr = pr),
-            ('_', '_', '+', '_', _) => format!("{:+width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '_', '_', '_', _) => format!("{:#width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '0', '+', '_', _) => format!("{:+#0width$.pr$}", c, width = p.width, pr = pr),
-            ('_', '0', '+', '_', _) => format!("{:+0width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '0', '_', '_', _) => format!("{:#0width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '_', '+', '-', _) => format!("{:<+#width$.pr$}", c, width = p.width, pr = pr),
-            ('_', '_', '+', '-', _) => format!("{:<+width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '_', '_', '-', _) => format!("{:<#width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '0', '+', '-', _) => format!("{:<+#0width$.pr$}", c, width = p.width, pr = pr),
-            ('_', '0', '+', '-', _) => format!("{:<+0width$.pr$}", c, width = p.width, pr = pr),
-            ('#', '0', '_', '-', _) => format!("{:<#0width$.pr$}", c, width = p.width, pr = pr),
-            (_, _, _, _, _) => format!("{:width$.pr$}", c, width = p.width, pr = pr),
-        }
-    } else {
-        match params_to_chars(p) {
-            ('#', '_', '+', '_', _) => format!("{:+#width$}", c, width = p.width),
-            ('_', '_', '+', '_', _) => format!("{:+width$}", c, width = p.width),
-            ('#', '_', '_', '_', _) => format!("{:#width$}", c, width = p.width),
-            ('#', '0', '+', '_', _) => format!("{:+#0width$}", c, width = p.width),
-            ('_', '0', '+', '_', _) => format!("{:+0width$}", c, width = p.width),
-            ('#', '0', '_', '_', _) => format!("{:#0width$}", c, width = p.width),
-            ('#', '_', '+', '-', _) => format!("{:<+#width$}", c, width = p.width),
-            ('_', '_', '+', '-', _) => format!("{:<+width$}", c, width = p.width),
-            ('#', '_', '_', '-', _) => format!("{:<#width$}", c, width = p.width),
-            ('#', '0', '+', '-', _) => format!("{:<+#0width$}", c, width = p.width),
-            ('_', '0', '+', '-', _) => format!("{:<+0width$}", c, width = p.width),
-            ('#', '0', '_', '-', _) => format!("{:<#0width$}", c, width = p.width),
-            (_, _, _, _, _) => format!("{:width$}", c, width = p.width),
+fn fmt_q(p: &FormatParams, s: &str) -> String {
+    let s = match p.precision {
+        Some(pr) => s.chars().take(pr).collect::<String>(),
+        None => s.to_owned(),
+    };
+    if p.sharp && can_backquote(&s) {
+        return pad(p, &format!("`{}`", s));
+    }
+    pad(p, &quote_str(&s, p.plus))
+}
+
+fn can_backquote(s: &str) -> bool {
+    s.chars()
+        .all(|c| c != '`' && c != '\u{feff}' && (c == '\t' || !c.is_control()))
+}
+
+fn fmt_sbx(p: &FormatParams, s: &str, digits: &[u8]) -> String {
+    let bytes = s.as_bytes();
+    let len = p.precision.map_or(bytes.len(), |pr| pr.min(bytes.len()));
+    let mut r = String::new();
+    if p.sharp {
+        r.push('0');
+        r.push(digits[16] as char);
+    }
+    for (i, b) in bytes.iter().take(len).enumerate() {
+        if p.space && i > 0 {
+            r.push(' ');
+            if p.sharp {
+                r.push('0');
+                r.push(digits[16] as char);
+            }
         }
+        r.push(digits[(b >> 4) as usize] as char);
+        r.push(digits[(b & 0xF) as usize] as char);
     }
+    if len == 0 {
+        r.clear();
+    }
+    pad_with(p, &r, false)
 }
 
-fn printf_e<E: fmt::LowerExp>(p: &FormatParams, f: E) -> String {
+fn fmt_integer(
+    p: &FormatParams,
+    verb: char,
+    neg: bool,
+    u: u64,
+    base: u64,
+    digits: &[u8],
+) -> String {
+    let mut prec = 0;
     if let Some(pr) = p.precision {
-        match params_to_chars(p) {
-            ('#', '_', '+', '_', _) => format!("{:+#width$.pr$e}", f, width = p.width, pr = pr),
-            ('_', '_', '+', '_', _) => format!("{:+width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '_', '_', '_', _) => format!("{:#width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '0', '+', '_', _) => format!("{:+#0width$.pr$e}", f, width = p.width, pr = pr),
-            ('_', '0', '+', '_', _) => format!("{:+0width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '0', '_', '_', _) => format!("{:#0width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '_', '+', '-', _) => format!("{:<+#width$.pr$e}", f, width = p.width, pr = pr),
-            ('_', '_', '+', '-', _) => format!("{:<+width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '_', '_', '-', _) => format!("{:<#width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '0', '+', '-', _) => format!("{:<+#0width$.pr$e}", f, width = p.width, pr = pr),
-            ('_', '0', '+', '-', _) => format!("{:<+0width$.pr$e}", f, width = p.width, pr = pr),
-            ('#', '0', '_', '-', _) => format!("{:<#0width$.pr$e}", f, width = p.width, pr = pr),
-            (_, _, _, _, _) => format!("{:width$.pr$e}", f, width = p.width, pr = pr),
+        prec = pr;
+        // Precision of 0 and value of 0 means "print nothing" but padding.
+        if pr == 0 && u == 0 {
+            return " ".repeat(p.width);
         }
-    } else {
-        match params_to_chars(p) {
-            ('#', '_', '+', '_', _) => format!("{:+#width$e}", f, width = p.width),
-            ('_', '_', '+', '_', _) => format!("{:+width$e}", f, width = p.width),
-            ('#', '_', '_', '_', _) => format!("{:#width$e}", f, width = p.width),
-            ('#', '0', '+', '_', _) => format!("{:+#0width$e}", f, width = p.width),
-            ('_', '0', '+', '_', _) => format!("{:+0width$e}", f, width = p.width),
-            ('#', '0', '_', '_', _) => format!("{:#0width$e}", f, width = p.width),
-            ('#', '_', '+', '-', _) => format!("{:<+#width$e}", f, width = p.width),
-            ('_', '_', '+', '-', _) => format!("{:<+width$e}", f, width = p.width),
-            ('#', '_', '_', '-', _) => format!("{:<#width$e}", f, width = p.width),
-            ('#', '0', '+', '-', _) => format!("{:<+#0width$e}", f, width = p.width),
-            ('_', '0', '+', '-', _) => format!("{:<+0width$e}", f, width = p.width),
-            ('#', '0', '_', '-', _) => format!("{:<#0width$e}", f, width = p.width),
-            (_, _, _, _, _) => format!("{:width$e}", f, width = p.width),
+    } else if p.zero && p.width > 0 {
+        prec = p.width;
+        if neg || p.plus || p.space {
+            // Leave room for the sign.
+            prec -= 1;
+        }
+    }
+
+    let mut buf = Vec::new();
+    let mut n = u;
+    loop {
+        buf.push(digits[(n % base) as usize]);
+        n /= base;
+        if n == 0 {
+            break;
+        }
+    }
+    while buf.len() < prec {
+        buf.push(b'0');
+    }
+    if p.sharp {
+        match base {
+            2 => buf.extend_from_slice(b"b0"),
+            8 if buf.last() != Some(&b'0') => buf.push(b'0'),
+            16 => {
+                buf.push(digits[16]);
+                buf.push(b'0');
+            }
+            _ => {}
         }
     }
+    if verb == 'O' {
+        buf.extend_from_slice(b"o0");
+    }
+    if neg {
+        buf.push(b'-');
+    } else if p.plus {
+        buf.push(b'+');
+    } else if p.space {
+        buf.push(b' ');
+    }
+    buf.reverse();
+    pad_with(p, &String::from_utf8_lossy(&buf), false)
 }
 
-fn printf_ee<E: fmt::UpperExp>(p: &FormatParams, f: E) -> String {
-    if let Some(pr) = p.precision {
-        match params_to_chars(p) {
-            ('#', '_', '+', '_', _) => format!("{:+#width$.pr$E}", f, width = p.width, pr = pr),
-            ('_', '_', '+', '_', _) => format!("{:+width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '_', '_', '_', _) => format!("{:#width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '0', '+', '_', _) => format!("{:+#0width$.pr$E}", f, width = p.width, pr = pr),
-            ('_', '0', '+', '_', _) => format!("{:+0width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '0', '_', '_', _) => format!("{:#0width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '_', '+', '-', _) => format!("{:<+#width$.pr$E}", f, width = p.width, pr = pr),
-            ('_', '_', '+', '-', _) => format!("{:<+width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '_', '_', '-', _) => format!("{:<#width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '0', '+', '-', _) => format!("{:<+#0width$.pr$E}", f, width = p.width, pr = pr),
-            ('_', '0', '+', '-', _) => format!("{:<+0width$.pr$E}", f, width = p.width, pr = pr),
-            ('#', '0', '_', '-', _) => format!("{:<#0width$.pr$E}", f, width = p.width, pr = pr),
-            (_, _, _, _, _) => format!("{:width$.pr$E}", f, width = p.width, pr = pr),
+fn fmt_unicode(p: &FormatParams, u: u64) -> String {
+    let prec = p.precision.map_or(4, |pr| pr.max(4));
+    let mut s = format!("U+{:0prec$X}", u, prec = prec);
+    if p.sharp {
+        if let Some(c) = char::from_u32(u as u32).filter(|_| u <= u64::from(u32::MAX)) {
+            if is_print(c) {
+                write!(s, " '{}'", c).unwrap();
+            }
         }
-    } else {
-        match params_to_chars(p) {
-            ('#', '_', '+', '_', _) => format!("{:+#width$E}", f, width = p.width),
-            ('_', '_', '+', '_', _) => format!("{:+width$E}", f, width = p.width),
-            ('#', '_', '_', '_', _) => format!("{:#width$E}", f, width = p.width),
-            ('#', '0', '+', '_', _) => format!("{:+#0width$E}", f, width = p.width),
-            ('_', '0', '+', '_', _) => format!("{:+0width$E}", f, width = p.width),
-            ('#', '0', '_', '_', _) => format!("{:#0width$E}", f, width = p.width),
-            ('#', '_', '+', '-', _) => format!("{:<+#width$E}", f, width = p.width),
-            ('_', '_', '+', '-', _) => format!("{:<+width$E}", f, width = p.width),
-            ('#', '_', '_', '-', _) => format!("{:<#width$E}", f, width = p.width),
-            ('#', '0', '+', '-', _) => format!("{:<+#0width$E}", f, width = p.width),
-            ('_', '0', '+', '-', _) => format!("{:<+0width$E}", f, width = p.width),
-            ('#', '0', '_', '-', _) => format!("{:<#0width$E}", f, width = p.width),
-            (_, _, _, _, _) => format!("{:width$E}", f, width = p.width),
+    }
+    pad_with(p, &s, false)
+}
+
+fn fmt_float(p: &FormatParams, verb: char, f: f64) -> String {
+    let verb = if verb == 'v' { 'g' } else { verb };
+    let prec = p.precision.or(match verb {
+        'e' | 'E' | 'f' | 'F' => Some(6),
+        _ => None,
+    });
+    let mut num = format_float(f, verb, prec);
+    // Reserve space for a leading + sign.
+    if !num.starts_with('-') && !num.starts_with('+') {
+        num.insert(0, '+');
+    }
+    if p.space && num.starts_with('+') && !p.plus {
+        num.replace_range(..1, " ");
+    }
+    // Infinities and NaN don't look like a number, so they are never padded with zeros.
+    if num[1..].starts_with('I') || num[1..].starts_with('N') {
+        if num[1..].starts_with('N') && !p.space && !p.plus {
+            num.remove(0);
         }
+        return pad_with(p, &num, false);
     }
+    if p.sharp && verb != 'b' {
+        num = sharp_float(&num, verb, prec);
+    }
+    if p.plus || !num.starts_with('+') {
+        // Zero padding goes between the sign and the number.
+        if p.zero && p.width > num.len() {
+            let (sign, digits) = num.split_at(1);
+            return format!("{}{}{}", sign, "0".repeat(p.width - num.len()), digits);
+        }
+        return pad(p, &num);
+    }
+    pad(p, &num[1..])
 }
 
-fn escape_char(c: char) -> String {
-    let mut s = c.escape_default().to_string();
-    if s.starts_with(r"\u") {
-        s = s.replace("{", "").replace("}", "");
+/// The `#` flag forces a decimal point and keeps trailing zeros for `%g`.
+fn sharp_float(num: &str, verb: char, prec: Option<usize>) -> String {
+    let mut digits = match verb {
+        'g' | 'G' | 'x' => prec.unwrap_or(6) as i64,
+        _ => 0,
+    };
+    let mut end = num.len();
+    let mut has_point = false;
+    let mut saw_nonzero = false;
+    for (i, c) in num.char_indices().skip(1) {
+        match c {
+            '.' => has_point = true,
+            'p' | 'P' => {
+                end = i;
+                break;
+            }
+            'e' | 'E' if verb != 'x' && verb != 'X' => {
+                end = i;
+                break;
+            }
+            _ => {
+                if c != '0' {
+                    saw_nonzero = true;
+                }
+                if saw_nonzero {
+                    digits -= 1;
+                }
+            }
+        }
+    }
+    let mut s = num[..end].to_owned();
+    if !has_point {
+        if s.len() == 2 && s.ends_with('0') {
+            digits -= 1;
+        }
+        s.push('.');
+    }
+    while digits > 0 {
+        s.push('0');
+        digits -= 1;
     }
+    s.push_str(&num[end..]);
     s
 }
 
-struct Hexer<'a> {
-    s: &'a str,
+/// An implementation of golang's `strconv.FormatFloat` for 64 bit floats. A precision of `None`
+/// yields the shortest representation that round trips.
+fn format_float(f: f64, verb: char, prec: Option<usize>) -> String {
+    if f.is_nan() {
+        return String::from("NaN");
+    }
+    if f.is_infinite() {
+        return String::from(if f > 0.0 { "+Inf" } else { "-Inf" });
+    }
+    let sign = if f.is_sign_negative() { "-" } else { "" };
+    let a = f.abs();
+    let body = match verb {
+        'b' => {
+            let (mant, exp) = decompose(a);
+            format!("{}p{:+}", mant, exp - 52)
+        }
+        'x' | 'X' => fmt_x(a, prec, verb),
+        'f' | 'F' => match prec {
+            Some(pr) => format!("{:.*}", pr, a),
+            None => format!("{}", a),
+        },
+        'e' | 'E' => {
+            let (d, dp) = decimal_digits(a, prec.map(|pr| pr + 1));
+            let prec = prec.unwrap_or_else(|| d.len().max(1) - 1);
+            fmt_e(&d, dp, prec, verb)
+        }
+        _ => {
+            let shortest = prec.is_none();
+            let mut prec = prec.map(|pr| pr.max(1));
+            let (d, dp) = decimal_digits(a, prec);
+            let nd = d.len();
+            let mut eprec = prec.unwrap_or(nd);
+            if eprec > nd && nd as i64 >= dp {
+                eprec = nd;
+            }
+            // %e is used if the exponent from the conversion is less than -4 or greater than
+            // or equal to the precision. The shortest format uses a precision of 6 for this.
+            if shortest {
+                eprec = 6;
+            }
+            let exp = dp - 1;
+            let echar = if verb == 'G' { 'E' } else { 'e' };
+            if exp < -4 || exp >= eprec as i64 {
+                let pr = prec.unwrap_or(nd).min(nd);
+                fmt_e(&d, dp, pr.max(1) - 1, echar)
+            } else {
+                if prec.filter(|&pr| pr as i64 <= dp).is_none() {
+                    prec = Some(nd);
+                }
+                let pr = prec.unwrap_or(nd) as i64;
+                fmt_f(&d, dp, (pr - dp).max(0) as usize)
+            }
+        }
+    };
+    format!("{}{}", sign, body)
+}
+
+/// Returns the significant decimal digits (without trailing zeros) and the position of the
+/// decimal point of `a`, rounded to `digits` significant digits if given.
+fn decimal_digits(a: f64, digits: Option<usize>) -> (String, i64) {
+    let s = match digits {
+        Some(n) => format!("{:.*e}", n.max(1) - 1, a),
+        None => format!("{:e}", a),
+    };
+    let e = s.find('e').unwrap_or(s.len());
+    let exp: i64 = s[e + 1..].parse().unwrap_or(0);
+    let d = s[..e]
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .trim_end_matches('0')
+        .to_owned();
+    let dp = if d.is_empty() { 0 } else { exp + 1 };
+    (d, dp)
 }
 
-impl<'a> From<&'a str> for Hexer<'a> {
-    fn from(s: &'a str) -> Self {
-        Hexer { s }
+// %e: d.ddddde±dd
+fn fmt_e(d: &str, dp: i64, prec: usize, echar: char) -> String {
+    let mut s = String::new();
+    s.push(d.chars().next().unwrap_or('0'));
+    if prec > 0 {
+        s.push('.');
+        let more = d.get(1..).unwrap_or("");
+        s.push_str(&more[..more.len().min(prec)]);
+        for _ in more.len().min(prec)..prec {
+            s.push('0');
+        }
     }
+    let exp = if d.is_empty() { 0 } else { dp - 1 };
+    write!(
+        s,
+        "{}{}{:02}",
+        echar,
+        if exp < 0 { '-' } else { '+' },
+        exp.abs()
+    )
+    .unwrap();
+    s
 }
 
-impl<'a> fmt::UpperHex for Hexer<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for u in self.s.as_bytes() {
-            write!(f, "{:X}", u)?
+// %f: ddddddd.ddddd
+fn fmt_f(d: &str, dp: i64, prec: usize) -> String {
+    let digit = |i: i64| {
+        if i >= 0 && (i as usize) < d.len() {
+            d.as_bytes()[i as usize] as char
+        } else {
+            '0'
+        }
+    };
+    let mut s = String::new();
+    if dp > 0 {
+        for i in 0..dp {
+            s.push(digit(i));
+        }
+    } else {
+        s.push('0');
+    }
+    if prec > 0 {
+        s.push('.');
+        for i in 0..prec as i64 {
+            s.push(digit(dp + i));
         }
-        Ok(())
     }
+    s
 }
 
-impl<'a> fmt::LowerHex for Hexer<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for u in self.s.as_bytes() {
-            write!(f, "{:x}", u)?
+// %x: 0x1.yyyyyyp±dd
+fn fmt_x(a: f64, prec: Option<usize>, verb: char) -> String {
+    let (mut mant, mut exp) = decompose(a);
+    if mant == 0 {
+        exp = 0;
+    }
+    // Shift digits so the leading 1 (if any) is at bit 1<<60.
+    mant <<= 60 - 52;
+    while mant != 0 && mant & (1 << 60) == 0 {
+        mant <<= 1;
+        exp -= 1;
+    }
+    if let Some(pr) = prec.filter(|&pr| pr < 15) {
+        let shift = pr as u32 * 4;
+        let extra = (mant << shift) & ((1 << 60) - 1);
+        mant >>= 60 - shift;
+        if extra | (mant & 1) > 1 << 59 {
+            mant += 1;
+        }
+        mant <<= 60 - shift;
+        if mant & (1 << 61) != 0 {
+            // Wrapped around.
+            mant >>= 1;
+            exp += 1;
         }
-        Ok(())
     }
+    let digits = if verb == 'X' { UDIGITS } else { LDIGITS };
+    let mut s = String::from("0");
+    s.push(verb);
+    s.push(if (mant >> 60) & 1 == 1 { '1' } else { '0' });
+    mant <<= 4;
+    let frac_len = match prec {
+        None if mant != 0 => Some(16 - mant.trailing_zeros() as usize / 4),
+        None => None,
+        Some(0) => None,
+        Some(pr) => Some(pr),
+    };
+    if let Some(n) = frac_len {
+        s.push('.');
+        for _ in 0..n {
+            s.push(digits[((mant >> 60) & 15) as usize] as char);
+            mant <<= 4;
+        }
+    }
+    let pchar = if verb == 'X' { 'P' } else { 'p' };
+    write!(
+        s,
+        "{}{}{:02}",
+        pchar,
+        if exp < 0 { '-' } else { '+' },
+        exp.abs()
+    )
+    .unwrap();
+    s
+}
+
+/// Splits a finite, positive float into mantissa and exponent.
+fn decompose(a: f64) -> (u64, i64) {
+    let bits = a.to_bits();
+    let mut exp = ((bits >> 52) & 0x7ff) as i64;
+    let mut mant = bits & ((1 << 52) - 1);
+    if exp == 0 {
+        // Denormal.
+        exp += 1;
+    } else {
+        mant |= 1 << 52;
+    }
+    (mant, exp - 1023)
+}
+
+fn fmt_array(p: &FormatParams, verb: char, a: &[Value]) -> String {
+    let elems = a.iter().map(|v| print(p, verb, v));
+    if p.sharp_v {
+        format!(
+            "[]interface {{}}{{{}}}",
+            elems.collect::<Vec<_>>().join(", ")
+        )
+    } else {
+        format!("[{}]", elems.collect::<Vec<_>>().join(" "))
+    }
+}
+
+fn fmt_map(p: &FormatParams, verb: char, m: &HashMap<String, Value>) -> String {
+    let mut keys = m.keys().collect::<Vec<_>>();
+    keys.sort();
+    let key_params = FormatParams {
+        sharp_v: p.sharp_v,
+        ..FormatParams::default()
+    };
+    let elems = keys.into_iter().map(|k| {
+        let key = Value::from(k.as_str());
+        format!(
+            "{}:{}",
+            print(&key_params, verb, &key),
+            print(p, verb, &m[k])
+        )
+    });
+    if p.sharp_v {
+        format!(
+            "map[string]interface {{}}{{{}}}",
+            elems.collect::<Vec<_>>().join(", ")
+        )
+    } else {
+        format!("map[{}]", elems.collect::<Vec<_>>().join(" "))
+    }
+}
+
+fn fmt_object(p: &FormatParams, verb: char, o: &HashMap<String, Value>) -> String {
+    let mut keys = o.keys().collect::<Vec<_>>();
+    keys.sort();
+    let fields = keys.into_iter().map(|k| {
+        if p.plus_v || p.sharp_v {
+            format!("{}:{}", k, print(p, verb, &o[k]))
+        } else {
+            print(p, verb, &o[k])
+        }
+    });
+    let sep = if p.sharp_v { ", " } else { " " };
+    format!("{{{}}}", fields.collect::<Vec<_>>().join(sep))
 }
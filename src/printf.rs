@@ -1,26 +1,25 @@
-use std::char;
+use gtmpl_value::Value;
 
-use gtmpl_value::{FromValue, Value};
-
-use crate::print_verb::print;
+use crate::print_verb::{print, type_name};
+use crate::utils::{to_num, Num};
 
+/// Formats `args` like golang's `fmt.Sprintf`.
+///
+/// Mistakes in the format string are not an error but reported inline like golang does, e.g.
+/// `%!d(MISSING)` for a missing argument or `%!(EXTRA string=foo)` for unused arguments.
 pub fn sprintf(s: &str, args: &[Value]) -> Result<String, String> {
-    let tokens = tokenize(s)?;
-    let mut fmt = String::new();
-    let mut i = 0;
-    let mut index = 0;
-    for t in tokens {
-        fmt.push_str(&s[i..t.start]);
-        let (s, idx) = process_verb(&s[t.start + 1..t.end], t.typ, args, index)?;
-        fmt.push_str(&s);
-        index = idx;
-        i = t.end + 1;
-    }
-    fmt.push_str(&s[i..]);
-    Ok(fmt)
+    let mut p = Printer {
+        args,
+        reordered: false,
+        good_arg_num: true,
+        buf: String::new(),
+    };
+    p.do_printf(s);
+    Ok(p.buf)
 }
 
-struct FormatArg {
+#[allow(dead_code)]
+pub(crate) struct FormatArg {
     pub start: usize,
     pub end: usize,
     pub typ: char,
@@ -28,7 +27,7 @@ struct FormatArg {
 
 static TYPS: &str = "vVtTbcdoqxXUeEfFgGsp";
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct FormatParams {
     pub sharp: bool,
     pub zero: bool,
@@ -37,142 +36,234 @@ pub struct FormatParams {
     pub space: bool,
     pub width: usize,
     pub precision: Option<usize>,
+    /// `%#v`, the `#` flag is moved here for the `v` verb.
+    pub sharp_v: bool,
+    /// `%+v`, the `+` flag is moved here for the `v` verb.
+    pub plus_v: bool,
 }
 
-fn process_verb(
-    s: &str,
-    typ: char,
-    args: &[Value],
-    mut index: usize,
-) -> Result<(String, usize), String> {
-    let mut params = FormatParams::default();
-    let mut complex = false;
-    let mut pos = 0;
-    for (i, c) in s.chars().enumerate() {
-        match c {
-            '#' => params.sharp = true,
-            '0' => params.zero = true,
-            '+' => params.plus = true,
-            '-' => {
-                params.minus = true;
-                // Golang does not pad with zeros to the right.
-                params.zero = false;
+struct Printer<'a> {
+    args: &'a [Value],
+    reordered: bool,
+    good_arg_num: bool,
+    buf: String,
+}
+
+impl<'a> Printer<'a> {
+    fn do_printf(&mut self, s: &str) {
+        let b = s.as_bytes();
+        let end = b.len();
+        let mut arg_num = 0;
+        let mut i = 0;
+        while i < end {
+            self.good_arg_num = true;
+            let last = i;
+            while i < end && b[i] != b'%' {
+                i += 1;
             }
-            ' ' => params.space = true,
-            _ => {
-                pos = i;
-                complex = true;
+            self.buf.push_str(&s[last..i]);
+            if i >= end {
                 break;
             }
-        }
-    }
-    if complex {
-        let mut after_index = false;
-        let arg_num = parse_index(&s[pos..])?.map(|(i, till)| {
-            pos += till;
-            after_index = true;
-            index = i;
-            i
-        });
-        if s[pos..].starts_with('*') {
-            pos += 1;
-            let arg_num = arg_num.unwrap_or_else(|| {
-                let i = index;
-                index += 1;
-                i
-            });
-            if let Some(width) = args.get(arg_num).and_then(|v| i64::from_value(v)) {
-                if width < 0 {
-                    params.minus = true;
+            // Skip the %.
+            i += 1;
+
+            let mut params = FormatParams::default();
+            while i < end {
+                match b[i] {
+                    b'#' => params.sharp = true,
                     // Golang does not pad with zeros to the right.
-                    params.zero = false;
+                    b'0' => params.zero = !params.minus,
+                    b'+' => params.plus = true,
+                    b'-' => {
+                        params.minus = true;
+                        params.zero = false;
+                    }
+                    b' ' => params.space = true,
+                    _ => break,
                 }
-                params.width = width.abs() as usize;
-            }
-            after_index = false;
-        } else if let Some((width, till)) = parse_num(&s[pos..])? {
-            if after_index {
-                return Err("width after index (e.g. %[3]2d)".to_owned());
+                i += 1;
             }
-            pos += till;
-            params.width = width;
-        }
 
-        if pos + 1 < s.len() && s[pos..].starts_with('.') {
-            pos += 1;
-            if after_index {
-                return Err("precision after index (e.g. %[3].2d)".to_owned());
+            let (n, till, mut after_index) = self.arg_number(arg_num, s, i);
+            arg_num = n;
+            i = till;
+
+            if i < end && b[i] == b'*' {
+                i += 1;
+                let (width, n) = self.int_from_arg(arg_num);
+                arg_num = n;
+                match width {
+                    Some(width) => {
+                        if width < 0 {
+                            params.minus = true;
+                            params.zero = false;
+                        }
+                        params.width = width.unsigned_abs() as usize;
+                    }
+                    None => self.buf.push_str("%!(BADWIDTH)"),
+                }
+                after_index = false;
+            } else if let Some((width, till)) = parse_num(&s[i..]) {
+                if after_index {
+                    // Width after index (e.g. %[3]2d).
+                    self.good_arg_num = false;
+                }
+                params.width = width;
+                i += till;
             }
 
-            let arg_num = parse_index(&s[pos..])?.map(|(i, till)| {
-                pos += till;
-                after_index = true;
-                index = i;
-                i
-            });
-            if s[pos..].starts_with('*') {
-                pos += 1;
-                let arg_num = arg_num.unwrap_or_else(|| {
-                    let i = index;
-                    index += 1;
-                    i
-                });
-                if let Some(prec) = args.get(arg_num).and_then(|v| i64::from_value(v)) {
-                    if prec < 0 {
-                        params.precision = None;
+            if i + 1 < end && b[i] == b'.' {
+                i += 1;
+                if after_index {
+                    // Precision after index (e.g. %[3].2d).
+                    self.good_arg_num = false;
+                }
+                let (n, till, found) = self.arg_number(arg_num, s, i);
+                arg_num = n;
+                i = till;
+                after_index = found;
+                if i < end && b[i] == b'*' {
+                    i += 1;
+                    let (prec, n) = self.int_from_arg(arg_num);
+                    arg_num = n;
+                    match prec {
+                        // Negative precision arguments don't make sense.
+                        Some(prec) if prec >= 0 => params.precision = Some(prec as usize),
+                        _ => self.buf.push_str("%!(BADPREC)"),
                     }
-                    params.precision = Some(prec.abs() as usize);
+                    after_index = false;
+                } else {
+                    let (prec, till) = parse_num(&s[i..]).unwrap_or((0, 0));
+                    params.precision = Some(prec);
+                    i += till;
                 }
-            } else if let Some((prec, till)) = parse_num(&s[pos..])? {
-                if after_index {
-                    return Err("precision after index (e.g. %[3].2d)".to_owned());
+            }
+
+            if !after_index {
+                let (n, till, _) = self.arg_number(arg_num, s, i);
+                arg_num = n;
+                i = till;
+            }
+
+            let verb = match s[i..].chars().next() {
+                Some(c) => c,
+                None => {
+                    self.buf.push_str("%!(NOVERB)");
+                    break;
+                }
+            };
+            i += verb.len_utf8();
+
+            if verb == '%' {
+                self.buf.push('%');
+            } else if !self.good_arg_num {
+                self.buf.push_str(&format!("%!{}(BADINDEX)", verb));
+            } else if arg_num >= self.args.len() {
+                self.buf.push_str(&format!("%!{}(MISSING)", verb));
+            } else {
+                if verb == 'v' {
+                    params.sharp_v = params.sharp;
+                    params.sharp = false;
+                    params.plus_v = params.plus;
+                    params.plus = false;
                 }
-                pos += till;
-                params.precision = Some(prec);
+                self.buf
+                    .push_str(&print(&params, verb, &self.args[arg_num]));
+                arg_num += 1;
             }
         }
+
+        if !self.reordered && arg_num < self.args.len() {
+            let extra = self.args[arg_num..]
+                .iter()
+                .map(|arg| match *arg {
+                    Value::Nil | Value::NoValue => String::from("<nil>"),
+                    _ => format!(
+                        "{}={}",
+                        type_name(arg),
+                        print(&FormatParams::default(), 'v', arg)
+                    ),
+                })
+                .collect::<Vec<_>>();
+            self.buf
+                .push_str(&format!("%!(EXTRA {})", extra.join(", ")));
+        }
     }
 
-    let arg_num = if let Some((i, _)) = parse_index(&s[pos..])? {
-        index = i;
-        i
-    } else {
-        let i = index;
-        index += 1;
-        i
-    };
+    /// Returns the next argument index, the position after a `[n]` and whether one was
+    /// found.
+    fn arg_number(&mut self, arg_num: usize, s: &str, i: usize) -> (usize, usize, bool) {
+        if !s[i..].starts_with('[') {
+            return (arg_num, i, false);
+        }
+        self.reordered = true;
+        match parse_index(&s[i..]) {
+            Ok(Some((index, till))) if index < self.args.len() => (index, i + till, true),
+            Ok(Some((_, till))) => {
+                self.good_arg_num = false;
+                (arg_num, i + till, true)
+            }
+            _ => {
+                self.good_arg_num = false;
+                let till = s[i..].find(']').map_or(1, |till| till + 1);
+                (arg_num, i + till, false)
+            }
+        }
+    }
 
-    if arg_num < args.len() {
-        return print(&params, typ, &args[arg_num]).map(|s| (s, index));
+    /// Returns the argument as width or precision if it is an integer and the next argument
+    /// index.
+    fn int_from_arg(&self, arg_num: usize) -> (Option<i64>, usize) {
+        match self.args.get(arg_num) {
+            Some(Value::Number(n)) => {
+                let num = match to_num(n) {
+                    Num::U(u) if u <= MAX_NUM as u64 => Some(u as i64),
+                    Num::I(i) if i.abs() <= MAX_NUM => Some(i),
+                    _ => None,
+                };
+                (num, arg_num + 1)
+            }
+            Some(_) => (None, arg_num + 1),
+            None => (None, arg_num),
+        }
     }
-    Err(format!("unable to process verb: {}", s))
 }
 
+/// Widths and precisions larger than this are treated as invalid.
+const MAX_NUM: i64 = 1_000_000;
+
 fn parse_index(s: &str) -> Result<Option<(usize, usize)>, String> {
     if s.starts_with('[') {
         let till = s.find(']').ok_or_else(|| format!("missing ] in {}", s))?;
-        s[1..till]
-            .parse::<usize>()
-            .map(|u| Some((u - 1, till + 1)))
-            .map_err(|e| format!("unable to parse index: {}", e))
+        match s[1..till].parse::<usize>() {
+            Ok(0) => Err(String::from("index must be greater than 0")),
+            Ok(u) => Ok(Some((u - 1, till + 1))),
+            Err(e) => Err(format!("unable to parse index: {}", e)),
+        }
     } else {
         Ok(None)
     }
 }
 
-fn parse_num(s: &str) -> Result<Option<(usize, usize)>, String> {
-    let till = s.find(|c: char| !c.is_digit(10)).unwrap_or_else(|| s.len());
+fn parse_num(s: &str) -> Option<(usize, usize)> {
+    let till = s
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| s.len());
     if till > 0 {
         s[..till]
             .parse()
-            .map(|u| Some((u, till)))
-            .map_err(|e| format!("unable to parse width: {}", e))
+            .ok()
+            .filter(|&u: &usize| u <= MAX_NUM as usize)
+            .map(|u| (u, till))
     } else {
-        Ok(None)
+        None
     }
 }
 
-fn tokenize(s: &str) -> Result<Vec<FormatArg>, String> {
+/// Strictly splits a format string into its verbs. Unlike `sprintf` this fails for unterminated
+/// verbs.
+pub(crate) fn tokenize(s: &str) -> Result<Vec<FormatArg>, String> {
     let mut iter = s.char_indices().peekable();
     let mut args = Vec::new();
     loop {
@@ -205,16 +296,6 @@ fn tokenize(s: &str) -> Result<Vec<FormatArg>, String> {
     Ok(args)
 }
 
-pub fn params_to_chars(params: &FormatParams) -> (char, char, char, char, char) {
-    (
-        if params.sharp { '#' } else { '_' },
-        if params.zero { '0' } else { '_' },
-        if params.plus { '+' } else { '_' },
-        if params.minus { '-' } else { '_' },
-        if params.space { ' ' } else { '_' },
-    )
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -226,10 +307,11 @@ mod test {
         let s = s.unwrap();
         assert_eq!(s, r"foobar2000");
 
+        // Like golang the + flag only adds field names for %v.
         let s = sprintf("%+0v", &vec![1.into()]);
         assert!(s.is_ok());
         let s = s.unwrap();
-        assert_eq!(s, r"+1");
+        assert_eq!(s, r"1");
     }
 
     #[test]
@@ -300,6 +382,96 @@ mod test {
         assert_eq!(s, r"+101");
     }
 
+    #[test]
+    fn test_sprintf_float() {
+        let s = sprintf(
+            "%g %G %g",
+            &[0.000_012_5.into(), 1.25e-7.into(), 1.5.into()],
+        );
+        assert_eq!(s.unwrap(), r"1.25e-05 1.25E-07 1.5");
+
+        let s = sprintf(
+            "%e %.2f %f %v",
+            &[1234.5678.into(), 0.125.into(), 1.5.into(), 2.5.into()],
+        );
+        assert_eq!(s.unwrap(), r"1.234568e+03 0.12 1.500000 2.5");
+
+        let s = sprintf("%x %X %.1x", &[1.5.into(), 1.5.into(), 1.96875.into()]);
+        assert_eq!(s.unwrap(), r"0x1.8p+00 0X1.8P+00 0x1.0p+01");
+
+        let s = sprintf(
+            "%08.3f|%-8.2f|%+.1e",
+            &[1.23456.into(), 2.5.into(), 12345.6.into()],
+        );
+        assert_eq!(s.unwrap(), r"0001.235|2.50    |+1.2e+04");
+
+        let s = sprintf("%v %v %d", &[1e300.into(), (-1e300).into(), 1e300.into()]);
+        assert_eq!(s.unwrap(), r"1e+300 -1e+300 %!d(float64=1e+300)");
+    }
+
+    #[test]
+    fn test_sprintf_type() {
+        let s = sprintf(
+            "%T %T %T %T %T",
+            &[1.into(), 1.5.into(), "foo".into(), true.into(), Value::Nil],
+        );
+        assert_eq!(s.unwrap(), r"int float64 string bool <nil>");
+    }
+
+    #[test]
+    fn test_sprintf_composite() {
+        let s = sprintf("%v %v", &[Value::Nil, vec![1, 2].into()]);
+        assert_eq!(s.unwrap(), r"<nil> [1 2]");
+
+        let mut m = ::std::collections::HashMap::new();
+        m.insert("b".to_owned(), 2);
+        m.insert("a".to_owned(), 1);
+        let s = sprintf("%v %d", &[m.clone().into(), m.into()]);
+        assert_eq!(
+            s.unwrap(),
+            r"map[a:1 b:2] map[%!d(string=a):1 %!d(string=b):2]"
+        );
+
+        let s = sprintf("%#v", &[vec!["a"].into()]);
+        assert_eq!(s.unwrap(), r#"[]interface {}{"a"}"#);
+    }
+
+    #[test]
+    fn test_sprintf_quote() {
+        let s = sprintf("%q %+q %#q", &["a\"b\n".into(), "☺".into(), "foo".into()]);
+        assert_eq!(s.unwrap(), r#""a\"b\n" "\u263a" `foo`"#);
+
+        let s = sprintf("%q", &[0x263a.into()]);
+        assert_eq!(s.unwrap(), r"'☺'");
+    }
+
+    #[test]
+    fn test_sprintf_bad() {
+        let s = sprintf("%s %d", &[5.into(), "foo".into()]);
+        assert_eq!(s.unwrap(), r"%!s(int=5) %!d(string=foo)");
+
+        let s = sprintf("%d %d", &[1.into()]);
+        assert_eq!(s.unwrap(), r"1 %!d(MISSING)");
+
+        let s = sprintf("%d", &[1.into(), "foo".into(), Value::Nil]);
+        assert_eq!(s.unwrap(), r"1%!(EXTRA string=foo, <nil>)");
+
+        let s = sprintf("foo%", &[]);
+        assert_eq!(s.unwrap(), r"foo%!(NOVERB)");
+
+        let s = sprintf("%[3]d %[0]d", &[1.into()]);
+        assert_eq!(s.unwrap(), r"%!d(BADINDEX) %!d(BADINDEX)");
+
+        let s = sprintf("%*d", &["foo".into(), 1.into()]);
+        assert_eq!(s.unwrap(), r"%!(BADWIDTH)1");
+    }
+
+    #[test]
+    fn test_sprintf_unicode_width() {
+        let s = sprintf("%5s|%-5s|%.2s", &["äö".into(), "☺".into(), "☺☺☺".into()]);
+        assert_eq!(s.unwrap(), r"   äö|☺    |☺☺");
+    }
+
     #[test]
     fn test_tokenize() {
         let t = tokenize("foobar%6.2ffoobar");
@@ -0,0 +1,287 @@
+//! A set of file backed templates which is reloaded when the files change.
+//!
+//! [`TemplateRegistry`](struct.TemplateRegistry.html) polls the modification times of its
+//! files, so no platform specific file notifications are required. Changed files are parsed
+//! into a new template set which replaces the current one only if all files parse. Otherwise
+//! the last good set keeps serving and the error is available from
+//! [`error`](struct.TemplateRegistry.html#method.error).
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, TemplateNaming, TemplateRegistry};
+//!
+//! let dir = std::env::temp_dir().join("gtmpl-registry-example");
+//! std::fs::create_dir_all(&dir).unwrap();
+//! std::fs::write(dir.join("index.tmpl"), "{{ . }}").unwrap();
+//!
+//! let mut registry = TemplateRegistry::new(|tmpl| tmpl.set_naming(TemplateNaming::RelativePath));
+//! registry.add_dir(&dir, "tmpl").unwrap();
+//! let output = registry.render("index.tmpl", &Context::from("hot").unwrap());
+//! assert_eq!(&output.unwrap(), "hot");
+//! ```
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::exec::Context;
+use crate::files::{dir_files, glob_files};
+use crate::template::Template;
+
+enum Source {
+    Files(Vec<PathBuf>),
+    Glob(String),
+    Dir(PathBuf, String),
+}
+
+// Modification time and length of every file, `None` for missing files.
+type Snapshot = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+type Setup = Box<dyn Fn(&mut Template) + Send + Sync>;
+
+/// A set of templates parsed from files and reparsed when the files change.
+pub struct TemplateRegistry {
+    setup: Setup,
+    sources: Vec<Source>,
+    interval: Duration,
+    current: RwLock<Arc<Template>>,
+    error: RwLock<Option<String>>,
+    snapshot: Mutex<Snapshot>,
+    last_poll: Mutex<Option<Instant>>,
+}
+
+impl TemplateRegistry {
+    /// Creates an empty registry. `setup` prepares every new template set before files are
+    /// parsed into it, e.g. by adding functions.
+    pub fn new<F>(setup: F) -> TemplateRegistry
+    where
+        F: Fn(&mut Template) + Send + Sync + 'static,
+    {
+        TemplateRegistry {
+            setup: Box::new(setup),
+            sources: vec![],
+            interval: Duration::from_secs(1),
+            current: RwLock::new(Arc::new(Template::default())),
+            error: RwLock::new(None),
+            snapshot: Mutex::new(vec![]),
+            last_poll: Mutex::new(None),
+        }
+    }
+
+    /// Sets the minimum time between two checks for changed files by `render`. Defaults to
+    /// one second, zero checks on every call.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Adds files like [`Template::parse_files`](struct.Template.html#method.parse_files)
+    /// and parses all files of the registry.
+    pub fn add_files<P: AsRef<Path>>(&mut self, paths: &[P]) -> Result<(), String> {
+        let paths = paths.iter().map(|p| p.as_ref().to_path_buf()).collect();
+        self.add_source(Source::Files(paths))
+    }
+
+    /// Adds files like [`Template::parse_glob`](struct.Template.html#method.parse_glob) and
+    /// parses all files of the registry. Files created later are picked up on reload.
+    pub fn add_glob(&mut self, pattern: &str) -> Result<(), String> {
+        self.add_source(Source::Glob(pattern.to_owned()))
+    }
+
+    /// Adds files like [`Template::parse_dir`](struct.Template.html#method.parse_dir) and
+    /// parses all files of the registry. Files created later are picked up on reload.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, root: P, extension: &str) -> Result<(), String> {
+        self.add_source(Source::Dir(
+            root.as_ref().to_path_buf(),
+            extension.to_owned(),
+        ))
+    }
+
+    fn add_source(&mut self, source: Source) -> Result<(), String> {
+        self.sources.push(source);
+        if let Err(e) = self.reload() {
+            self.sources.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Returns the current template set.
+    pub fn template(&self) -> Arc<Template> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(e) => e.into_inner().clone(),
+        }
+    }
+
+    /// Returns the error of the last reparse if it failed. The previous template set is
+    /// still in use then.
+    pub fn error(&self) -> Option<String> {
+        self.error.read().ok().and_then(|e| e.clone())
+    }
+
+    /// Renders the template `name` after reloading changed files, see
+    /// [`set_poll_interval`](#method.set_poll_interval).
+    pub fn render(&self, name: &str, data: &Context) -> Result<String, String> {
+        let due = match self.last_poll.lock() {
+            Ok(mut last) => {
+                let due = last.is_none_or(|l| l.elapsed() >= self.interval);
+                if due {
+                    *last = Some(Instant::now());
+                }
+                due
+            }
+            Err(_) => true,
+        };
+        if due {
+            self.poll();
+        }
+        self.template().render_template(name, data)
+    }
+
+    /// Checks the files for changes and reparses them if needed. Returns `true` if a new
+    /// template set is in use.
+    pub fn poll(&self) -> bool {
+        let snapshot = match self.snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                self.set_error(Some(e));
+                return false;
+            }
+        };
+        let changed = self.snapshot.lock().map(|s| *s != snapshot).unwrap_or(true);
+        changed && self.reload().is_ok()
+    }
+
+    fn files(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files = vec![];
+        for source in &self.sources {
+            match *source {
+                Source::Files(ref paths) => files.extend(paths.iter().cloned()),
+                Source::Glob(ref pattern) => files.extend(glob_files(pattern)?),
+                Source::Dir(ref root, ref ext) => files.extend(dir_files(root, ext)?),
+            }
+        }
+        Ok(files)
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, String> {
+        Ok(self
+            .files()?
+            .into_iter()
+            .map(|path| {
+                let meta = fs::metadata(&path)
+                    .ok()
+                    .and_then(|m| m.modified().ok().map(|t| (t, m.len())));
+                (path, meta)
+            })
+            .collect())
+    }
+
+    // Parses all files into a new template set and replaces the current one on success.
+    fn reload(&self) -> Result<(), String> {
+        let snapshot = self.snapshot();
+        let result = snapshot.clone().and_then(|_| {
+            let mut tmpl = Template::default();
+            (self.setup)(&mut tmpl);
+            for source in &self.sources {
+                match *source {
+                    Source::Files(ref paths) => tmpl.parse_files(paths)?,
+                    Source::Glob(ref pattern) => tmpl.parse_glob(pattern)?,
+                    Source::Dir(ref root, ref ext) => tmpl.parse_dir(root, ext)?,
+                }
+            }
+            Ok(tmpl)
+        });
+        // Broken files are only parsed again once they change.
+        if let (Ok(snapshot), Ok(mut s)) = (snapshot, self.snapshot.lock()) {
+            *s = snapshot;
+        }
+        match result {
+            Ok(tmpl) => {
+                if let Ok(mut current) = self.current.write() {
+                    *current = Arc::new(tmpl);
+                }
+                self.set_error(None);
+                Ok(())
+            }
+            Err(e) => {
+                self.set_error(Some(e.clone()));
+                Err(e)
+            }
+        }
+    }
+
+    fn set_error(&self, error: Option<String>) {
+        if let Ok(mut e) = self.error.write() {
+            *e = error;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_mocked {
+    use super::*;
+
+    fn setup(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gtmpl-registry-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("page.tmpl"), r#"<{{ template "part.tmpl" . }}>"#).unwrap();
+        fs::write(dir.join("part.tmpl"), "v1 {{ . }}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_sync() {
+        fn assert_sync<T: Send + Sync>() {}
+        assert_sync::<TemplateRegistry>();
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = setup("reload");
+        let mut registry = TemplateRegistry::new(|_| {});
+        registry.set_poll_interval(Duration::from_secs(0));
+        registry.add_dir(&dir, "tmpl").unwrap();
+        let ctx = Context::from(1).unwrap();
+        assert_eq!(
+            registry.render("page.tmpl", &ctx),
+            Ok(String::from("<v1 1>"))
+        );
+        assert!(!registry.poll());
+
+        fs::write(dir.join("part.tmpl"), "version 2 {{ . }}").unwrap();
+        assert_eq!(
+            registry.render("page.tmpl", &ctx),
+            Ok(String::from("<version 2 1>"))
+        );
+
+        fs::write(dir.join("part.tmpl"), "{{ if }}").unwrap();
+        assert_eq!(
+            registry.render("page.tmpl", &ctx),
+            Ok(String::from("<version 2 1>"))
+        );
+        assert!(registry.error().unwrap().contains("part.tmpl"));
+
+        fs::write(dir.join("new.tmpl"), "new").unwrap();
+        fs::write(dir.join("part.tmpl"), "v3").unwrap();
+        assert!(registry.poll());
+        assert_eq!(registry.error(), None);
+        assert_eq!(registry.render("new.tmpl", &ctx), Ok(String::from("new")));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_initial_error() {
+        let dir = setup("initial");
+        fs::write(dir.join("broken.tmpl"), "{{ end }}").unwrap();
+        let mut registry = TemplateRegistry::new(|_| {});
+        assert!(registry.add_files(&[dir.join("broken.tmpl")]).is_err());
+        assert!(registry
+            .add_files(&[dir.join("page.tmpl"), dir.join("part.tmpl")])
+            .is_ok());
+        assert_eq!(registry.error(), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,7 +1,11 @@
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
-use crate::funcs::BUILTINS;
-use crate::parse::{parse, Tree};
+use crate::exec::{MissingKey, MAX_TEMPLATE_DEPTH};
+use crate::files::TemplateNaming;
+use crate::funcs::{FuncState, BUILTINS};
+use crate::loader::Loader;
+use crate::parse::{parse_with_delims, Tree};
 use gtmpl_value::Func;
 
 /// The main template structure.
@@ -10,6 +14,13 @@ pub struct Template {
     pub text: String,
     pub funcs: HashMap<String, Func>,
     pub tree_set: HashMap<String, Tree>,
+    pub(crate) state: FuncState,
+    pub(crate) naming: TemplateNaming,
+    pub(crate) loader: Option<Arc<dyn Loader>>,
+    pub(crate) loaded: RwLock<HashMap<String, Arc<Tree>>>,
+    pub(crate) delims: (String, String),
+    pub(crate) missing_key: MissingKey,
+    pub(crate) max_depth: usize,
 }
 
 impl Default for Template {
@@ -19,6 +30,13 @@ impl Default for Template {
             text: String::from(""),
             funcs: BUILTINS.iter().map(|&(k, v)| (k.to_owned(), v)).collect(),
             tree_set: HashMap::default(),
+            state: FuncState::default(),
+            naming: TemplateNaming::default(),
+            loader: None,
+            loaded: RwLock::default(),
+            delims: (String::from("{{"), String::from("}}")),
+            missing_key: MissingKey::default(),
+            max_depth: MAX_TEMPLATE_DEPTH,
         }
     }
 }
@@ -77,6 +95,356 @@ impl Template {
             .extend(funcs.iter().cloned().map(|(k, v)| (k.into(), v)));
     }
 
+    /// Adds the Sprig compatible string functions from [`funcs::strings`] to the template.
+    ///
+    /// [`funcs::strings`]: funcs/strings/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_string_funcs();
+    /// tmpl.parse(r#"{{ "hello" | upper | repeat 2 }}"#).unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert_eq!(&output.unwrap(), "HELLOHELLO");
+    /// ```
+    #[cfg(feature = "strings")]
+    pub fn add_string_funcs(&mut self) {
+        self.add_funcs(crate::funcs::strings::STRINGS);
+    }
+
+    /// Adds the Sprig compatible default and flow control functions from [`funcs::defaults`]
+    /// to the template.
+    ///
+    /// [`funcs::defaults`]: funcs/defaults/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_default_funcs();
+    /// tmpl.parse(r#"{{ coalesce 0 "" . }}"#).unwrap();
+    /// let output = tmpl.render(&Context::from("foo").unwrap());
+    /// assert_eq!(&output.unwrap(), "foo");
+    /// ```
+    #[cfg(feature = "defaults")]
+    pub fn add_default_funcs(&mut self) {
+        self.add_funcs(crate::funcs::defaults::DEFAULTS);
+    }
+
+    /// Adds the dictionary functions from [`funcs::dicts`] to the template.
+    ///
+    /// [`funcs::dicts`]: funcs/dicts/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_dict_funcs();
+    /// tmpl.parse(r#"{{ $d := dict "a" 1 }}{{ hasKey $d "a" }}"#).unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert_eq!(&output.unwrap(), "true");
+    /// ```
+    #[cfg(feature = "dicts")]
+    pub fn add_dict_funcs(&mut self) {
+        self.add_funcs(crate::funcs::dicts::DICTS);
+    }
+
+    /// Adds the JSON functions from [`funcs::json`] to the template.
+    ///
+    /// [`funcs::json`]: funcs/json/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_json_funcs();
+    /// tmpl.parse("{{ toJson . }}").unwrap();
+    /// let output = tmpl.render(&Context::from(vec![1, 2]).unwrap());
+    /// assert_eq!(&output.unwrap(), "[1,2]");
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn add_json_funcs(&mut self) {
+        self.add_funcs(crate::funcs::json::JSON);
+    }
+
+    /// Adds the YAML functions from [`funcs::yaml`] to the template.
+    ///
+    /// [`funcs::yaml`]: funcs/yaml/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_yaml_funcs();
+    /// tmpl.parse("{{ toYaml . }}").unwrap();
+    /// let output = tmpl.render(&Context::from(vec![1, 2]).unwrap());
+    /// assert_eq!(&output.unwrap(), "- 1\n- 2");
+    /// ```
+    #[cfg(feature = "yaml")]
+    pub fn add_yaml_funcs(&mut self) {
+        self.add_funcs(crate::funcs::yaml::YAML);
+    }
+
+    /// Adds the encoding and hashing functions from [`funcs::encoding`] to the template.
+    ///
+    /// [`funcs::encoding`]: funcs/encoding/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_encoding_funcs();
+    /// tmpl.parse("{{ sha1sum . }}").unwrap();
+    /// let output = tmpl.render(&Context::from("abc").unwrap());
+    /// assert_eq!(&output.unwrap(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    /// ```
+    #[cfg(feature = "encoding")]
+    pub fn add_encoding_funcs(&mut self) {
+        self.add_funcs(crate::funcs::encoding::ENCODING);
+    }
+
+    /// Adds the regular expression functions from [`funcs::regex`] to the template.
+    ///
+    /// [`funcs::regex`]: funcs/regex/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_regex_funcs();
+    /// tmpl.parse(r#"{{ range . }}{{ regexFind "[0-9]+" . }} {{ end }}"#).unwrap();
+    /// let output = tmpl.render(&Context::from(vec!["a1", "b22"]).unwrap());
+    /// assert_eq!(&output.unwrap(), "1 22 ");
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn add_regex_funcs(&mut self) {
+        self.add_funcs(crate::funcs::regex::REGEX);
+    }
+
+    /// Adds the date and time functions from [`funcs::time`] to the template.
+    ///
+    /// [`funcs::time`]: funcs/time/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_time_funcs();
+    /// tmpl.parse(r#"{{ date "2006-01-02" . }}"#).unwrap();
+    /// let output = tmpl.render(&Context::from(1514764800).unwrap());
+    /// assert_eq!(&output.unwrap(), "2018-01-01");
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn add_time_funcs(&mut self) {
+        self.add_funcs(crate::funcs::time::TIME);
+    }
+
+    /// Replaces the system clock used by the time functions, e.g. for deterministic output
+    /// in tests.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_time_funcs();
+    /// tmpl.set_clock(|| UNIX_EPOCH + Duration::from_secs(3600));
+    /// tmpl.parse(r#"{{ now }} {{ ago 0 }}"#).unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert_eq!(&output.unwrap(), "1970-01-01T01:00:00Z 1h0m0s");
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn set_clock<F>(&mut self, clock: F)
+    where
+        F: Fn() -> std::time::SystemTime + Send + Sync + 'static,
+    {
+        self.state.clock = Some(std::sync::Arc::new(clock));
+    }
+
+    /// Adds the semantic version functions from [`funcs::semver`] to the template.
+    ///
+    /// [`funcs::semver`]: funcs/semver/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_semver_funcs();
+    /// tmpl.parse(r#"{{ (semver .).Minor }} {{ semverCompare "^1.2" . }}"#).unwrap();
+    /// let output = tmpl.render(&Context::from("v1.4.2").unwrap());
+    /// assert_eq!(&output.unwrap(), "4 true");
+    /// ```
+    #[cfg(feature = "semver")]
+    pub fn add_semver_funcs(&mut self) {
+        self.add_funcs(crate::funcs::semver::SEMVER);
+    }
+
+    /// Adds the environment and file system functions from [`funcs::host`] to the template
+    /// and allows them to access the host. File access is confined to `root_dir`.
+    ///
+    /// Only use this for trusted templates, they can read any environment variable and any
+    /// file below `root_dir`.
+    ///
+    /// [`funcs::host`]: funcs/host/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.allow_host_access(std::env::temp_dir()).unwrap();
+    /// tmpl.parse(r#"{{ fileExists "../etc/passwd" }}"#).unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert!(output.is_err());
+    /// ```
+    #[cfg(feature = "host")]
+    pub fn allow_host_access<P: AsRef<std::path::Path>>(
+        &mut self,
+        root_dir: P,
+    ) -> Result<(), String> {
+        let root_dir = root_dir.as_ref();
+        let root = std::fs::canonicalize(root_dir)
+            .map_err(|e| format!("unable to access {}: {}", root_dir.display(), e))?;
+        if !root.is_dir() {
+            return Err(format!("{} is not a directory", root_dir.display()));
+        }
+        self.state.host_root = Some(root);
+        self.add_funcs(crate::funcs::host::HOST);
+        Ok(())
+    }
+
+    /// Adds the random functions from [`funcs::random`] to the template.
+    ///
+    /// [`funcs::random`]: funcs/random/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_random_funcs();
+    /// tmpl.parse(r#"{{ randNumeric 4 | len }}"#).unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert_eq!(&output.unwrap(), "4");
+    /// ```
+    #[cfg(feature = "random")]
+    pub fn add_random_funcs(&mut self) {
+        self.add_funcs(crate::funcs::random::RANDOM);
+    }
+
+    /// Seeds the random functions, e.g. for stable output in snapshot tests. Every execution
+    /// starts from the seed and produces the same output unless the context has its own seed,
+    /// see [`Context::with_seed`](struct.Context.html#method.with_seed).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_random_funcs();
+    /// tmpl.set_seed(42);
+    /// tmpl.parse(r#"{{ uuidv4 }} {{ randAlphaNum 8 }}"#).unwrap();
+    /// let first = tmpl.render(&Context::empty()).unwrap();
+    /// assert_eq!(first, tmpl.render(&Context::empty()).unwrap());
+    /// assert_ne!(first, tmpl.render(&Context::empty().with_seed(7)).unwrap());
+    /// ```
+    #[cfg(feature = "random")]
+    pub fn set_seed(&mut self, seed: u64) {
+        self.state.seed = Some(seed);
+    }
+
+    /// Adds the Helm compatible `include` and `tpl` functions from [`funcs::include`] to the
+    /// template.
+    ///
+    /// [`funcs::include`]: funcs/include/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    ///
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_include_funcs();
+    /// tmpl.parse(r#"{{ tpl .msg . }}"#).unwrap();
+    /// let mut values = HashMap::new();
+    /// values.insert("msg".to_owned(), "Hello {{ .name }}".to_owned());
+    /// values.insert("name".to_owned(), "World".to_owned());
+    /// let output = tmpl.render(&Context::from(values).unwrap());
+    /// assert_eq!(&output.unwrap(), "Hello World");
+    /// ```
+    #[cfg(feature = "include")]
+    pub fn add_include_funcs(&mut self) {
+        self.state.include = true;
+        self.add_funcs(crate::funcs::include::INCLUDE);
+    }
+
+    /// Adds the Sprig compatible list functions from [`funcs::lists`] to the template.
+    ///
+    /// [`funcs::lists`]: funcs/lists/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_list_funcs();
+    /// tmpl.parse("{{ range until 3 }}{{ . }}{{ end }}").unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert_eq!(&output.unwrap(), "012");
+    /// ```
+    #[cfg(feature = "lists")]
+    pub fn add_list_funcs(&mut self) {
+        self.add_funcs(crate::funcs::lists::LISTS);
+    }
+
+    /// Adds the Sprig compatible math functions from [`funcs::math`] to the template.
+    ///
+    /// [`funcs::math`]: funcs/math/index.html
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::Context;
+    ///
+    /// let mut tmpl = gtmpl::Template::default();
+    /// tmpl.add_math_funcs();
+    /// tmpl.parse("{{ add 1 . | max 7 }}").unwrap();
+    /// let output = tmpl.render(&Context::from(41).unwrap());
+    /// assert_eq!(&output.unwrap(), "42");
+    /// ```
+    #[cfg(feature = "math")]
+    pub fn add_math_funcs(&mut self) {
+        self.add_funcs(crate::funcs::math::MATH);
+    }
+
     /// Parse the given `text` as template body.
     ///
     /// ## Example
@@ -86,11 +454,7 @@ impl Template {
     /// tmpl.parse("Hello World!").unwrap();
     /// ```
     pub fn parse<T: Into<String>>(&mut self, text: T) -> Result<(), String> {
-        let tree_set = parse(
-            self.name.clone(),
-            text.into(),
-            self.funcs.keys().cloned().collect(),
-        )?;
+        let tree_set = self.parse_trees(self.name.clone(), text.into())?;
         self.tree_set.extend(tree_set);
         Ok(())
     }
@@ -113,14 +477,84 @@ impl Template {
         name: N,
         text: T,
     ) -> Result<(), String> {
-        let tree_set = parse(
-            name.into(),
-            text.into(),
-            self.funcs.keys().cloned().collect(),
-        )?;
+        let tree_set = self.parse_trees(name.into(), text.into())?;
         self.tree_set.extend(tree_set);
         Ok(())
     }
+
+    /// Sets the delimiters of actions for subsequent calls to parse. Empty delimiters
+    /// stand for the defaults `{{` and `}}`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::{Context, Template};
+    ///
+    /// let mut tmpl = Template::default();
+    /// tmpl.set_delims("[[", "]]");
+    /// tmpl.parse("{{ [[ . ]] }}").unwrap();
+    /// let output = tmpl.render(&Context::from("x").unwrap());
+    /// assert_eq!(&output.unwrap(), "{{ x }}");
+    /// ```
+    pub fn set_delims<L: Into<String>, R: Into<String>>(&mut self, left: L, right: R) {
+        let (left, right) = (left.into(), right.into());
+        self.delims = (
+            if left.is_empty() {
+                String::from("{{")
+            } else {
+                left
+            },
+            if right.is_empty() {
+                String::from("}}")
+            } else {
+                right
+            },
+        );
+    }
+
+    /// Sets how missing keys of maps are handled during execution, like golang's
+    /// `missingkey` option.
+    pub fn set_missing_key(&mut self, missing_key: MissingKey) {
+        self.missing_key = missing_key;
+    }
+
+    /// Sets how deep `{{ template }}`, `include` and `tpl` calls may be nested before
+    /// execution fails, 150 by default. Independent of the depth, execution and
+    /// [`check`](#method.check) fail before using more than 1 MiB of the stack, so they
+    /// don't overflow threads with the default stack size of 2 MiB. A level takes up to
+    /// about 5.5 KiB in debug builds and 2.5 KiB in release builds, more with deeply nested
+    /// template bodies.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use gtmpl::{Context, Template};
+    ///
+    /// let mut tmpl = Template::default();
+    /// tmpl.set_max_depth(10);
+    /// tmpl.parse(r#"{{ define "loop" }}{{ template "loop" }}{{ end }}{{ template "loop" }}"#)
+    ///     .unwrap();
+    /// let output = tmpl.render(&Context::empty());
+    /// assert_eq!(output.unwrap_err(), "exceeded maximum template depth (10)");
+    /// ```
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Parses `text` with the functions and delimiters of this template.
+    pub(crate) fn parse_trees(
+        &self,
+        name: String,
+        text: String,
+    ) -> Result<HashMap<String, Tree>, String> {
+        parse_with_delims(
+            name,
+            text,
+            self.funcs.keys().cloned().collect(),
+            &self.delims.0,
+            &self.delims.1,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -133,4 +567,19 @@ mod tests_mocked {
         assert!(t.parse(r#"{{ if eq "bar" "bar" }} 2000 {{ end }}"#).is_ok());
         assert!(t.tree_set.contains_key("foo"));
     }
+
+    #[test]
+    fn test_delims() {
+        let mut t = Template::default();
+        t.set_delims("<%", "%>");
+        assert!(t
+            .parse("{{ x }} <%- /* c */ -%> <% if . -%> a <%- end %>")
+            .is_ok());
+        let out = t.render(&crate::Context::from(true).unwrap());
+        assert_eq!(out, Ok(String::from("{{ x }}a")));
+        t.set_delims("", "");
+        assert!(t.parse("<% . %>{{ . }}").is_ok());
+        let out = t.render(&crate::Context::from(1).unwrap());
+        assert_eq!(out, Ok(String::from("<% . %>1")));
+    }
 }
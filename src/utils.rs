@@ -1,5 +1,6 @@
-use gtmpl_value::Value;
+use gtmpl_value::{Number, Value};
 use std::char;
+use std::fmt::Write;
 
 pub fn unquote_char(s: &str, quote: char) -> Option<char> {
     if s.len() < 2 || !s.starts_with(quote) || !s.ends_with(quote) {
@@ -108,6 +109,165 @@ fn extract_bytes_x(s: &str) -> Option<(String, usize)> {
     String::from_utf8(bytes).ok().map(|s| (s, i))
 }
 
+/// Quotes a string like golang's `strconv.Quote`. If `ascii` is set all non ASCII characters
+/// are escaped as well (`strconv.QuoteToASCII`).
+pub fn quote_str(s: &str, ascii: bool) -> String {
+    quote_with(s, '"', ascii)
+}
+
+/// Quotes a character like golang's `strconv.QuoteRune` or `strconv.QuoteRuneToASCII`.
+pub fn quote_char(c: char, ascii: bool) -> String {
+    quote_with(c.encode_utf8(&mut [0; 4]), '\'', ascii)
+}
+
+fn quote_with(s: &str, quote: char, ascii: bool) -> String {
+    let mut r = String::with_capacity(s.len() + 2);
+    r.push(quote);
+    for c in s.chars() {
+        if c == quote || c == '\\' {
+            r.push('\\');
+            r.push(c);
+            continue;
+        }
+        if is_print(c) && (!ascii || c.is_ascii()) {
+            r.push(c);
+            continue;
+        }
+        match c {
+            '\u{7}' => r.push_str(r"\a"),
+            '\u{8}' => r.push_str(r"\b"),
+            '\u{c}' => r.push_str(r"\f"),
+            '\n' => r.push_str(r"\n"),
+            '\r' => r.push_str(r"\r"),
+            '\t' => r.push_str(r"\t"),
+            '\u{b}' => r.push_str(r"\v"),
+            c if c < ' ' || c == '\u{7f}' => write!(r, r"\x{:02x}", c as u32).unwrap(),
+            c if (c as u32) < 0x10000 => write!(r, r"\u{:04x}", c as u32).unwrap(),
+            c => write!(r, r"\U{:08x}", c as u32).unwrap(),
+        }
+    }
+    r.push(quote);
+    r
+}
+
+/// Approximates golang's `strconv.IsPrint`: letters, marks, numbers, punctuation, symbols and
+/// the ASCII space.
+pub fn is_print(c: char) -> bool {
+    if c == ' ' {
+        return true;
+    }
+    !c.is_control()
+        && !c.is_whitespace()
+        && !matches!(c,
+            '\u{ad}'
+            | '\u{600}'..='\u{605}'
+            | '\u{61c}'
+            | '\u{6dd}'
+            | '\u{70f}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{206f}'
+            | '\u{e000}'..='\u{f8ff}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+            | '\u{f0000}'..='\u{10ffff}')
+}
+
+/// The representation of a `Number`, which `gtmpl_value` keeps private.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Num {
+    U(u64),
+    I(i64),
+    F(f64),
+}
+
+/// Recovers the representation of a `Number`. `gtmpl_value` stores integral floats as
+/// integers, so only numbers with a fractional part, beyond the range of `i64` and `u64`
+/// (or `NaN` and infinities) are floats.
+pub fn to_num(n: &Number) -> Num {
+    if let Some(f) = n.as_f64() {
+        // Integers never convert to floats outside of [-2^63, 2^64), which is where a
+        // saturating `as_u64` or `as_i64` would lose the value.
+        let in_range = f >= i64::MIN as f64 && f < u64::MAX as f64;
+        if !f.is_finite() || f.fract() != 0.0 || !in_range {
+            return Num::F(f);
+        }
+    }
+    if let Some(u) = n.as_u64() {
+        return Num::U(u);
+    }
+    if let Some(i) = n.as_i64() {
+        return Num::I(i);
+    }
+    Num::F(n.as_f64().unwrap_or(f64::NAN))
+}
+
+/// Returns the textual representation of a value like golang's `fmt.Sprint` would print it.
+#[cfg(any(
+    feature = "defaults",
+    feature = "dicts",
+    feature = "encoding",
+    feature = "host",
+    feature = "include",
+    feature = "json",
+    feature = "lists",
+    feature = "math",
+    feature = "random",
+    feature = "regex",
+    feature = "semver",
+    feature = "strings",
+    feature = "time",
+    feature = "yaml",
+))]
+pub fn strval(val: &Value) -> String {
+    match *val {
+        Value::String(ref s) => s.clone(),
+        _ => crate::print_verb::print(&Default::default(), 'v', val),
+    }
+}
+
+/// Converts a value to an integer. Floats are truncated and strings are parsed.
+#[cfg(any(
+    feature = "lists",
+    feature = "math",
+    feature = "random",
+    feature = "regex",
+    feature = "strings",
+    feature = "time",
+))]
+pub fn to_int(val: &Value) -> Option<i64> {
+    match *val {
+        Value::Number(ref n) => match to_num(n) {
+            Num::U(u) if u <= i64::MAX as u64 => Some(u as i64),
+            Num::I(i) => Some(i),
+            Num::F(f) if f.is_finite() && f.abs() < 9.2e18 => Some(f.trunc() as i64),
+            _ => None,
+        },
+        Value::String(ref s) => s.trim().parse().ok(),
+        Value::Bool(b) => Some(b as i64),
+        Value::Nil => Some(0),
+        _ => None,
+    }
+}
+
+/// Stack a template execution or check may use. The rest of a thread with the default stack
+/// size of 2 MiB is left to the caller and the frames between two checks.
+pub const STACK_BUDGET: usize = 1 << 20;
+
+/// Approximates the current position of the stack by the address of a local variable.
+#[inline(never)]
+pub fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Returns whether more than `STACK_BUDGET` of the stack is used since `start`, a
+/// `stack_position`. Stacks grow downwards on most but not all platforms.
+pub fn stack_exhausted(start: usize) -> bool {
+    stack_position().abs_diff(start) > STACK_BUDGET
+}
+
 /// Returns
 pub fn is_true(val: &Value) -> bool {
     match *val {
@@ -176,6 +336,39 @@ mod tests {
         assert_eq!(u, Some("Fran & Freddie's Diner\t☺".to_owned()));
     }
 
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote_str("foo\"bar'\n", false), r#""foo\"bar'\n""#);
+        assert_eq!(quote_str("☺\u{7f}\u{200b}", false), r#""☺\x7f\u200b""#);
+        assert_eq!(quote_str("☺💩", true), r#""\u263a\U0001f4a9""#);
+        assert_eq!(quote_char('\'', false), r"'\''");
+        assert_eq!(quote_char('"', false), r#"'"'"#);
+    }
+
+    #[test]
+    fn test_to_num() {
+        assert_eq!(to_num(&Number::from(23u8)), Num::U(23));
+        assert_eq!(to_num(&Number::from(-23i8)), Num::I(-23));
+        assert_eq!(to_num(&Number::from(23.5f64)), Num::F(23.5));
+        assert_eq!(to_num(&Number::from(u64::MAX)), Num::U(u64::MAX));
+        assert_eq!(to_num(&Number::from(i64::MIN)), Num::I(i64::MIN));
+        assert_eq!(
+            to_num(&Number::from(1e19f64)),
+            Num::U(10_000_000_000_000_000_000)
+        );
+        assert_eq!(
+            to_num(&Number::from(-1e18f64)),
+            Num::I(-1_000_000_000_000_000_000)
+        );
+        assert_eq!(to_num(&Number::from(1e300f64)), Num::F(1e300));
+        assert_eq!(to_num(&Number::from(-1e300f64)), Num::F(-1e300));
+        assert_eq!(to_num(&Number::from(2f64.powi(64))), Num::F(2f64.powi(64)));
+        assert_eq!(
+            to_num(&Number::from(-(2f64.powi(63)) - 2048.0)),
+            Num::F(-(2f64.powi(63)) - 2048.0)
+        );
+    }
+
     #[test]
     fn test_is_true() {
         let t = Value::from(1i8);
@@ -0,0 +1,133 @@
+//! Conversions between `serde_yaml::Value` and `gtmpl::Value`.
+//!
+//! YAML mappings become `Value::Map` with their keys converted to strings, `null` becomes
+//! `Value::Nil` and tags are dropped. When converting back mapping keys are sorted, so the
+//! output is stable.
+//!
+//! ## Example
+//! ```rust
+//! use gtmpl::{Context, Template};
+//!
+//! let mut tmpl = Template::default();
+//! tmpl.parse("{{ .name }}: {{ range .ports }}{{ . }} {{ end }}").unwrap();
+//! let ctx = Context::from_yaml_str("name: web\nports: [80, 443]").unwrap();
+//! let output = tmpl.render(&ctx);
+//! assert_eq!(&output.unwrap(), "web: 80 443 ");
+//! ```
+extern crate serde_yaml;
+
+use std::collections::HashMap;
+
+use gtmpl_value::Value;
+
+use self::serde_yaml::{Mapping, Number, Value as Yaml};
+use crate::exec::Context;
+use crate::utils::{to_num, Num};
+
+/// Converts a YAML value into a template value.
+pub fn yaml_to_value(yaml: Yaml) -> Value {
+    match yaml {
+        Yaml::Null => Value::Nil,
+        Yaml::Bool(b) => Value::Bool(b),
+        Yaml::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else {
+                Value::from(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        Yaml::String(s) => Value::String(s),
+        Yaml::Sequence(a) => Value::Array(a.into_iter().map(yaml_to_value).collect()),
+        Yaml::Mapping(m) => Value::Map(
+            m.into_iter()
+                .map(|(k, v)| (key_to_string(k), yaml_to_value(v)))
+                .collect::<HashMap<String, Value>>(),
+        ),
+        Yaml::Tagged(t) => yaml_to_value(t.value),
+    }
+}
+
+fn key_to_string(key: Yaml) -> String {
+    match key {
+        Yaml::String(s) => s,
+        Yaml::Null => String::from("null"),
+        Yaml::Bool(b) => b.to_string(),
+        Yaml::Number(n) => n.to_string(),
+        k => serde_yaml::to_string(&k)
+            .map(|s| s.trim_end().to_owned())
+            .unwrap_or_default(),
+    }
+}
+
+/// Converts a template value into a YAML value. Functions can not be represented in YAML and
+/// fail.
+pub fn value_to_yaml(val: &Value) -> Result<Yaml, String> {
+    Ok(match *val {
+        Value::NoValue | Value::Nil => Yaml::Null,
+        Value::Bool(b) => Yaml::Bool(b),
+        Value::String(ref s) => Yaml::String(s.clone()),
+        Value::Number(ref n) => Yaml::Number(match to_num(n) {
+            Num::U(u) => Number::from(u),
+            Num::I(i) => Number::from(i),
+            Num::F(f) => Number::from(f),
+        }),
+        Value::Array(ref a) => Yaml::Sequence(
+            a.iter()
+                .map(value_to_yaml)
+                .collect::<Result<Vec<_>, String>>()?,
+        ),
+        Value::Object(ref o) | Value::Map(ref o) => {
+            let mut keys: Vec<&String> = o.keys().collect();
+            keys.sort();
+            let mut m = Mapping::new();
+            for k in keys {
+                m.insert(Yaml::String(k.clone()), value_to_yaml(&o[k])?);
+            }
+            Yaml::Mapping(m)
+        }
+        Value::Function(_) => return Err(String::from("unsupported type in YAML: func")),
+    })
+}
+
+impl Context {
+    /// Creates a context from a YAML document.
+    pub fn from_yaml_str(s: &str) -> Result<Context, String> {
+        let yaml: Yaml = serde_yaml::from_str(s).map_err(|e| format!("invalid YAML: {}", e))?;
+        Ok(Context::from_value(yaml_to_value(yaml)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_yaml_to_value() {
+        let yaml: Yaml = serde_yaml::from_str("a: ~\nb: [true, x]\n1: -1\nd: !foo 1.5").unwrap();
+        let mut m = HashMap::new();
+        m.insert("a".to_owned(), Value::Nil);
+        m.insert("b".to_owned(), Value::Array(vec![true.into(), "x".into()]));
+        m.insert("1".to_owned(), (-1).into());
+        m.insert("d".to_owned(), 1.5.into());
+        assert_eq!(yaml_to_value(yaml), Value::Map(m));
+    }
+
+    #[test]
+    fn test_sorted_keys() {
+        let mut m = HashMap::new();
+        for k in &["c", "a", "b"] {
+            m.insert(k.to_string(), Value::from(*k));
+        }
+        let yaml = value_to_yaml(&Value::Map(m)).unwrap();
+        assert_eq!(serde_yaml::to_string(&yaml).unwrap(), "a: a\nb: b\nc: c\n");
+    }
+
+    #[test]
+    fn test_from_yaml_str() {
+        let ctx = Context::from_yaml_str("[18446744073709551615]").unwrap();
+        assert_eq!(ctx.dot, Value::from(vec![u64::MAX]));
+        assert!(Context::from_yaml_str("a: [").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+#![cfg(feature = "cli")]
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn setup(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gtmpl-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("partials/sub")).unwrap();
+    dir
+}
+
+fn gtmpl(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gtmpl"))
+        .args(args)
+        .env("GTMPL_TEST", "from env")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_render_file() {
+    let dir = setup("file");
+    fs::write(
+        dir.join("values.json"),
+        r#"{"app": {"name": "web", "port": 80}}"#,
+    )
+    .unwrap();
+    fs::write(dir.join("values.yaml"), "app:\n  port: 8080\n").unwrap();
+    fs::write(
+        dir.join("main.tmpl"),
+        "{{ .app.name }}:{{ .app.port }} {{ .app.debug }} {{ .Env.GTMPL_TEST | upper }}",
+    )
+    .unwrap();
+    let out = dir.join("out.txt");
+    let output = gtmpl(
+        &[
+            "-d",
+            dir.join("values.json").to_str().unwrap(),
+            "--data",
+            dir.join("values.yaml").to_str().unwrap(),
+            "--set",
+            "app.debug=true",
+            "-o",
+            out.to_str().unwrap(),
+            dir.join("main.tmpl").to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(out).unwrap(), "web:8080 true FROM ENV");
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_render_stdin() {
+    let dir = setup("stdin");
+    fs::write(dir.join("partials/sub/name.tmpl"), "[[ .name ]]").unwrap();
+    fs::write(
+        dir.join("partials/defs.tmpl"),
+        r#"[[ define "greet" ]]Hello [[ template "sub/name.tmpl" . ]][[ end ]]"#,
+    )
+    .unwrap();
+    let output = gtmpl(
+        &[
+            "--template-dir",
+            dir.join("partials").to_str().unwrap(),
+            "--left-delim=[[",
+            "--right-delim=]]",
+            "--set",
+            "name=World",
+        ],
+        r#"{{ [[ template "greet" . ]] }}"#,
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "{{ Hello World }}");
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_errors() {
+    let output = gtmpl(&["--missingkey", "error", "-"], "{{ .missing }}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("map has no entry for key missing"));
+    let output = gtmpl(&["--missingkey", "zero"], "{{ .missing }}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "nil");
+    let output = gtmpl(&["--unknown"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_render_dir() {
+    let dir = setup("dir");
+    let input = dir.join("in");
+    fs::create_dir_all(input.join("conf/old")).unwrap();
+    fs::write(
+        input.join("_helpers.tmpl"),
+        r#"{{ define "header" }}# {{ .name }}{{ end }}"#,
+    )
+    .unwrap();
+    fs::write(
+        input.join("conf/app.ini.tmpl"),
+        "{{ template \"header\" . }}\nport={{ .port }}\n",
+    )
+    .unwrap();
+    fs::write(input.join("run.sh.tmpl"), "#!/bin/sh\necho {{ .name }}\n").unwrap();
+    fs::write(input.join("conf/old/app.ini.tmpl"), "{{ .name }}").unwrap();
+    fs::write(input.join("notes.bak"), "{{").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::Permissions::from_mode(0o750);
+        fs::set_permissions(input.join("run.sh.tmpl"), mode).unwrap();
+    }
+    let output = dir.join("out");
+    let result = gtmpl(
+        &[
+            "--input-dir",
+            input.to_str().unwrap(),
+            "--output-dir",
+            output.to_str().unwrap(),
+            "--exclude",
+            "*.bak",
+            "--exclude",
+            "conf/old",
+            "--output-map",
+            r#"{{ if eq .in "_helpers.tmpl" }}helpers.txt{{ else }}{{ .in | trimSuffix ".tmpl" }}{{ end }}"#,
+            "--set",
+            "name=app",
+            "--set",
+            "port=8080",
+        ],
+        "",
+    );
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("conf/app.ini")).unwrap(),
+        "# app\nport=8080\n"
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("run.sh")).unwrap(),
+        "#!/bin/sh\necho app\n"
+    );
+    assert_eq!(fs::read_to_string(output.join("helpers.txt")).unwrap(), "");
+    assert!(!output.join("conf/old").exists());
+    assert!(!output.join("notes.bak").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(output.join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+
+    let result = gtmpl(
+        &[
+            "--input-dir",
+            input.to_str().unwrap(),
+            "--output-dir",
+            output.to_str().unwrap(),
+            "--exclude",
+            "*.bak",
+            "--output-map",
+            "../{{ .in }}",
+        ],
+        "",
+    );
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&result.stderr)
+        .contains("is not a relative path below the output directory"));
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_render_dir_outputs() {
+    let dir = setup("outputs");
+    let input = dir.join("in");
+    let output = dir.join("out");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("a.tmpl"), "a").unwrap();
+    fs::write(input.join("b.tmpl"), "b").unwrap();
+    let render = |output_map: &str| {
+        gtmpl(
+            &[
+                "--input-dir",
+                input.to_str().unwrap(),
+                "--output-dir",
+                output.to_str().unwrap(),
+                "--output-map",
+                output_map,
+            ],
+            "",
+        )
+    };
+    let result = render("same");
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&result.stderr)
+        .contains("b.tmpl: output path \"same\" is also used for a.tmpl"));
+    assert!(!output.exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: PathBuf| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        fs::set_permissions(input.join("a.tmpl"), fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(input.join("b.tmpl"), fs::Permissions::from_mode(0o600)).unwrap();
+        for _ in 0..2 {
+            let result = render("{{ .in }}");
+            assert!(
+                result.status.success(),
+                "{}",
+                String::from_utf8_lossy(&result.stderr)
+            );
+        }
+        assert_eq!(fs::read_to_string(output.join("a.tmpl")).unwrap(), "a");
+        assert_eq!(mode(output.join("a.tmpl")), 0o444);
+        assert_eq!(mode(output.join("b.tmpl")), 0o600);
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 2);
+    }
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_template_dirs() {
+    let dir = setup("tdirs");
+    let partials = dir.join("p[1]");
+    fs::create_dir_all(partials.join(".git")).unwrap();
+    fs::create_dir_all(dir.join("empty")).unwrap();
+    fs::write(partials.join("name.tmpl"), "{{ .name }}").unwrap();
+    fs::write(partials.join(".name.tmpl.swp"), [0xff, 0xfe]).unwrap();
+    fs::write(partials.join(".git/config"), "{{").unwrap();
+    let output = gtmpl(
+        &[
+            "-t",
+            partials.to_str().unwrap(),
+            "-t",
+            dir.join("empty").to_str().unwrap(),
+            "--set",
+            "name=World",
+        ],
+        r#"Hello {{ template "name.tmpl" . }}"#,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hello World");
+    let output = gtmpl(&["-t", dir.join("missing").to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(1));
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_set_and_fail() {
+    let output = gtmpl(
+        &["--set", "tag=1.10", "--set", "replicas=3"],
+        r#"{{ .tag }} {{ add .replicas 1 }} {{ printf "%T" .tag }}"#,
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1.10 4 string");
+    let output = gtmpl(&[], r#"{{ fail "boom" }}"#);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "gtmpl: boom\n");
+}